@@ -0,0 +1,641 @@
+//! A GDB remote serial protocol server, so gdb/lldb can debug the emulated program:
+//!   retrowin32 --gdb=1234 foo.exe
+//!   gdb -ex 'target remote :1234'
+//!
+//! Breakpoints (Z0) patch the instruction cache like the web debugger's, and
+//! watchpoints (Z2/Z3/Z4) map onto memory watchpoints.  Memory writes (M) into
+//! code aren't seen by already-decoded instructions.
+
+use anyhow::bail;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Instructions to run between checks for an interrupt from gdb.
+const BATCH: usize = 10000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>i386</architecture>
+  <feature name="org.gnu.gdb.i386.core">
+    <reg name="eax" bitsize="32" type="int32"/>
+    <reg name="ecx" bitsize="32" type="int32"/>
+    <reg name="edx" bitsize="32" type="int32"/>
+    <reg name="ebx" bitsize="32" type="int32"/>
+    <reg name="esp" bitsize="32" type="data_ptr"/>
+    <reg name="ebp" bitsize="32" type="data_ptr"/>
+    <reg name="esi" bitsize="32" type="int32"/>
+    <reg name="edi" bitsize="32" type="int32"/>
+    <reg name="eip" bitsize="32" type="code_ptr"/>
+    <reg name="eflags" bitsize="32" type="int32"/>
+    <reg name="cs" bitsize="32" type="int32"/>
+    <reg name="ss" bitsize="32" type="int32"/>
+    <reg name="ds" bitsize="32" type="int32"/>
+    <reg name="es" bitsize="32" type="int32"/>
+    <reg name="fs" bitsize="32" type="int32"/>
+    <reg name="gs" bitsize="32" type="int32"/>
+    <reg name="st0" bitsize="80" type="i387_ext"/>
+    <reg name="st1" bitsize="80" type="i387_ext"/>
+    <reg name="st2" bitsize="80" type="i387_ext"/>
+    <reg name="st3" bitsize="80" type="i387_ext"/>
+    <reg name="st4" bitsize="80" type="i387_ext"/>
+    <reg name="st5" bitsize="80" type="i387_ext"/>
+    <reg name="st6" bitsize="80" type="i387_ext"/>
+    <reg name="st7" bitsize="80" type="i387_ext"/>
+    <reg name="fctrl" bitsize="32" type="int" group="float"/>
+    <reg name="fstat" bitsize="32" type="int" group="float"/>
+    <reg name="ftag" bitsize="32" type="int" group="float"/>
+    <reg name="fiseg" bitsize="32" type="int" group="float"/>
+    <reg name="fioff" bitsize="32" type="int" group="float"/>
+    <reg name="foseg" bitsize="32" type="int" group="float"/>
+    <reg name="fooff" bitsize="32" type="int" group="float"/>
+    <reg name="fop" bitsize="32" type="int" group="float"/>
+  </feature>
+</target>
+"#;
+
+/// Number of registers in TARGET_XML.
+const REG_COUNT: usize = 32;
+const REG_ST0: usize = 16;
+const REG_FCTRL: usize = 24;
+
+fn f64_to_f80(x: f64) -> [u8; 10] {
+    let bits = x.to_bits();
+    let sign = (bits >> 63) as u16;
+    let exp = ((bits >> 52) & 0x7ff) as i32;
+    let frac = bits & ((1 << 52) - 1);
+    let (exp, mant) = match exp {
+        0 if frac == 0 => (0, 0),
+        0 => {
+            // Subnormal: normalize, making the integer bit explicit.
+            let lz = frac.leading_zeros();
+            (-1074 + (63 - lz as i32) + 16383, frac << lz)
+        }
+        0x7ff => (0x7fff, (1 << 63) | (frac << 11)),
+        _ => (exp - 1023 + 16383, (1 << 63) | (frac << 11)),
+    };
+    let mut out = [0; 10];
+    out[..8].copy_from_slice(&mant.to_le_bytes());
+    out[8..].copy_from_slice(&((sign << 15) | exp as u16).to_le_bytes());
+    out
+}
+
+fn f80_to_f64(bytes: &[u8]) -> f64 {
+    let mant = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let se = u16::from_le_bytes(bytes[8..10].try_into().unwrap());
+    let sign = if se & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = (se & 0x7fff) as i32;
+    if exp == 0x7fff {
+        return if mant << 1 == 0 {
+            sign * f64::INFINITY
+        } else {
+            f64::NAN
+        };
+    }
+    // Scale in steps: 2^exp alone underflows for results that are f64 subnormals.
+    let mut x = mant as f64;
+    let mut exp = exp - 16383 - 63;
+    while exp < -1000 {
+        x *= 2f64.powi(-1000);
+        exp += 1000;
+    }
+    sign * x * 2f64.powi(exp)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(text: &str) -> anyhow::Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        bail!("odd-length hex {text:?}");
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&text[i..i + 2], 16)?))
+        .collect()
+}
+
+fn parse_hex(text: &str) -> anyhow::Result<u32> {
+    Ok(u32::from_str_radix(text, 16)?)
+}
+
+/// Parses "addr,len" into hex numbers.
+fn parse_addr_len(text: &str) -> anyhow::Result<(u32, u32)> {
+    let (addr, len) = text
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("expected addr,len in {text:?}"))?;
+    Ok((parse_hex(addr)?, parse_hex(len)?))
+}
+
+fn get_reg(regs: &win32::Registers, n: usize) -> Vec<u8> {
+    let u32 = |v: u32| v.to_le_bytes().to_vec();
+    match n {
+        0..=7 => u32([
+            regs.eax, regs.ecx, regs.edx, regs.ebx, regs.esp, regs.ebp, regs.esi, regs.edi,
+        ][n]),
+        8 => u32(regs.eip),
+        9 => u32(regs.flags.bits()),
+        10..=15 => u32([regs.cs, regs.ss, regs.ds, regs.es, regs.fs, regs.gs][n - 10] as u32),
+        REG_ST0..=23 => {
+            let i = regs.st_top + n - REG_ST0;
+            f64_to_f80(if i < 8 { regs.st[i] } else { 0.0 }).to_vec()
+        }
+        REG_FCTRL => u32(regs.fpu_control as u32),
+        25 => u32(regs.fpu_status.bits() as u32 | ((regs.st_top as u32 & 7) << 11)),
+        26 => {
+            // Tag word, by physical register: 0b11 (empty) below the stack top.
+            let tags = (0..regs.st_top.min(8)).fold(0, |tags, i| tags | (0b11 << (i * 2)));
+            u32(tags)
+        }
+        _ => u32(0),
+    }
+}
+
+fn set_reg(regs: &mut win32::Registers, n: usize, bytes: &[u8]) -> anyhow::Result<()> {
+    let len = if (REG_ST0..REG_FCTRL).contains(&n) {
+        10
+    } else {
+        4
+    };
+    if bytes.len() != len {
+        bail!("register {n} wants {len} bytes, got {}", bytes.len());
+    }
+    if len == 10 {
+        let i = regs.st_top + n - REG_ST0;
+        if i < 8 {
+            regs.st[i] = f80_to_f64(bytes);
+        }
+        return Ok(());
+    }
+    let value = u32::from_le_bytes(bytes.try_into().unwrap());
+    match n {
+        0 => regs.eax = value,
+        1 => regs.ecx = value,
+        2 => regs.edx = value,
+        3 => regs.ebx = value,
+        4 => regs.esp = value,
+        5 => regs.ebp = value,
+        6 => regs.esi = value,
+        7 => regs.edi = value,
+        8 => regs.eip = value,
+        9 => regs.flags = win32::Flags::from_bits_truncate(value),
+        10 => regs.cs = value as u16,
+        11 => regs.ss = value as u16,
+        12 => regs.ds = value as u16,
+        13 => regs.es = value as u16,
+        14 => regs.fs = value as u16,
+        15 => regs.gs = value as u16,
+        REG_FCTRL => regs.fpu_control = value as u16,
+        // The stack top is tracked separately, so only take the condition bits.
+        25 => regs.fpu_status = win32::FPUStatus::from_bits_truncate(value as u16),
+        // Other FPU control registers aren't modeled.
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Encodes all registers for a 'g' reply, in TARGET_XML order.
+fn get_regs(regs: &win32::Registers) -> String {
+    (0..REG_COUNT).map(|n| hex(&get_reg(regs, n))).collect()
+}
+
+/// Decodes a 'G' packet's register bytes, stopping early if gdb sent fewer.
+fn set_regs(regs: &mut win32::Registers, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut rest = bytes;
+    for n in 0..REG_COUNT {
+        let len = get_reg(regs, n).len();
+        if rest.len() < len {
+            break;
+        }
+        set_reg(regs, n, &rest[..len])?;
+        rest = &rest[len..];
+    }
+    Ok(())
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Wraps packet contents as "$data#xx".
+fn frame(data: &str) -> String {
+    format!("${data}#{:02x}", checksum(data.as_bytes()))
+}
+
+/// Whether the two hex digits after '#' match the packet contents.
+fn checksum_ok(data: &[u8], digits: &[u8]) -> bool {
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|text| u8::from_str_radix(text, 16).ok())
+        == Some(checksum(data))
+}
+
+struct Conn {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Set by QStartNoAckMode.
+    no_ack: bool,
+}
+
+impl Conn {
+    fn read_byte(&mut self) -> anyhow::Result<Option<u8>> {
+        let mut byte = [0];
+        Ok(match self.reader.read(&mut byte)? {
+            0 => None,
+            _ => Some(byte[0]),
+        })
+    }
+
+    /// Reads the next packet's contents, or None when gdb disconnects.
+    fn read_packet(&mut self) -> anyhow::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => break,
+                // Acks, and interrupts while already stopped.
+                Some(_) => continue,
+            }
+        }
+        let mut data = Vec::new();
+        self.reader.read_until(b'#', &mut data)?;
+        if data.pop() != Some(b'#') {
+            return Ok(None);
+        }
+        let mut digits = [0; 2];
+        self.reader.read_exact(&mut digits)?;
+        let ok = checksum_ok(&data, &digits);
+        if !self.no_ack {
+            self.writer.write_all(if ok { b"+" } else { b"-" })?;
+        }
+        if !ok {
+            log::warn!("gdb: bad checksum");
+            return self.read_packet();
+        }
+        Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+    }
+
+    fn send_packet(&mut self, data: &str) -> anyhow::Result<()> {
+        let packet = frame(data);
+        loop {
+            self.writer.write_all(packet.as_bytes())?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Checks, without blocking, whether gdb sent an interrupt (ctl-c).
+    fn interrupted(&mut self) -> anyhow::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let filled = self.reader.fill_buf().map(|_| ());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match filled {
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+                result => result?,
+            }
+        }
+        if self.reader.buffer().first() == Some(&0x03) {
+            self.reader.consume(1);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+struct Server<'a> {
+    runner: &'a mut win32::Runner,
+    conn: Conn,
+    /// Called between batches of instructions; returns the exit code once the program exits.
+    poll: &'a mut dyn FnMut() -> Option<u32>,
+    /// Reply to '?', describing the last stop.
+    last_stop: String,
+    /// Set once gdb asks to stop acking packets, which takes effect after our reply.
+    start_no_ack: bool,
+}
+
+impl<'a> Server<'a> {
+    fn stop_reply(&mut self) -> String {
+        if let Some(code) = (self.poll)() {
+            return format!("W{:02x}", code & 0xFF);
+        }
+        match &self.runner.stop_reason {
+            Some(win32::StopReason::Interrupt) | None => "T05swbreak:;".into(),
+            Some(win32::StopReason::Watchpoint(hit)) => {
+                let kind = match hit.watchpoint.kind {
+                    win32::WatchKind::Read => "rwatch",
+                    win32::WatchKind::Write => "watch",
+                    win32::WatchKind::Access => "awatch",
+                };
+                let addr = hit.addr.max(hit.watchpoint.addrs.start);
+                format!("T05{kind}:{addr:x};")
+            }
+        }
+    }
+
+    /// Runs one instruction, or until something stops execution.
+    fn resume(&mut self, single_step: bool) -> anyhow::Result<String> {
+        // Run through a breakpoint at the current position.
+        self.runner.resume();
+        loop {
+            let count = if single_step { 1 } else { BATCH };
+            match self.runner.step_many(count) {
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    return Ok("S0b".into());
+                }
+                Ok(n) if n < count => return Ok(self.stop_reply()),
+                Ok(_) if single_step => return Ok("S05".into()),
+                Ok(_) => {}
+            }
+            if let Some(code) = (self.poll)() {
+                return Ok(format!("W{:02x}", code & 0xFF));
+            }
+            if self.conn.interrupted()? {
+                return Ok("S02".into());
+            }
+        }
+    }
+
+    fn watchpoint(kind: &str, addr: u32, len: u32) -> Option<win32::Watchpoint> {
+        let kind = match kind {
+            "2" => win32::WatchKind::Write,
+            "3" => win32::WatchKind::Read,
+            "4" => win32::WatchKind::Access,
+            _ => return None,
+        };
+        Some(win32::Watchpoint {
+            addrs: addr..addr.saturating_add(len.max(1)),
+            kind,
+        })
+    }
+
+    /// Handles a Z (insert) or z (remove) packet.
+    fn breakpoint(&mut self, insert: bool, args: &str) -> anyhow::Result<String> {
+        let mut parts = args.split(',');
+        let (Some(kind), Some(addr), Some(len)) = (parts.next(), parts.next(), parts.next()) else {
+            bail!("bad breakpoint packet {args:?}");
+        };
+        let (addr, len) = (parse_hex(addr)?, parse_hex(len.split(';').next().unwrap())?);
+        if kind == "0" {
+            // gdb may ask for any address, e.g. one mid-instruction or in a DLL that
+            // isn't loaded yet.
+            let ok = if insert {
+                self.runner.add_breakpoint(addr, Default::default())
+            } else {
+                self.runner.clear_breakpoint(addr)
+            };
+            return Ok(if ok { "OK" } else { "E01" }.into());
+        }
+        let Some(watchpoint) = Self::watchpoint(kind, addr, len) else {
+            // Unsupported kind, e.g. hardware execution breakpoints.
+            return Ok(String::new());
+        };
+        if insert {
+            self.runner.add_watchpoint(watchpoint);
+        } else {
+            self.runner.clear_watchpoint(&watchpoint);
+        }
+        Ok("OK".into())
+    }
+
+    fn query(&self, packet: &str) -> anyhow::Result<String> {
+        if packet.starts_with("qSupported") {
+            return Ok("PacketSize=4000;qXfer:features:read+;swbreak+;QStartNoAckMode+".into());
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = parse_addr_len(args)?;
+            let start = (offset as usize).min(TARGET_XML.len());
+            let end = (start + len as usize).min(TARGET_XML.len());
+            let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+            return Ok(format!("{more}{}", &TARGET_XML[start..end]));
+        }
+        Ok(match packet {
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            _ => "",
+        }
+        .into())
+    }
+
+    /// Returns the reply to a packet, or None to end the session.
+    fn handle(&mut self, packet: &str) -> anyhow::Result<Option<String>> {
+        let (cmd, args) = packet.split_at(packet.len().min(1));
+        let eip = self.runner.machine.x86.regs.eip;
+        let regs = &mut self.runner.machine.x86.regs;
+        let reply = match cmd {
+            "?" => self.last_stop.clone(),
+            "g" => get_regs(regs),
+            "G" => {
+                set_regs(regs, &unhex(args)?)?;
+                "OK".into()
+            }
+            "p" => match parse_hex(args)? as usize {
+                n if n < REG_COUNT => hex(&get_reg(regs, n)),
+                _ => "E01".into(),
+            },
+            "P" => {
+                let (n, value) = args
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("bad P packet"))?;
+                set_reg(regs, parse_hex(n)? as usize, &unhex(value)?)?;
+                "OK".into()
+            }
+            "m" => {
+                let (addr, len) = parse_addr_len(args)?;
+                match self
+                    .runner
+                    .machine
+                    .x86
+                    .mem
+                    .get(addr as usize..addr as usize + len as usize)
+                {
+                    Some(bytes) => hex(bytes),
+                    None => "E14".into(),
+                }
+            }
+            "M" => {
+                let (range, data) = args
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("bad M packet"))?;
+                let (addr, len) = parse_addr_len(range)?;
+                let data = unhex(data)?;
+                match self
+                    .runner
+                    .machine
+                    .x86
+                    .mem
+                    .get_mut(addr as usize..addr as usize + len as usize)
+                {
+                    Some(bytes) if bytes.len() == data.len() => {
+                        bytes.copy_from_slice(&data);
+                        "OK".into()
+                    }
+                    _ => "E14".into(),
+                }
+            }
+            "Z" | "z" => self.breakpoint(cmd == "Z", args)?,
+            "c" | "s" => {
+                if !args.is_empty() {
+                    self.runner.jmp(parse_hex(args)?)?;
+                }
+                let reply = self.resume(cmd == "s")?;
+                self.last_stop = reply.clone();
+                reply
+            }
+            "q" => self.query(packet)?,
+            "Q" if packet == "QStartNoAckMode" => {
+                self.start_no_ack = true;
+                "OK".into()
+            }
+            "H" | "T" => "OK".into(),
+            "D" => {
+                self.conn.send_packet("OK")?;
+                return Ok(None);
+            }
+            "k" => return Ok(None),
+            _ => String::new(),
+        };
+        if matches!(cmd, "G" | "P") && self.runner.machine.x86.regs.eip != eip {
+            self.runner.jmp(self.runner.machine.x86.regs.eip)?;
+        }
+        Ok(Some(reply))
+    }
+
+    fn serve(&mut self) -> anyhow::Result<()> {
+        while let Some(packet) = self.conn.read_packet()? {
+            log::debug!("gdb: {packet}");
+            let reply = match self.handle(&packet) {
+                Ok(Some(reply)) => reply,
+                Ok(None) => break,
+                Err(err) => {
+                    log::warn!("gdb: {packet:?}: {err}");
+                    "E01".into()
+                }
+            };
+            self.conn.send_packet(&reply)?;
+            if self.start_no_ack {
+                self.conn.no_ack = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Waits for gdb to connect on the given local port, then serves it until it detaches.
+pub fn serve(
+    runner: &mut win32::Runner,
+    port: u16,
+    poll: &mut dyn FnMut() -> Option<u32>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("waiting for gdb on port {port}");
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let conn = Conn {
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+        no_ack: false,
+    };
+    Server {
+        runner,
+        conn,
+        poll,
+        last_stop: "S05".into(),
+        start_no_ack: false,
+    }
+    .serve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f80_round_trip() {
+        for x in [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            std::f64::consts::PI,
+            1e300,
+            f64::MIN_POSITIVE,
+            f64::MIN_POSITIVE / 1024.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let f80 = f64_to_f80(x);
+            assert_eq!(f80_to_f64(&f80).to_bits(), x.to_bits(), "{x}");
+        }
+        assert!(f80_to_f64(&f64_to_f80(f64::NAN)).is_nan());
+    }
+
+    #[test]
+    fn f80_layout() {
+        // 1.0: explicit integer bit, biased exponent 0x3fff.
+        assert_eq!(f64_to_f80(1.0), [0, 0, 0, 0, 0, 0, 0, 0x80, 0xff, 0x3f]);
+        // -2.0: sign bit set, exponent one higher.
+        assert_eq!(f64_to_f80(-2.0), [0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0xc0]);
+        // The smallest subnormal f64, 2^-1074, normalized.
+        assert_eq!(
+            f64_to_f80(f64::from_bits(1)),
+            [0, 0, 0, 0, 0, 0, 0, 0x80, 0xcd, 0x3b]
+        );
+    }
+
+    #[test]
+    fn framing() {
+        assert_eq!(frame("OK"), "$OK#9a");
+        assert_eq!(frame(""), "$#00");
+        assert!(checksum_ok(b"OK", b"9a"));
+        assert!(checksum_ok(b"OK", b"9A"));
+        assert!(!checksum_ok(b"OK", b"9b"));
+        assert!(!checksum_ok(b"OK", b"zz"));
+        // The sum wraps: 0xff + 0x02 = 0x01.
+        assert_eq!(checksum(&[0xff, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn g_layout() {
+        let mut regs = win32::Registers::new();
+        regs.eax = 0x11223344;
+        regs.edi = 0x55667788;
+        regs.eip = 0x401000;
+        regs.fs = 0x3b;
+        regs.st_top = 7;
+        regs.st[7] = 1.0;
+        regs.fpu_control = 0x0e7f;
+        let g = unhex(&get_regs(&regs)).unwrap();
+        // 16 general registers, 8 st registers, 8 fpu control registers.
+        assert_eq!(g.len(), 16 * 4 + 8 * 10 + 8 * 4);
+        let u32_at = |ofs: usize| u32::from_le_bytes(g[ofs..ofs + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), 0x11223344);
+        assert_eq!(u32_at(7 * 4), 0x55667788);
+        assert_eq!(u32_at(8 * 4), 0x401000);
+        assert_eq!(u32_at(14 * 4), 0x3b);
+        assert_eq!(&g[64..74], &f64_to_f80(1.0));
+        assert_eq!(&g[74..84], &f64_to_f80(0.0));
+        assert_eq!(u32_at(144), 0x0e7f);
+        // fstat carries the stack top in bits 11-13.
+        assert_eq!(u32_at(148) >> 11 & 7, 7);
+
+        let mut other = win32::Registers::new();
+        other.st_top = 7;
+        set_regs(&mut other, &g).unwrap();
+        assert_eq!(get_regs(&other), get_regs(&regs));
+        assert_eq!(other.fpu_control, 0x0e7f);
+
+        // A short G packet only sets the registers it covers.
+        let mut short = win32::Registers::new();
+        set_regs(&mut short, &g[..8]).unwrap();
+        assert_eq!((short.eax, short.ecx, short.edx), (0x11223344, 0, 0));
+    }
+}
@@ -1,13 +1,18 @@
 extern crate sdl2;
 extern crate win32;
 
+mod gdb;
 mod logging;
 use std::{cell::RefCell, io::Write, rc::Rc};
 
 use anyhow::bail;
 
 fn dump_asm(runner: &win32::Runner) {
-    let instrs = win32::disassemble(&runner.machine.x86.mem, runner.machine.x86.regs.eip);
+    let instrs = win32::disassemble(
+        &runner.machine.x86.mem,
+        runner.machine.x86.regs.eip,
+        &runner.machine.labels,
+    );
 
     for instr in &instrs[..std::cmp::min(instrs.len(), 5)] {
         print!("{:08x} {:10} ", instr.addr, instr.bytes);
@@ -18,6 +23,13 @@ fn dump_asm(runner: &win32::Runner) {
     }
 }
 
+fn dump_backtrace(runner: &win32::Runner) {
+    println!("backtrace:");
+    for (i, frame) in runner.machine.backtrace().iter().enumerate() {
+        println!("  #{i} {frame}");
+    }
+}
+
 struct GUI {
     video: sdl2::VideoSubsystem,
     pump: sdl2::EventPump,
@@ -88,6 +100,10 @@ impl win32::Host for EnvRef {
             .as_millis() as u32
     }
 
+    fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        std::fs::read(path).ok()
+    }
+
     fn create_window(&mut self) -> Box<dyn win32::Window> {
         let mut env = self.0.borrow_mut();
         let gui = env.ensure_gui().unwrap();
@@ -234,40 +250,145 @@ impl Texture {
     }
 }
 
+fn parse_hex(text: &str) -> anyhow::Result<u32> {
+    Ok(u32::from_str_radix(text.trim_start_matches("0x"), 16)?)
+}
+
+struct Flags {
+    /// From --trace*.
+    tracer: Option<win32::Tracer>,
+    /// Snapshot to restore after loading the exe.
+    load_snapshot: Option<String>,
+    /// Where to write a snapshot when the run stops.
+    save_snapshot: Option<String>,
+    /// Port to serve the gdb remote protocol on, instead of just running.
+    gdb: Option<u16>,
+    /// Extra directories to search for DLLs, before the exe's own.
+    dll_paths: Vec<String>,
+}
+
+fn parse_flags(flags: &[&str]) -> anyhow::Result<Flags> {
+    let mut load_snapshot = None;
+    let mut save_snapshot = None;
+    let mut gdb = None;
+    let mut dll_paths = Vec::new();
+    let mut path = None;
+    let mut format = win32::TraceFormat::Text;
+    let mut filter = win32::TraceFilter::default();
+    for flag in flags {
+        let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+        match name {
+            "--trace" => path = Some(value),
+            "--trace-format" => {
+                format = match value {
+                    "text" => win32::TraceFormat::Text,
+                    "binary" => win32::TraceFormat::Binary,
+                    _ => bail!("bad trace format {value:?}, expected text or binary"),
+                }
+            }
+            "--trace-addrs" => {
+                let (start, end) = value
+                    .split_once('-')
+                    .ok_or_else(|| anyhow::anyhow!("expected --trace-addrs=START-END"))?;
+                filter.addrs = Some(parse_hex(start)?..parse_hex(end)?);
+            }
+            "--trace-skip" => filter.skip = value.parse()?,
+            "--trace-limit" => filter.limit = Some(value.parse()?),
+            "--load-snapshot" => load_snapshot = Some(value.to_string()),
+            "--save-snapshot" => save_snapshot = Some(value.to_string()),
+            "--gdb" => gdb = Some(value.parse()?),
+            "--dll-path" => dll_paths.push(value.to_string()),
+            _ => bail!("unknown flag {name}"),
+        }
+    }
+    let tracer = match path {
+        None => None,
+        Some(path) => {
+            let out: Box<dyn Write> = if path == "-" {
+                Box::new(std::io::BufWriter::new(std::io::stderr()))
+            } else {
+                Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            };
+            Some(win32::Tracer::new(out, format, filter)?)
+        }
+    };
+    Ok(Flags {
+        tracer,
+        load_snapshot,
+        save_snapshot,
+        gdb,
+        dll_paths,
+    })
+}
+
 fn main() -> anyhow::Result<()> {
     logging::init()?;
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        bail!("specify path to exe");
+    let flags: Vec<&str> = args[1..]
+        .iter()
+        .map(|arg| arg.as_str())
+        .take_while(|arg| arg.starts_with("--"))
+        .collect();
+    let args = &args[1 + flags.len()..];
+    if args.is_empty() {
+        bail!("usage: retrowin32 [--trace=FILE|- [--trace-format=text|binary] [--trace-addrs=START-END] [--trace-skip=N] [--trace-limit=N]] [--load-snapshot=FILE] [--save-snapshot=FILE] [--gdb=PORT] [--dll-path=DIR]... exe [args...]");
     }
-    let exe = &args[1];
-    let cmdline = args[1..].join(" ");
+    let exe = &args[0];
+    let cmdline = args.join(" ");
 
     let buf = std::fs::read(exe)?;
     let host = EnvRef(Rc::new(RefCell::new(Env::new())));
+    let mut flags = parse_flags(&flags)?;
     let mut runner = win32::Runner::new(Box::new(host.clone()));
+    let exe_dir = std::path::Path::new(exe)
+        .parent()
+        .map_or(String::new(), |dir| dir.to_string_lossy().into_owned());
+    flags.dll_paths.push(exe_dir);
+    runner.machine.dll_paths = std::mem::take(&mut flags.dll_paths);
     runner.load_exe(&buf, cmdline)?;
+    if let Some(path) = &flags.load_snapshot {
+        runner.load_snapshot(win32::snapshot::load(&std::fs::read(path)?)?)?;
+    }
+    runner.set_tracer(flags.tracer);
 
     let start = std::time::Instant::now();
-    loop {
-        if let Some(gui) = &mut host.0.borrow_mut().gui {
-            if !gui.pump_messages() {
-                break;
+    if let Some(port) = flags.gdb {
+        gdb::serve(&mut runner, port, &mut || {
+            if let Some(gui) = &mut host.0.borrow_mut().gui {
+                if !gui.pump_messages() {
+                    return Some(0);
+                }
             }
-        }
-        match runner.step_many(10000) {
-            Err(err) => {
-                dump_asm(&runner);
-                log::error!("{:?}", err);
-                break;
+            host.0.borrow().exit_code
+        })?;
+    } else {
+        loop {
+            if let Some(gui) = &mut host.0.borrow_mut().gui {
+                if !gui.pump_messages() {
+                    break;
+                }
             }
-            Ok(_) => {
-                if host.0.borrow().exit_code.is_some() {
+            match runner.step_many(10000) {
+                Err(err) => {
+                    dump_asm(&runner);
+                    dump_backtrace(&runner);
+                    log::error!("{:?}", err);
                     break;
                 }
+                Ok(_) => {
+                    if host.0.borrow().exit_code.is_some() {
+                        break;
+                    }
+                }
             }
         }
     }
+    if let Some(mut tracer) = runner.set_tracer(None) {
+        tracer.flush()?;
+    }
+    if let Some(path) = &flags.save_snapshot {
+        std::fs::write(path, win32::snapshot::save(&runner.machine)?)?;
+    }
     let millis = start.elapsed().as_millis() as usize;
     if millis > 0 {
         eprintln!(
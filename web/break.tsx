@@ -7,14 +7,31 @@ export interface Breakpoint {
   addr: number;
   disabled?: boolean;
   oneShot?: boolean;
+  /** Expression that must be nonzero for the breakpoint to stop; see x86::expr. */
+  condition?: string;
+  /** Number of hits to pass over before stopping. */
+  ignoreCount?: number;
+  /** If set, log this message (with embedded {expr}s) instead of stopping. */
+  log?: string;
+}
+
+/** Matches win32::Watchpoint, with the kind as accepted by Emulator.watchpoint_add. */
+export interface Watchpoint {
+  start: number;
+  end: number;
+  kind: 'r' | 'w' | 'rw';
 }
 
 namespace BreakpointsComponent {
   export interface Props extends Number.Interactions {
     breakpoints: Breakpoint[];
+    watchpoints: Watchpoint[];
+    /** Watchpoint that most recently stopped execution, if any. */
+    watchHit?: Watchpoint;
     labels: Labels;
     highlight: number;
     toggle: (addr: number) => void;
+    delWatch: (wp: Watchpoint) => void;
     add: (text: string) => boolean;
   }
 }
@@ -34,6 +51,9 @@ export class BreakpointsComponent extends preact.Component<BreakpointsComponent.
             </code>
           </div>
           {bp.oneShot ? '[once]' : null}
+          {bp.condition ? <code>if {bp.condition}</code> : null}
+          {bp.ignoreCount ? `after ${bp.ignoreCount}` : null}
+          {bp.log ? <code>log {bp.log}</code> : null}
           {label
             ? (
               <div>
@@ -44,6 +64,18 @@ export class BreakpointsComponent extends preact.Component<BreakpointsComponent.
         </div>,
       );
     }
+    for (const wp of this.props.watchpoints) {
+      const className = wp === this.props.watchHit ? 'highlight' : undefined;
+      rows.push(
+        <div className={className} style={{ display: 'flex', alignItems: 'center', gap: '0.5ex' }}>
+          <button onClick={() => this.props.delWatch(wp)}>x</button>
+          <code>
+            {wp.kind} <Number digits={8} {...this.props}>{wp.start}</Number>
+            -<Number digits={8} {...this.props}>{wp.end}</Number>
+          </code>
+        </div>,
+      );
+    }
     return (
       <section>
         {rows}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(
@@ -92,6 +93,8 @@ extern "C" {
     #[wasm_bindgen(method)]
     fn time(this: &JsHost) -> u32;
     #[wasm_bindgen(method)]
+    fn read_file(this: &JsHost, path: &str) -> Option<Vec<u8>>;
+    #[wasm_bindgen(method)]
     fn create_window(this: &JsHost) -> JsWindow;
     #[wasm_bindgen(method)]
     fn create_surface(this: &JsHost, opts: win32::SurfaceOptions) -> JsSurface;
@@ -107,6 +110,9 @@ impl win32::Host for JsHost {
     fn time(&self) -> u32 {
         JsHost::time(self)
     }
+    fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        JsHost::read_file(self, path)
+    }
     fn create_window(&mut self) -> Box<dyn win32::Window> {
         let window = JsHost::create_window(self);
         window.set_title("test");
@@ -117,6 +123,19 @@ impl win32::Host for JsHost {
     }
 }
 
+fn watchpoint(start: u32, end: u32, kind: &str) -> Result<win32::Watchpoint, String> {
+    let kind = match kind {
+        "r" => win32::WatchKind::Read,
+        "w" => win32::WatchKind::Write,
+        "rw" => win32::WatchKind::Access,
+        _ => return Err(format!("bad watchpoint kind {kind:?}")),
+    };
+    Ok(win32::Watchpoint {
+        addrs: start..end,
+        kind,
+    })
+}
+
 #[wasm_bindgen]
 pub struct Emulator {
     runner: win32::Runner,
@@ -126,11 +145,11 @@ pub struct Emulator {
 impl Emulator {
     #[wasm_bindgen]
     pub fn load_exe(&mut self, buf: &[u8]) -> Result<String, String> {
-        let imports = self
-            .runner
+        self.runner
             .load_exe(buf, "".into())
             .map_err(|err| err.to_string())?;
-        serde_json::to_string(&imports).map_err(|err| err.to_string())
+        let labels: HashMap<u32, &str> = self.runner.machine.labels.iter().collect();
+        serde_json::to_string(&labels).map_err(|err| err.to_string())
     }
 
     pub fn memory(&self) -> js_sys::DataView {
@@ -222,8 +241,16 @@ impl Emulator {
     }
 
     pub fn disassemble_json(&self, addr: u32) -> String {
-        serde_json::to_string(&win32::disassemble(&self.runner.machine.x86.mem, addr))
-            .unwrap_throw()
+        serde_json::to_string(&win32::disassemble(
+            &self.runner.machine.x86.mem,
+            addr,
+            &self.runner.machine.labels,
+        ))
+        .unwrap_throw()
+    }
+
+    pub fn backtrace_json(&self) -> String {
+        serde_json::to_string(&self.runner.machine.backtrace()).unwrap_throw()
     }
 
     pub fn step(&mut self) -> Result<(), String> {
@@ -236,12 +263,68 @@ impl Emulator {
         self.runner.step_many(count).map_err(|err| err.to_string())
     }
 
-    pub fn breakpoint_add(&mut self, addr: u32) {
-        self.runner.add_breakpoint(addr)
+    /// Add a breakpoint that stops only when condition (a win32::Expr expression) is
+    /// nonzero, after ignore_count hits; with log, it logs the message (a template
+    /// with embedded {expr}s) instead of stopping.
+    pub fn breakpoint_add(
+        &mut self,
+        addr: u32,
+        condition: Option<String>,
+        ignore_count: Option<u32>,
+        log: Option<String>,
+    ) -> Result<(), String> {
+        let bp = win32::Breakpoint {
+            condition: condition
+                .map(|text| win32::Expr::parse(&text))
+                .transpose()?,
+            ignore_count: ignore_count.unwrap_or(0),
+            log: log.map(|text| win32::Template::parse(&text)).transpose()?,
+            hits: 0,
+        };
+        if !self.runner.add_breakpoint(addr, bp) {
+            return Err(format!("no instruction starts at {addr:x}"));
+        }
+        Ok(())
     }
-    pub fn breakpoint_clear(&mut self, addr: u32) {
+    pub fn breakpoint_clear(&mut self, addr: u32) -> bool {
         self.runner.clear_breakpoint(addr)
     }
+    /// Let the next step run through a breakpoint at the current eip.
+    pub fn resume(&mut self) {
+        self.runner.resume()
+    }
+
+    /// Watch the bytes [start, end) for kind "r", "w" or "rw" accesses.
+    pub fn watchpoint_add(&mut self, start: u32, end: u32, kind: &str) -> Result<(), String> {
+        let watchpoint = watchpoint(start, end, kind)?;
+        self.runner.add_watchpoint(watchpoint);
+        Ok(())
+    }
+    pub fn watchpoint_clear(&mut self, start: u32, end: u32, kind: &str) -> Result<(), String> {
+        let watchpoint = watchpoint(start, end, kind)?;
+        self.runner.clear_watchpoint(&watchpoint);
+        Ok(())
+    }
+
+    /// JSON description of why the last step stopped, or "null".
+    pub fn stop_reason_json(&self) -> String {
+        serde_json::to_string(&self.runner.stop_reason).unwrap_throw()
+    }
+
+    /// Step backwards one instruction; requires a recorded run.
+    pub fn step_back(&mut self) -> Result<(), String> {
+        self.runner.step_back().map_err(|err| err.to_string())
+    }
+    /// Move to the point where instr_count instructions have executed.
+    pub fn seek(&mut self, instr_count: usize) -> Result<(), String> {
+        self.runner.seek(instr_count).map_err(|err| err.to_string())
+    }
+    /// Rewind to just before the last write to [start, end), returning false if there was none.
+    pub fn run_back_to_write(&mut self, start: u32, end: u32) -> Result<bool, String> {
+        self.runner
+            .run_back_to_write(start..end)
+            .map_err(|err| err.to_string())
+    }
 
     pub fn mappings_json(&self) -> String {
         serde_json::to_string(&self.runner.machine.state.kernel32.mappings.vec()).unwrap_throw()
@@ -251,18 +334,26 @@ impl Emulator {
         self.runner.machine.x86.mem[addr as usize] = value;
     }
 
-    pub fn snapshot(&self) -> Box<[u8]> {
-        bincode::serialize(&self.runner.machine.x86).unwrap().into()
+    pub fn snapshot(&self) -> Result<Box<[u8]>, String> {
+        let bytes = win32::snapshot::save(&self.runner.machine).map_err(|err| err.to_string())?;
+        Ok(bytes.into())
     }
-    pub fn load_snapshot(&mut self, bytes: &[u8]) {
-        let snap = bincode::deserialize(bytes).unwrap();
-        self.runner.load_snapshot(snap);
+    pub fn load_snapshot(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let snap = win32::snapshot::load(bytes).map_err(|err| err.to_string())?;
+        self.runner
+            .load_snapshot(snap)
+            .map_err(|err| err.to_string())
     }
 }
 
 #[wasm_bindgen]
-pub fn new_emulator(host: JsHost) -> Result<Emulator, String> {
-    let runner = win32::Runner::new(Box::new(host));
+/// If record_interval is given, the run is recorded for reverse stepping, with a
+/// checkpoint every record_interval instructions.
+pub fn new_emulator(host: JsHost, record_interval: Option<usize>) -> Result<Emulator, String> {
+    let runner = match record_interval {
+        Some(interval) => win32::Runner::new_recorded(Box::new(host), interval),
+        None => win32::Runner::new(Box::new(host)),
+    };
     Ok(Emulator { runner })
 }
 
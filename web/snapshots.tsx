@@ -37,6 +37,8 @@ namespace SnapshotsComponent {
   export interface Props {
     take: () => Uint8Array;
     load: (snap: Uint8Array) => void;
+    /** Rewinds to the last write to an "ADDR[-END]" range; false if none. */
+    runBackToWrite: (text: string) => boolean;
   }
   interface StateLoading {
     state: undefined;
@@ -114,6 +116,14 @@ class Snapshots extends preact.Component<Snapshots.Props> {
     await finishTransaction(t);
     this.props.load(image);
   }
+  private runBack(ev: Event) {
+    ev.preventDefault();
+    const form = ev.target as HTMLFormElement;
+    const input = form.elements.namedItem('addr') as HTMLInputElement;
+    if (!this.props.runBackToWrite(input.value)) {
+      input.select();
+    }
+  }
   private async clear() {
     const t = this.props.db.transaction(['snap', 'image'], 'readwrite');
     t.objectStore('snap').clear();
@@ -144,6 +154,9 @@ class Snapshots extends preact.Component<Snapshots.Props> {
           <button onClick={() => this.save()}>save snapshot</button>
         </p>
         {snaps}
+        <form onSubmit={(ev) => this.runBack(ev)}>
+          run back to last write of <input name='addr' size={20} placeholder='ADDR[-END]' />
+        </form>
       </section>
     );
   }
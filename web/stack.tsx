@@ -4,6 +4,16 @@ import { Emulator } from './glue/pkg';
 import { Labels } from './labels';
 import { Number } from './memory';
 
+/** Mirrors win32::backtrace::Frame. */
+interface Frame {
+  addr: number;
+  stack_addr: number | null;
+  symbol: string | null;
+  call: string | null;
+  shim: boolean;
+  scanned: boolean;
+}
+
 namespace Stack {
   export interface Props extends Number.Interactions {
     emu: Emulator;
@@ -14,6 +24,8 @@ export class Stack extends preact.Component<Stack.Props> {
   render() {
     const { emu } = this.props;
     const esp = emu.esp;
+    // Note: backtrace_json() may cause allocations, so call it before .memory().
+    const frames = JSON.parse(emu.backtrace_json()) as Frame[];
     const memory = emu.memory();
     const rows = [];
     for (let addr = esp - 0x10; addr < esp + 0x20; addr += 4) {
@@ -35,9 +47,19 @@ export class Stack extends preact.Component<Stack.Props> {
       }
       rows.push(row);
     }
+    const backtrace = frames.map((frame) => (
+      <div title={frame.call ?? undefined}>
+        <Number digits={8} {...this.props}>{frame.addr}</Number>
+        {frame.symbol && ` ${frame.symbol}`}
+        {frame.shim && ' [shim]'}
+        {frame.scanned && ' ?'}
+      </div>
+    ));
     return (
       <section>
         <code>{rows}</code>
+        <br />
+        <code>{backtrace}</code>
       </section>
     );
   }
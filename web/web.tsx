@@ -1,6 +1,6 @@
 import * as preact from 'preact';
 import { Fragment, h } from 'preact';
-import { Breakpoint, BreakpointsComponent } from './break';
+import { Breakpoint, BreakpointsComponent, Watchpoint } from './break';
 import { Code } from './code';
 import * as wasm from './glue/pkg';
 import { Labels, Loader as LabelsLoader } from './labels';
@@ -35,6 +35,7 @@ interface JsHost {
   exit(code: number): void;
   write(buf: Uint8Array): number;
   time(): number;
+  read_file(path: string): Uint8Array | undefined;
   create_window(): JsWindow;
   create_surface(opts: wasm.SurfaceOptions): JsSurface;
 }
@@ -97,9 +98,11 @@ class Window implements JsWindow {
 }
 
 class VM implements JsHost {
-  emu: wasm.Emulator = wasm.new_emulator(this);
+  emu: wasm.Emulator = wasm.new_emulator(this, 1_000_000);
   decoder = new TextDecoder();
   breakpoints = new Map<number, Breakpoint>();
+  watchpoints: Watchpoint[] = [];
+  watchHit: Watchpoint | undefined;
   imports: string[] = [];
   labels: Labels;
   exitCode: number | undefined = undefined;
@@ -140,21 +143,60 @@ class VM implements JsHost {
   addBreak(bp: Breakpoint, save = true) {
     this.breakpoints.set(bp.addr, bp);
     if (!bp.disabled) {
-      this.emu.breakpoint_add(bp.addr);
+      this.enableBreak(bp);
     }
     if (save) this.saveBreakpoints();
   }
 
-  addBreakByName(name: string): boolean {
+  private enableBreak(bp: Breakpoint) {
+    try {
+      this.emu.breakpoint_add(bp.addr, bp.condition, bp.ignoreCount, bp.log);
+    } catch (e) {
+      console.error(`breakpoint ${hex(bp.addr, 8)}: ${e}`);
+      bp.disabled = true;
+    }
+  }
+
+  addWatch(wp: Watchpoint) {
+    this.emu.watchpoint_add(wp.start, wp.end, wp.kind);
+    this.watchpoints.push(wp);
+  }
+
+  delWatch(wp: Watchpoint) {
+    this.emu.watchpoint_clear(wp.start, wp.end, wp.kind);
+    this.watchpoints = this.watchpoints.filter((w) => w !== wp);
+  }
+
+  /**
+   * Parses text like "WinMain", "401000 if eax == 3", "401000 after 10" or "401000 log eax={eax}"
+   * into a breakpoint, or like "rw 402000-402010" into a watchpoint.
+   */
+  addBreakByName(text: string): boolean {
+    // Watchpoints are written as e.g. "rw 402000-402010" or "w 402000" (a single byte).
+    const watch = text.match(/^(r|w|rw)\s+([0-9a-fA-F]+)(?:-([0-9a-fA-F]+))?$/);
+    if (watch) {
+      const start = parseInt(watch[2], 16);
+      const end = watch[3] ? parseInt(watch[3], 16) : start + 1;
+      this.addWatch({ start, end, kind: watch[1] as Watchpoint['kind'] });
+      return true;
+    }
+    const match = text.match(/^(\S+)(?:\s+if\s+(.*?))?(?:\s+after\s+(\d+))?(?:\s+log\s+(.*))?$/);
+    if (!match) return false;
+    const [, name, condition, after, log] = match;
+    const bp = (addr: number): Breakpoint => ({
+      addr,
+      condition,
+      ignoreCount: after ? parseInt(after) : undefined,
+      log,
+    });
     for (const [addr, label] of this.labels.byAddr) {
       if (label === name) {
-        this.addBreak({ addr });
+        this.addBreak(bp(addr));
         return true;
       }
     }
     if (name.match(/^[0-9a-fA-F]+$/)) {
-      const addr = parseInt(name, 16);
-      this.addBreak({ addr });
+      this.addBreak(bp(parseInt(name, 16)));
       return true;
     }
     return false;
@@ -172,7 +214,7 @@ class VM implements JsHost {
     if (bp.disabled) {
       this.emu.breakpoint_clear(addr);
     } else {
-      this.emu.breakpoint_add(addr);
+      this.enableBreak(bp);
     }
     this.saveBreakpoints();
   }
@@ -180,6 +222,18 @@ class VM implements JsHost {
   /** Check if the current address is a break/exit point, returning true if so. */
   checkBreak(): boolean {
     if (this.exitCode !== undefined) return true;
+    this.watchHit = undefined;
+    const stop = JSON.parse(this.emu.stop_reason_json());
+    if (stop && stop.Watchpoint) {
+      const { addrs, kind } = stop.Watchpoint.watchpoint;
+      this.watchHit = this.watchpoints.find((wp) =>
+        wp.start === addrs.start && wp.end === addrs.end
+        && wp.kind === { Read: 'r', Write: 'w', Access: 'rw' }[kind as string]
+      );
+      this.page.setState({ selectedTab: 'breakpoints' });
+      return true;
+    }
+    if (stop !== 'Interrupt') return false;
     const ip = this.emu.eip;
     const bp = this.breakpoints.get(ip);
     if (bp && !bp.disabled) {
@@ -195,16 +249,8 @@ class VM implements JsHost {
 
   /** Returns true if we should keep running after this (no breakpoint). */
   stepPastBreak(): boolean {
-    const ip = this.emu.eip;
-    const bp = this.breakpoints.get(ip);
-    if (bp && !bp.disabled) {
-      this.emu.breakpoint_clear(ip);
-      const ret = this.step();
-      this.emu.breakpoint_add(ip);
-      return ret;
-    } else {
-      return this.step();
-    }
+    this.emu.resume();
+    return this.step();
   }
 
   /** Returns true if we should keep running after this (no breakpoint). */
@@ -213,6 +259,22 @@ class VM implements JsHost {
     return !this.checkBreak();
   }
 
+  stepBack() {
+    this.emu.step_back();
+  }
+
+  /**
+   * Parses text like "402000" or "402000-402010" and rewinds to the last write there.
+   * Returns false if the text didn't parse or nothing had written there.
+   */
+  runBackToWrite(text: string): boolean {
+    const match = text.trim().match(/^([0-9a-fA-F]+)(?:-([0-9a-fA-F]+))?$/);
+    if (!match) return false;
+    const start = parseInt(match[1], 16);
+    const end = match[2] ? parseInt(match[2], 16) : start + 1;
+    return this.emu.run_back_to_write(start, end);
+  }
+
   /** Number of instructions to execute per stepMany, adjusted dynamically. */
   stepSize = 5000;
   /** Moving average of instructions executed per millisecond. */
@@ -261,6 +323,10 @@ class VM implements JsHost {
   time(): number {
     return Math.floor(performance.now());
   }
+  read_file(path: string): Uint8Array | undefined {
+    // Only the exe itself is fetched; there's no filesystem to find DLLs in.
+    return undefined;
+  }
 
   windows: Window[] = [];
   create_window(): JsWindow {
@@ -461,6 +527,15 @@ class Page extends preact.Component<Page.Props, Page.State> {
             step over
           </button>
           &nbsp;
+          <button
+            onClick={() => {
+              this.props.vm.stepBack();
+              this.forceUpdate();
+            }}
+          >
+            step back
+          </button>
+          &nbsp;
           <div>
             {this.props.vm.emu.instr_count} instrs executed | {Math.floor(this.props.vm.instrPerMs)}/ms
           </div>
@@ -511,6 +586,8 @@ class Page extends preact.Component<Page.Props, Page.State> {
               breakpoints: (
                 <BreakpointsComponent
                   breakpoints={Array.from(this.props.vm.breakpoints.values())}
+                  watchpoints={this.props.vm.watchpoints}
+                  watchHit={this.props.vm.watchHit}
                   labels={this.props.vm.labels}
                   highlight={this.props.vm.emu.eip}
                   highlightMemory={this.highlightMemory}
@@ -519,6 +596,10 @@ class Page extends preact.Component<Page.Props, Page.State> {
                     this.props.vm.toggleBreak(addr);
                     this.forceUpdate();
                   }}
+                  delWatch={(wp) => {
+                    this.props.vm.delWatch(wp);
+                    this.forceUpdate();
+                  }}
                   add={(text) => {
                     const ret = this.props.vm.addBreakByName(text);
                     this.forceUpdate();
@@ -530,6 +611,11 @@ class Page extends preact.Component<Page.Props, Page.State> {
               snapshots: (
                 <SnapshotsComponent
                   take={() => this.props.vm.emu.snapshot()}
+                  runBackToWrite={(text) => {
+                    const ret = this.props.vm.runBackToWrite(text);
+                    this.forceUpdate();
+                    return ret;
+                  }}
                   load={(snap) => {
                     this.props.vm.emu.load_snapshot(snap);
                     this.forceUpdate();
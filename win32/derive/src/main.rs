@@ -9,7 +9,8 @@ use quote::{quote, ToTokens};
 mod gen;
 
 enum Attribute {
-    DllExport,
+    /// #[win32_derive::dllexport], optionally with (ordinal = N).
+    DllExport { ordinal: Option<u32> },
 }
 
 fn parse_attr(attr: &syn::Attribute) -> anyhow::Result<Option<Attribute>> {
@@ -20,11 +21,27 @@ fn parse_attr(attr: &syn::Attribute) -> anyhow::Result<Option<Attribute>> {
         return Ok(None);
     }
     let seg = &attr.path.segments[1];
-    if seg.ident == "dllexport" {
-        Ok(Some(Attribute::DllExport))
-    } else {
-        anyhow::bail!("bad win32_derive attribute")
+    if seg.ident != "dllexport" {
+        anyhow::bail!("bad win32_derive attribute");
+    }
+    let mut ordinal = None;
+    match attr.parse_meta()? {
+        syn::Meta::Path(_) => {}
+        syn::Meta::List(list) => {
+            for arg in list.nested {
+                match arg {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Int(lit),
+                        ..
+                    })) if path.is_ident("ordinal") => ordinal = Some(lit.base10_parse()?),
+                    _ => anyhow::bail!("bad dllexport argument"),
+                }
+            }
+        }
+        syn::Meta::NameValue(_) => anyhow::bail!("bad dllexport attribute"),
     }
+    Ok(Some(Attribute::DllExport { ordinal }))
 }
 
 /// Process one module, generating the wrapper functions and resolve helper.
@@ -33,14 +50,20 @@ fn process_mod(module: &syn::Ident, path: &str) -> anyhow::Result<TokenStream> {
     let file = syn::parse_file(&buf)?;
     let mut fns = Vec::new();
     let mut matches = Vec::new();
+    let mut ordinals = Vec::new();
+    let mut seen_ordinals = std::collections::HashSet::new();
     for item in &file.items {
         match item {
             syn::Item::Fn(func) => {
                 let mut dllexport = false;
+                let mut ordinal = None;
                 for attr in func.attrs.iter() {
                     if let Some(attr) = parse_attr(attr)? {
                         match attr {
-                            Attribute::DllExport => dllexport = true,
+                            Attribute::DllExport { ordinal: ord } => {
+                                dllexport = true;
+                                ordinal = ord;
+                            }
                         }
                     }
                 }
@@ -50,6 +73,13 @@ fn process_mod(module: &syn::Ident, path: &str) -> anyhow::Result<TokenStream> {
                     let ident = &func.sig.ident;
                     let quoted = ident.to_string();
                     matches.push(quote!(#quoted => #ident));
+                    if let Some(ordinal) = ordinal {
+                        if !seen_ordinals.insert(ordinal) {
+                            anyhow::bail!("{module}: duplicate ordinal {ordinal} on {quoted}");
+                        }
+                        let ordinal = proc_macro2::Literal::u32_unsuffixed(ordinal);
+                        ordinals.push(quote!(#ordinal => #ident));
+                    }
                 }
             }
             // syn::Item::Struct(_) => todo!(),
@@ -59,6 +89,16 @@ fn process_mod(module: &syn::Ident, path: &str) -> anyhow::Result<TokenStream> {
     if fns.is_empty() {
         return Ok(quote!());
     }
+    let ordinal_match = if ordinals.is_empty() {
+        quote!(winapi::ImportSymbol::Ordinal(_) => return None)
+    } else {
+        quote! {
+            winapi::ImportSymbol::Ordinal(ordinal) => match ordinal {
+                #(#ordinals,)*
+                _ => return None,
+            }
+        }
+    };
     Ok(quote! {
         pub mod #module {
             use super::*;
@@ -67,11 +107,11 @@ fn process_mod(module: &syn::Ident, path: &str) -> anyhow::Result<TokenStream> {
             #(#fns)*
             pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
                 Some(match *sym {
-                    winapi::ImportSymbol::Name(name) => match name {
+                    winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                         #(#matches,)*
                        _ => return None,
                     }
-                    _ => return None, // TODO: ordinal
+                    #ordinal_match,
                 })
             }
         }
@@ -0,0 +1,188 @@
+//! Guest call stack unwinding, for crash reports and the debugger.
+//!
+//! Frames are found by following the saved-EBP chain.  Code compiled without
+//! frame pointers breaks the chain, so from wherever it stops we fall back to
+//! scanning the rest of the stack for values that look like return addresses:
+//! pointers into code that directly follow a call instruction.
+
+use crate::{
+    machine::{Machine, SHIM_BASE},
+    pe::ImageSectionFlags,
+};
+
+/// Give up after this many frames, in case of a cyclic or garbage stack.
+const MAX_FRAMES: usize = 64;
+
+#[derive(serde::Serialize)]
+pub struct Frame {
+    /// The address executing in this frame: eip for the innermost frame, a return address for the rest.
+    pub addr: u32,
+    /// Where on the stack the return address was found; None for the innermost frame.
+    pub stack_addr: Option<u32>,
+    /// The label for addr, as "name" or "name+0x12".
+    pub symbol: Option<String>,
+    /// The call instruction that made this frame's callee, e.g. "call dword ptr [ExitProcess@IAT]".
+    pub call: Option<String>,
+    /// Whether addr is in a shim rather than in guest code.
+    pub shim: bool,
+    /// Whether the frame was found by scanning rather than by following the EBP chain,
+    /// and so might be spurious.
+    pub scanned: bool,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08x}", self.addr)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " {symbol}")?;
+        }
+        if self.shim {
+            write!(f, " [shim]")?;
+        }
+        if let Some(call) = &self.call {
+            write!(f, " ({call})")?;
+        }
+        if self.scanned {
+            write!(f, " ?")?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(mem: &[u8], addr: u32) -> Option<u32> {
+    let bytes = mem.get(addr as usize..addr as usize + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+struct Unwinder<'a> {
+    machine: &'a Machine,
+    /// (start, end) of each code mapping.
+    code: Vec<(u32, u32)>,
+}
+
+impl<'a> Unwinder<'a> {
+    fn new(machine: &'a Machine) -> Self {
+        let code = machine
+            .state
+            .kernel32
+            .mappings
+            .vec()
+            .iter()
+            .filter(|mapping| mapping.flags.contains(ImageSectionFlags::CODE))
+            .map(|mapping| (mapping.addr, mapping.addr + mapping.size))
+            .collect();
+        Unwinder { machine, code }
+    }
+
+    fn is_shim(addr: u32) -> bool {
+        addr & 0xFFFF_0000 == SHIM_BASE
+    }
+
+    fn code_mapping(&self, addr: u32) -> Option<(u32, u32)> {
+        self.code
+            .iter()
+            .copied()
+            .find(|&(start, end)| start <= addr && addr < end)
+    }
+
+    /// Whether addr is plausibly a return address.
+    fn is_return_addr(&self, addr: u32) -> bool {
+        self.code_mapping(addr).is_some()
+            && x86::debug::call_before(&self.machine.x86.mem, addr).is_some()
+    }
+
+    fn symbol(&self, addr: u32) -> Option<String> {
+        let labels = &self.machine.labels;
+        if let Some(name) = labels.get(addr) {
+            return Some(name.to_string());
+        }
+        // Otherwise the nearest label before addr in the same code mapping.
+        let (start, _) = self.code_mapping(addr)?;
+        let (base, name) = labels
+            .iter()
+            .filter(|&(label, name)| start <= label && label < addr && !name.ends_with("@IAT"))
+            .max_by_key(|&(label, _)| label)?;
+        Some(format!("{name}+{:#x}", addr - base))
+    }
+
+    fn frame(&self, addr: u32, stack_addr: Option<u32>, scanned: bool) -> Frame {
+        let shim = Self::is_shim(addr);
+        let symbol = if shim {
+            self.machine.shims.name(addr).map(str::to_string)
+        } else {
+            self.symbol(addr)
+        };
+        let call = stack_addr
+            .and_then(|_| x86::debug::call_before(&self.machine.x86.mem, addr))
+            .map(|instr| x86::debug::format_instr(&instr, &self.machine.labels));
+        Frame {
+            addr,
+            stack_addr,
+            symbol,
+            call,
+            shim,
+            scanned,
+        }
+    }
+
+    fn unwind(&self) -> Vec<Frame> {
+        let regs = &self.machine.x86.regs;
+        let mem = &self.machine.x86.mem;
+        let stack_end = self
+            .machine
+            .state
+            .kernel32
+            .mappings
+            .vec()
+            .iter()
+            .find(|mapping| mapping.addr <= regs.esp && regs.esp < mapping.addr + mapping.size)
+            .map_or(mem.len() as u32, |mapping| mapping.addr + mapping.size);
+
+        let mut frames = vec![self.frame(regs.eip, None, false)];
+
+        // Follow the EBP chain, where each frame holds [saved ebp, return address].
+        // The outermost frame's saved ebp points at the top of the stack, so if we
+        // stop short of that, something along the way didn't keep a frame pointer.
+        let mut ebp = regs.ebp;
+        let mut scan_from = regs.esp;
+        let mut complete = false;
+        while frames.len() < MAX_FRAMES && scan_from <= ebp && ebp < stack_end {
+            if ebp.saturating_add(8) > stack_end {
+                complete = true;
+                break;
+            }
+            let Some(ret) = read_u32(mem, ebp + 4) else {
+                break;
+            };
+            if !self.is_return_addr(ret) {
+                break;
+            }
+            frames.push(self.frame(ret, Some(ebp + 4), false));
+            scan_from = ebp + 8;
+            match read_u32(mem, ebp) {
+                Some(next) if next > ebp => ebp = next,
+                _ => break,
+            }
+        }
+
+        if !complete {
+            let mut addr = scan_from;
+            while frames.len() < MAX_FRAMES && addr.saturating_add(4) <= stack_end {
+                if let Some(value) = read_u32(mem, addr) {
+                    if self.is_return_addr(value) {
+                        frames.push(self.frame(value, Some(addr), true));
+                    }
+                }
+                addr += 4;
+            }
+        }
+        frames
+    }
+}
+
+impl Machine {
+    /// Walk the guest stack from the current eip outwards.
+    pub fn backtrace(&self) -> Vec<Frame> {
+        Unwinder::new(self).unwind()
+    }
+}
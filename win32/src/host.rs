@@ -45,6 +45,8 @@ pub trait Host {
     fn exit(&mut self, code: u32);
     fn write(&self, buf: &[u8]) -> usize;
     fn time(&self) -> u32;
+    /// Read a whole file, e.g. a DLL the exe imports; None if it can't be read.
+    fn read_file(&self, path: &str) -> Option<Vec<u8>>;
 
     fn create_window(&mut self) -> Box<dyn Window>;
     fn create_surface(&mut self, opts: &SurfaceOptions) -> Box<dyn Surface>;
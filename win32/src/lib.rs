@@ -1,13 +1,23 @@
+pub mod backtrace;
 mod host;
 mod machine;
 mod pe;
 mod reader;
+pub mod replay;
+pub mod snapshot;
+#[cfg(test)]
+mod tests;
 mod winapi;
 mod windows;
 
 pub use host::{Host, Surface, SurfaceOptions, Window};
-pub use machine::Runner;
-pub use x86::debug::disassemble;
+pub use machine::{Runner, StopReason};
+pub use x86::debug::{disassemble, Symbols};
+pub use x86::expr::{Expr, Template};
+pub use x86::{
+    Breakpoint, FPUStatus, Flags, Registers, TraceFilter, TraceFormat, Tracer, WatchKind,
+    Watchpoint,
+};
 
 #[macro_use]
 extern crate num_derive;
@@ -1,33 +1,78 @@
-use std::collections::HashMap;
-
 use anyhow::bail;
 use x86::X86;
 
-use crate::{host, pe::ImageSectionFlags, winapi, windows::load_exe};
+use crate::{
+    host,
+    pe::ImageSectionFlags,
+    replay::{Replay, ReplayHost},
+    winapi,
+    windows::load_exe,
+};
 
 /// Code that calls from x86 to the host will jump to addresses in this
 /// magic range.
 /// "fake IAT" => "FIAT" => "F1A7"
 pub const SHIM_BASE: u32 = 0xF1A7_0000;
 
+/// A shim's handler, or an error message if it's unimplemented.
+pub type ShimEntry = Result<fn(&mut Machine), String>;
+
+struct Shim {
+    /// Name as understood by winapi::resolve_shim(), e.g. "kernel32.dll!ExitProcess"
+    /// or "IDirectDraw::CreateSurface".
+    name: String,
+    handler: ShimEntry,
+}
+
 /// Jumps to memory address SHIM_BASE+x are interpreted as calling shims[x].
 /// This is how emulated code calls out to hosting code for e.g. DLL imports.
-pub struct Shims(Vec<Result<fn(&mut Machine), String>>);
+pub struct Shims(Vec<Shim>);
 impl Shims {
     fn new() -> Self {
         Shims(Vec::new())
     }
 
+    /// Rebuild a table from the names() of another, e.g. when loading a snapshot.
+    pub fn from_names(names: Vec<String>) -> Self {
+        Shims(
+            names
+                .into_iter()
+                .map(|name| Shim {
+                    handler: winapi::resolve_shim(&name),
+                    name,
+                })
+                .collect(),
+        )
+    }
+
     /// Returns the (fake) address of the registered function.
-    pub fn add(&mut self, entry: Result<fn(&mut Machine), String>) -> u32 {
+    pub fn add(&mut self, name: String, handler: ShimEntry) -> u32 {
         let id = SHIM_BASE | self.0.len() as u32;
-        self.0.push(entry);
+        self.0.push(Shim { name, handler });
         id
     }
 
+    /// Like add(), but reuses an existing shim of the same name.
+    pub fn get_or_add(&mut self, name: String, handler: ShimEntry) -> u32 {
+        match self.0.iter().position(|shim| shim.name == name) {
+            Some(index) => SHIM_BASE | index as u32,
+            None => self.add(name, handler),
+        }
+    }
+
+    /// The name of the shim at a SHIM_BASE address.
+    pub fn name(&self, addr: u32) -> Option<&str> {
+        let index = (addr & 0x0000_FFFF) as usize;
+        self.0.get(index).map(|shim| shim.name.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|shim| shim.name.as_str())
+    }
+
     pub fn get(&self, addr: u32) -> Option<&fn(&mut Machine)> {
         let index = (addr & 0x0000_FFFF) as usize;
-        let handler = self.0.get(index);
+        let handler = self.0.get(index).map(|shim| &shim.handler);
         match handler {
             Some(handler) => match handler {
                 Ok(handler) => return Some(handler),
@@ -44,6 +89,10 @@ pub struct Machine {
     pub host: Box<dyn host::Host>,
     pub state: winapi::State,
     pub shims: Shims,
+    /// Names for addresses in the loaded image, for disassembly, traces and errors.
+    pub labels: x86::debug::Symbols,
+    /// Directories to look for DLLs in that aren't implemented by shims.
+    pub dll_paths: Vec<String>,
 }
 
 impl Machine {
@@ -53,15 +102,32 @@ impl Machine {
             host,
             state: winapi::State::new(),
             shims: Shims::new(),
+            labels: Default::default(),
+            dll_paths: Vec::new(),
         }
     }
 }
 
+/// Why Runner::step() stopped execution.
+#[derive(Debug, serde::Serialize)]
+pub enum StopReason {
+    /// A breakpoint (int3) or process exit.
+    Interrupt,
+    /// A memory watchpoint, tripped by the instruction that just completed.
+    Watchpoint(x86::WatchHit),
+}
+
 /// Manages decoding and running instructions in an owned Machine.
 pub struct Runner {
     pub machine: Machine,
     /// Total number of instructions executed.
     pub instr_count: usize,
+    /// Set when step() returns Ok(false).
+    pub stop_reason: Option<StopReason>,
+    /// Number of calls from x86 code into shims.
+    pub shim_calls: usize,
+    /// Present if this run is being recorded for replay.
+    pub replay: Option<Replay>,
 
     icache: x86::InstrCache,
 }
@@ -70,74 +136,162 @@ impl Runner {
         Runner {
             machine: Machine::new(host),
             instr_count: 0,
+            stop_reason: None,
+            shim_calls: 0,
+            replay: None,
             icache: x86::InstrCache::new(),
         }
     }
 
-    pub fn load_exe(
-        &mut self,
-        buf: &[u8],
-        cmdline: String,
-    ) -> anyhow::Result<HashMap<u32, String>> {
-        let labels = load_exe(&mut self.machine, buf, cmdline)?;
+    /// Like new(), but records the run so that it can be replayed and stepped backwards,
+    /// taking a checkpoint every interval instructions.
+    pub fn new_recorded(host: Box<dyn host::Host>, interval: usize) -> Self {
+        let host = ReplayHost::new(host);
+        let mut runner = Runner::new(Box::new(host.clone()));
+        runner.replay = Some(Replay::new(host, interval));
+        runner
+    }
 
-        // Disassemble the 'code' section.
-        let mapping = self
+    pub fn load_exe(&mut self, buf: &[u8], cmdline: String) -> anyhow::Result<()> {
+        if let Some(replay) = &mut self.replay {
+            replay.exe = buf.to_vec();
+            replay.cmdline = cmdline.clone();
+        }
+        load_exe(&mut self.machine, buf, cmdline)?;
+        self.disassemble_code()
+    }
+
+    /// Decode the code sections of all loaded images into the instruction cache and
+    /// position it at eip.
+    fn disassemble_code(&mut self) -> anyhow::Result<()> {
+        let mem = &self.machine.x86.mem;
+        let spans: Vec<(&[u8], u32)> = self
             .machine
             .state
             .kernel32
             .mappings
             .vec()
             .iter()
-            .find(|mapping| mapping.flags.contains(ImageSectionFlags::CODE))
-            .ok_or_else(|| anyhow::anyhow!("no code section"))?;
-        let section =
-            &self.machine.x86.mem[mapping.addr as usize..(mapping.addr + mapping.size) as usize];
-        self.icache.disassemble(section, mapping.addr);
+            .filter(|mapping| mapping.flags.contains(ImageSectionFlags::CODE))
+            .map(|mapping| {
+                let span = &mem[mapping.addr as usize..(mapping.addr + mapping.size) as usize];
+                (span, mapping.addr)
+            })
+            .collect();
+        if spans.is_empty() {
+            bail!("no code section");
+        }
+        self.icache.disassemble_spans(&spans);
         self.icache
             .jmp(&self.machine.x86.mem, self.machine.x86.regs.eip)?;
-
-        Ok(labels)
+        Ok(())
     }
 
-    pub fn add_breakpoint(&mut self, addr: u32) {
-        self.icache.add_breakpoint(addr)
+    /// Returns false if addr isn't the start of a decoded instruction.
+    pub fn add_breakpoint(&mut self, addr: u32, bp: x86::Breakpoint) -> bool {
+        self.icache.add_breakpoint(addr, bp)
     }
 
-    pub fn clear_breakpoint(&mut self, addr: u32) {
+    /// Returns false if there was no breakpoint at addr.
+    pub fn clear_breakpoint(&mut self, addr: u32) -> bool {
         self.icache.clear_breakpoint(addr)
     }
 
+    /// Continue execution at addr.
+    pub fn jmp(&mut self, addr: u32) -> anyhow::Result<()> {
+        self.machine.x86.regs.eip = addr;
+        self.icache.jmp(&self.machine.x86.mem, addr)?;
+        Ok(())
+    }
+
+    /// Let the next step() run through a breakpoint at the current eip.
+    pub fn resume(&mut self) {
+        self.icache.resume()
+    }
+
+    /// Attach (or with None, detach) an instruction tracer, returning the previous one.
+    /// Traces use the machine's labels as of each instruction, so later-loaded DLLs get names.
+    pub fn set_tracer(&mut self, tracer: Option<x86::Tracer>) -> Option<x86::Tracer> {
+        std::mem::replace(&mut self.icache.tracer, tracer)
+    }
+
     /// If eip points at a shim address, call the handler and update eip.
     fn check_shim_call(&mut self) -> anyhow::Result<()> {
         if self.machine.x86.regs.eip & 0xFFFF_0000 != SHIM_BASE {
             return Ok(());
         }
-        let ret = x86::ops::pop(&mut self.machine.x86);
-        let handler = self
+        self.shim_calls += 1;
+        if let Some(replay) = &self.replay {
+            // The count already includes the call into the shim, which ran before
+            // any rewind from just after it.
+            replay.update_muted(self.instr_count - 1);
+        }
+        let handler = *self
             .machine
             .shims
             .get(self.machine.x86.regs.eip)
             .ok_or_else(|| anyhow::anyhow!("missing shim"))?;
+        // While the handler runs, eip holds the return address, so that a handler
+        // needing to run guest code (e.g. LoadLibrary running DllMains) can divert it.
+        self.machine.x86.regs.eip = x86::ops::pop(&mut self.machine.x86);
+        let dlls = self.machine.state.kernel32.dlls.len();
         handler(&mut self.machine);
-        x86::ops::x86_jmp(&mut self.machine.x86, ret).map_err(|err| anyhow::anyhow!(err))
+        if let Some(replay) = &mut self.replay {
+            // The handler may have recreated a window or surface from before a rewind.
+            replay.unstash(&mut self.machine.state);
+        }
+        let eip = self.machine.x86.regs.eip;
+        x86::ops::x86_jmp(&mut self.machine.x86, eip).map_err(|err| anyhow::anyhow!(err))?;
+        if self.machine.state.kernel32.dlls.len() != dlls {
+            // The handler loaded new code.
+            self.disassemble_code()?;
+        }
+        Ok(())
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: x86::Watchpoint) {
+        self.machine.x86.watchpoints.add(watchpoint)
+    }
+
+    pub fn clear_watchpoint(&mut self, watchpoint: &x86::Watchpoint) -> bool {
+        self.machine.x86.watchpoints.remove(watchpoint)
     }
 
-    // Single-step execution.  Returns Ok(false) if we stopped.
+    // Single-step execution.  Returns Ok(false) if we stopped, with the reason in stop_reason.
     pub fn step(&mut self) -> anyhow::Result<bool> {
+        if let Some(replay) = &mut self.replay {
+            if replay.due(self.instr_count) {
+                replay.checkpoint(&self.machine, self.instr_count, self.shim_calls)?;
+            }
+        }
         self.instr_count += 1;
-        match self.icache.step(&mut self.machine.x86) {
-            Err(x86::StepError::Interrupt) => Ok(false),
-            Err(x86::StepError::Error(err)) => bail!(err),
+        self.stop_reason = None;
+        match self
+            .icache
+            .step(&mut self.machine.x86, &self.machine.labels)
+        {
+            Err(x86::StepError::Interrupt) => {
+                // The instruction didn't run.
+                self.instr_count -= 1;
+                self.stop_reason = Some(StopReason::Interrupt);
+            }
+            Err(x86::StepError::Error(err)) => {
+                self.machine.x86.watchpoints.take_hit();
+                let eip = self.machine.x86.regs.eip;
+                bail!("{err} at {}", self.machine.labels.describe(eip))
+            }
             Ok(false) => {
                 self.check_shim_call()?;
                 // Instruction changed eip.  Update icache to match.
                 self.icache
                     .jmp(&self.machine.x86.mem, self.machine.x86.regs.eip)?;
-                Ok(true)
             }
-            Ok(true) => Ok(true),
+            Ok(true) => {}
+        }
+        if let Some(hit) = self.machine.x86.watchpoints.take_hit() {
+            self.stop_reason = Some(StopReason::Watchpoint(hit));
         }
+        Ok(self.stop_reason.is_none())
     }
 
     // Multi-step execution.  Returns Ok(false) on breakpoint.
@@ -150,10 +304,151 @@ impl Runner {
         Ok(count)
     }
 
-    pub fn load_snapshot(&mut self, snap: x86::Snapshot) {
-        self.machine.x86.load_snapshot(snap);
+    /// Return to the checkpoint at index in the replay's list, or to the start of the run.
+    fn restore(&mut self, index: Option<usize>) -> anyhow::Result<()> {
+        let replay = self
+            .replay
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("run isn't being recorded"))?;
+        // Re-execution reproduces the same code, so the icache (and its breakpoints)
+        // stay valid.
+        match replay.rewind(self.instr_count, &mut self.machine.state, index)? {
+            Some((cp, snap)) => {
+                self.instr_count = cp.instr_count;
+                self.shim_calls = cp.shim_calls;
+                self.machine.load_snapshot(snap);
+            }
+            None => {
+                // No checkpoint is early enough, so start over from a fresh load.
+                let watchpoints = std::mem::take(&mut self.machine.x86.watchpoints);
+                let dll_paths = std::mem::take(&mut self.machine.dll_paths);
+                self.machine = Machine::new(Box::new(replay.host.clone()));
+                self.machine.x86.watchpoints = watchpoints;
+                self.machine.dll_paths = dll_paths;
+                load_exe(&mut self.machine, &replay.exe, replay.cmdline.clone())?;
+                self.instr_count = 0;
+                self.shim_calls = 0;
+            }
+        }
+        replay.unstash(&mut self.machine.state);
+        self.machine.x86.watchpoints.take_hit();
+        self.stop_reason = None;
         self.icache
-            .jmp(&self.machine.x86.mem, self.machine.x86.regs.eip)
-            .unwrap();
+            .jmp(&self.machine.x86.mem, self.machine.x86.regs.eip)?;
+        Ok(())
+    }
+
+    /// Run forward until target instructions have executed, passing through breakpoints
+    /// and watchpoints.  If watch is given, returns the position just before the last
+    /// instruction that tripped it.
+    fn replay_to(
+        &mut self,
+        target: usize,
+        watch: Option<&x86::Watchpoint>,
+    ) -> anyhow::Result<Option<usize>> {
+        let mut last_hit = None;
+        let mut interrupted = false;
+        while self.instr_count < target {
+            if self.step()? {
+                interrupted = false;
+                continue;
+            }
+            match self.stop_reason.take() {
+                Some(StopReason::Interrupt) => {
+                    if interrupted {
+                        bail!("replay stuck at {:x}", self.machine.x86.regs.eip);
+                    }
+                    interrupted = true;
+                    self.resume();
+                }
+                Some(StopReason::Watchpoint(hit)) => {
+                    interrupted = false;
+                    if Some(&hit.watchpoint) == watch {
+                        last_hit = Some(self.instr_count - 1);
+                    }
+                }
+                None => unreachable!(),
+            }
+        }
+        if let Some(replay) = &self.replay {
+            replay.update_muted(self.instr_count);
+        }
+        Ok(last_hit)
+    }
+
+    /// Move to the point where target instructions have executed, which may be
+    /// behind the current position if the run is being recorded.
+    pub fn seek(&mut self, target: usize) -> anyhow::Result<()> {
+        if target < self.instr_count {
+            let replay = self
+                .replay
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("run isn't being recorded"))?;
+            let index = replay.find(target);
+            self.restore(index)?;
+        }
+        self.replay_to(target, None)?;
+        Ok(())
+    }
+
+    pub fn step_back(&mut self) -> anyhow::Result<()> {
+        if self.instr_count == 0 {
+            bail!("at start of run");
+        }
+        self.seek(self.instr_count - 1)
+    }
+
+    /// Rewind to just before the most recent instruction that wrote to addrs.
+    /// Returns Ok(false), leaving the position unchanged, if there was no such write.
+    pub fn run_back_to_write(&mut self, addrs: std::ops::Range<u32>) -> anyhow::Result<bool> {
+        let end = self.instr_count;
+        let watch = x86::Watchpoint {
+            addrs,
+            kind: x86::WatchKind::Write,
+        };
+        self.machine.x86.watchpoints.add(watch.clone());
+        let found = self.find_last_hit(&watch, end);
+        self.machine.x86.watchpoints.remove(&watch);
+        match found? {
+            Some(pos) => self.seek(pos)?,
+            None => {
+                self.seek(end)?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Search backwards from end for the last instruction that tripped watch, re-executing
+    /// from successively earlier checkpoints.
+    fn find_last_hit(
+        &mut self,
+        watch: &x86::Watchpoint,
+        end: usize,
+    ) -> anyhow::Result<Option<usize>> {
+        let mut window_end = end;
+        while window_end > 0 {
+            let replay = self
+                .replay
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("run isn't being recorded"))?;
+            let index = replay.find(window_end - 1);
+            let start = index.map_or(0, |i| replay.checkpoints()[i].instr_count);
+            self.restore(index)?;
+            if let Some(hit) = self.replay_to(window_end, Some(watch))? {
+                return Ok(Some(hit));
+            }
+            window_end = start;
+        }
+        Ok(None)
+    }
+
+    pub fn load_snapshot(&mut self, snap: crate::snapshot::Snapshot) -> anyhow::Result<()> {
+        if self.replay.take().is_some() {
+            log::warn!("loading snapshot ends replay recording");
+        }
+        self.machine.load_snapshot(snap);
+        self.stop_reason = None;
+        self.disassemble_code()
     }
 }
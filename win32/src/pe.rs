@@ -1,7 +1,7 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 
-use std::mem::size_of;
+use std::{collections::HashMap, mem::size_of};
 
 use crate::{
     reader::Reader,
@@ -133,7 +133,7 @@ impl IMAGE_SECTION_HEADER {
 }
 
 bitflags! {
-    #[derive(serde::Serialize)]
+    #[derive(serde::Serialize, serde::Deserialize)]
     pub struct ImageSectionFlags: u32 {
         const CODE = 0x20;
         const INITIALIZED_DATA = 0x40;
@@ -186,6 +186,22 @@ struct IMAGE_IMPORT_DESCRIPTOR {
 }
 unsafe impl x86::Pod for IMAGE_IMPORT_DESCRIPTOR {}
 
+/// The names of the DLLs an image imports from, as spelled in the file.
+/// mem, addr: as in parse_imports()
+pub fn import_dlls(mem: &[u8], addr: usize) -> anyhow::Result<Vec<String>> {
+    let mut r = Reader::new(mem);
+    r.seek(addr)?;
+    let mut dlls = Vec::new();
+    loop {
+        let descriptor = r.view::<IMAGE_IMPORT_DESCRIPTOR>();
+        if descriptor.Name == 0 {
+            break;
+        }
+        dlls.push(mem[descriptor.Name as usize..].read_strz().to_string());
+    }
+    Ok(dlls)
+}
+
 /// mem: memory starting at image base
 /// addr: address of imports table relative to mem start
 /// resolve: map an import name to the address we will jump to for it
@@ -235,7 +251,7 @@ pub fn parse_imports(
                 // First two bytes at offset are hint/name table index, used to look up
                 // the name faster in the DLL; we just skip them.
                 let sym_name = mem[(entry + 2) as usize..].read_strz();
-                ImportSymbol::Name(sym_name)
+                ImportSymbol::Name(sym_name.to_string())
             };
             let target = resolve(&dll_name, symbol, addr);
             patches.push((addr, target));
@@ -249,6 +265,341 @@ pub fn parse_imports(
     Ok(())
 }
 
+#[repr(C)]
+#[derive(Debug)]
+struct IMAGE_DELAYLOAD_DESCRIPTOR {
+    Attributes: DWORD,
+    DllNameRVA: DWORD,
+    ModuleHandleRVA: DWORD,
+    ImportAddressTableRVA: DWORD,
+    ImportNameTableRVA: DWORD,
+    BoundImportAddressTableRVA: DWORD,
+    UnloadInformationTableRVA: DWORD,
+    TimeDateStamp: DWORD,
+}
+unsafe impl x86::Pod for IMAGE_DELAYLOAD_DESCRIPTOR {}
+
+/// Set in IMAGE_DELAYLOAD_DESCRIPTOR.Attributes when its fields are RVAs; older
+/// linkers (VC6) wrote virtual addresses instead.
+const DELAYLOAD_RVA_BASED: DWORD = 1;
+
+/// The functions an image delay-loads from one DLL.
+pub struct DelayImports {
+    /// DLL name as spelled in the file.
+    pub dll: String,
+    /// Where the DLL's module handle is cached, relative to the image base.
+    pub hmodule_addr: u32,
+    /// Each symbol, along with the address of its IAT entry relative to the image base.
+    pub symbols: Vec<(ImportSymbol, u32)>,
+}
+
+/// mem: memory starting at image base
+/// addr: address of the delay import table relative to mem start
+/// base: the address mem starts at, for converting VA-based descriptors
+pub fn parse_delay_imports(
+    mem: &[u8],
+    addr: usize,
+    base: u32,
+) -> anyhow::Result<Vec<DelayImports>> {
+    // Linked with /DELAYLOAD, each IAT entry initially points at a stub that calls
+    // the CRT's __delayLoadHelper2 to load the DLL and patch the entry on first call.
+    // Filling in the IAT up front skips all of that.
+    let mut r = Reader::new(mem);
+    r.seek(addr)?;
+    let mut imports = Vec::new();
+    loop {
+        let descriptor = r.view::<IMAGE_DELAYLOAD_DESCRIPTOR>();
+        if descriptor.DllNameRVA == 0 {
+            break;
+        }
+        let rva = |field: DWORD| -> anyhow::Result<u32> {
+            if descriptor.Attributes & DELAYLOAD_RVA_BASED != 0 {
+                Ok(field)
+            } else {
+                field
+                    .checked_sub(base)
+                    .ok_or_else(|| anyhow!("delay import address {field:#x} below image base"))
+            }
+        };
+        let read_strz = |addr: u32| {
+            mem.get(addr as usize..)
+                .map(|str| str.read_strz().to_string())
+                .ok_or_else(|| anyhow!("delay import string at {addr:#x} out of bounds"))
+        };
+
+        let mut delay = DelayImports {
+            dll: read_strz(rva(descriptor.DllNameRVA)?)?,
+            hmodule_addr: rva(descriptor.ModuleHandleRVA)?,
+            symbols: Vec::new(),
+        };
+        let iat = rva(descriptor.ImportAddressTableRVA)?;
+        let names = rva(descriptor.ImportNameTableRVA)?;
+        for i in 0.. {
+            let name_addr = (names + i * 4) as usize;
+            if name_addr + 4 > mem.len() {
+                bail!("delay import name table at {names:#x} out of bounds");
+            }
+            let entry = mem.read_u32(name_addr as u32);
+            if entry == 0 {
+                break;
+            }
+            let symbol = if entry & (1 << 31) != 0 {
+                ImportSymbol::Ordinal(entry & 0xFFFF)
+            } else {
+                // Skip the hint, as in parse_imports().
+                ImportSymbol::Name(read_strz(rva(entry)? + 2)?)
+            };
+            delay.symbols.push((symbol, iat + i * 4));
+        }
+        imports.push(delay);
+    }
+    Ok(imports)
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct IMAGE_EXPORT_DIRECTORY {
+    Characteristics: DWORD,
+    TimeDateStamp: DWORD,
+    MajorVersion: WORD,
+    MinorVersion: WORD,
+    Name: DWORD,
+    Base: DWORD,
+    NumberOfFunctions: DWORD,
+    NumberOfNames: DWORD,
+    AddressOfFunctions: DWORD,
+    AddressOfNames: DWORD,
+    AddressOfNameOrdinals: DWORD,
+}
+unsafe impl x86::Pod for IMAGE_EXPORT_DIRECTORY {}
+
+/// The functions a DLL exports.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Exports {
+    /// Ordinal of the first entry in fns.
+    pub ordinal_base: u32,
+    /// Function addresses relative to the image base, indexed by ordinal - ordinal_base.
+    /// Zero for unused ordinals.
+    pub fns: Vec<u32>,
+    /// Exported names, mapped to their index in fns.
+    pub names: HashMap<String, usize>,
+    /// Entries of fns that are forwarded to another DLL, as "DLL.name" or "DLL.#ordinal".
+    #[serde(default)]
+    pub forwards: HashMap<usize, String>,
+}
+
+/// Where an export lives.
+pub enum Export<'a> {
+    /// Address relative to the image base.
+    Addr(u32),
+    /// Forwarded to another DLL's export, as "DLL.name" or "DLL.#ordinal".
+    Forward(&'a str),
+}
+
+impl Exports {
+    /// Look up an export.
+    pub fn resolve(&self, sym: &ImportSymbol) -> Option<Export<'_>> {
+        let index = match *sym {
+            ImportSymbol::Name(ref name) => *self.names.get(name)?,
+            ImportSymbol::Ordinal(ordinal) => ordinal.checked_sub(self.ordinal_base)? as usize,
+        };
+        if let Some(forward) = self.forwards.get(&index) {
+            return Some(Export::Forward(forward));
+        }
+        self.fns
+            .get(index)
+            .copied()
+            .filter(|&addr| addr != 0)
+            .map(Export::Addr)
+    }
+}
+
+/// Split a forwarder like "NTDLL.RtlAllocateHeap" into a DLL file name and symbol.
+pub fn parse_forwarder(forward: &str) -> Option<(String, ImportSymbol)> {
+    // The DLL part has no extension, but may itself contain dots.
+    let (dll, sym) = forward.rsplit_once('.')?;
+    let sym = match sym.strip_prefix('#') {
+        Some(ordinal) => ImportSymbol::Ordinal(ordinal.parse().ok()?),
+        None => ImportSymbol::Name(sym.to_string()),
+    };
+    Some((format!("{dll}.dll"), sym))
+}
+
+/// mem: memory starting at image base
+/// addr, size: location of the export directory relative to mem start
+pub fn parse_exports(mem: &[u8], addr: usize, size: usize) -> anyhow::Result<Exports> {
+    let mut r = Reader::new(mem);
+    r.seek(addr)?;
+    let dir = r.view::<IMAGE_EXPORT_DIRECTORY>();
+    let table = |addr: DWORD, count: DWORD, size: usize| {
+        mem.get(addr as usize..addr as usize + count as usize * size)
+            .ok_or_else(|| anyhow!("export table at {addr:#x} out of bounds"))
+    };
+    let read_str = |addr: u32| {
+        mem.get(addr as usize..)
+            .map(|str| str.read_strz().to_string())
+            .ok_or_else(|| anyhow!("export string at {addr:#x} out of bounds"))
+    };
+
+    let fns: Vec<u32> = table(dir.AddressOfFunctions, dir.NumberOfFunctions, 4)?
+        .chunks_exact(4)
+        .map(|addr| u32::from_le_bytes(addr.try_into().unwrap()))
+        .collect();
+    // A function address pointing back within the export directory is really a
+    // forwarder string.
+    let mut forwards = HashMap::new();
+    for (index, &fn_addr) in fns.iter().enumerate() {
+        if (addr..addr + size).contains(&(fn_addr as usize)) {
+            forwards.insert(index, read_str(fn_addr)?);
+        }
+    }
+    let names = table(dir.AddressOfNames, dir.NumberOfNames, 4)?.chunks_exact(4);
+    let ordinals = table(dir.AddressOfNameOrdinals, dir.NumberOfNames, 2)?.chunks_exact(2);
+    let mut exports = Exports {
+        ordinal_base: dir.Base,
+        fns,
+        names: HashMap::new(),
+        forwards,
+    };
+    for (name, index) in names.zip(ordinals) {
+        let name = read_str(u32::from_le_bytes(name.try_into().unwrap()))?;
+        let index = u16::from_le_bytes(index.try_into().unwrap());
+        exports.names.insert(name, index as usize);
+    }
+    Ok(exports)
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct IMAGE_TLS_DIRECTORY32 {
+    StartAddressOfRawData: DWORD,
+    EndAddressOfRawData: DWORD,
+    AddressOfIndex: DWORD,
+    AddressOfCallBacks: DWORD,
+    SizeOfZeroFill: DWORD,
+    Characteristics: DWORD,
+}
+unsafe impl x86::Pod for IMAGE_TLS_DIRECTORY32 {}
+
+/// An image's static thread-local storage.  Unlike most of the PE format these are
+/// all (relocated) virtual addresses rather than RVAs.
+#[derive(Debug)]
+pub struct TlsDirectory {
+    /// Initial contents of each thread's block: [start, end).
+    pub start: u32,
+    pub end: u32,
+    /// Zero bytes following that in the block.
+    pub zero_fill: u32,
+    /// Where the loader writes the image's TLS index.
+    pub index_addr: u32,
+    /// Functions to call like DllMain on process and thread attach/detach.
+    pub callbacks: Vec<u32>,
+}
+
+/// mem: all of memory, with the image loaded
+/// addr: address of the TLS directory
+pub fn parse_tls(mem: &[u8], addr: u32) -> anyhow::Result<TlsDirectory> {
+    let mut r = Reader::new(mem);
+    r.seek(addr as usize)?;
+    let dir = r.view::<IMAGE_TLS_DIRECTORY32>();
+    if dir.EndAddressOfRawData < dir.StartAddressOfRawData
+        || dir.EndAddressOfRawData as usize > mem.len()
+    {
+        bail!(
+            "bad TLS data range {:#x}..{:#x}",
+            dir.StartAddressOfRawData,
+            dir.EndAddressOfRawData
+        );
+    }
+    let mut tls = TlsDirectory {
+        start: dir.StartAddressOfRawData,
+        end: dir.EndAddressOfRawData,
+        zero_fill: dir.SizeOfZeroFill,
+        index_addr: dir.AddressOfIndex,
+        callbacks: Vec::new(),
+    };
+    if dir.AddressOfCallBacks != 0 {
+        // A null-terminated array of function pointers.
+        r.seek(dir.AddressOfCallBacks as usize)?;
+        loop {
+            if r.pos + 4 > mem.len() {
+                bail!(
+                    "TLS callbacks at {:#x} overrun memory",
+                    dir.AddressOfCallBacks
+                );
+            }
+            let callback = *r.view::<DWORD>();
+            if callback == 0 {
+                break;
+            }
+            tls.callbacks.push(callback);
+        }
+    }
+    Ok(tls)
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct IMAGE_BASE_RELOCATION {
+    VirtualAddress: DWORD,
+    SizeOfBlock: DWORD,
+}
+unsafe impl x86::Pod for IMAGE_BASE_RELOCATION {}
+
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+
+/// Adjust the image for being loaded delta bytes away from its preferred base.
+/// mem: the image's memory, from its base to its end
+/// addr, size: location of the base relocation table relative to mem start
+pub fn apply_relocations(
+    mem: &mut [u8],
+    addr: usize,
+    size: usize,
+    delta: u32,
+) -> anyhow::Result<()> {
+    // The table is a series of blocks, each covering a 4k page: a header followed by
+    // u16 entries, each a 4-bit type and a 12-bit offset within the page.
+    let mut patches = Vec::new();
+    let table = mem
+        .get(addr..addr + size)
+        .ok_or_else(|| anyhow!("relocations out of bounds"))?;
+    let mut r = Reader::new(table);
+    while r.pos + size_of::<IMAGE_BASE_RELOCATION>() <= table.len() {
+        let block = r.view::<IMAGE_BASE_RELOCATION>();
+        let block_size = block.SizeOfBlock as usize;
+        if block_size < size_of::<IMAGE_BASE_RELOCATION>() {
+            bail!("bad relocation block size {block_size:#x}");
+        }
+        let count = (block_size - size_of::<IMAGE_BASE_RELOCATION>()) / size_of::<WORD>();
+        if r.pos + count * size_of::<WORD>() > table.len() {
+            bail!(
+                "relocation block at {:#x} overruns table",
+                block.VirtualAddress
+            );
+        }
+        for _ in 0..count {
+            let entry = *r.view::<WORD>();
+            let ofs = block.VirtualAddress + (entry & 0xFFF) as u32;
+            match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => {} // padding
+                IMAGE_REL_BASED_HIGHLOW => patches.push(ofs),
+                ty => bail!("unhandled relocation type {ty} at {ofs:#x}"),
+            }
+        }
+    }
+
+    for ofs in patches {
+        // Relocated addresses are often unaligned, e.g. the operand of a mov.
+        let Some(bytes) = mem.get_mut(ofs as usize..ofs as usize + 4) else {
+            bail!("relocation at {ofs:#x} out of bounds");
+        };
+        let value = u32::from_le_bytes((&*bytes).try_into().unwrap());
+        bytes.copy_from_slice(&value.wrapping_add(delta).to_le_bytes());
+    }
+    Ok(())
+}
+
 #[repr(C)]
 #[derive(Debug)]
 struct IMAGE_RESOURCE_DIRECTORY {
@@ -260,17 +611,6 @@ struct IMAGE_RESOURCE_DIRECTORY {
     NumberOfIdEntries: WORD,
 }
 unsafe impl x86::Pod for IMAGE_RESOURCE_DIRECTORY {}
-impl IMAGE_RESOURCE_DIRECTORY {
-    fn entries(&self, mem: &[u8]) -> &[IMAGE_RESOURCE_DIRECTORY_ENTRY] {
-        let count = (self.NumberOfIdEntries + self.NumberOfNamedEntries) as usize;
-        // Entries are in memory immediately after the directory.
-        let mem = &mem[size_of::<IMAGE_RESOURCE_DIRECTORY>()..];
-        let mem = &mem[0..(count * size_of::<IMAGE_RESOURCE_DIRECTORY_ENTRY>()) as usize];
-        unsafe {
-            std::slice::from_raw_parts(mem.as_ptr() as *const IMAGE_RESOURCE_DIRECTORY_ENTRY, count)
-        }
-    }
-}
 
 #[repr(C)]
 #[derive(Debug)]
@@ -283,19 +623,48 @@ unsafe impl x86::Pod for IMAGE_RESOURCE_DIRECTORY_ENTRY {}
 /// Top-level dir entry.
 pub const RT_BITMAP: u32 = 2;
 
-#[derive(Debug)]
-enum ResourceName {
+/// A resource type, name, or language: either an integer ID or a string.
+#[derive(Debug, Clone)]
+pub enum ResourceName {
     Name(String),
     Id(u32),
 }
+impl ResourceName {
+    /// Whether a directory entry is for this name.  Like Windows, string names
+    /// are compared case-insensitively.
+    fn matches(&self, other: &ResourceName) -> bool {
+        match (self, other) {
+            (ResourceName::Id(a), ResourceName::Id(b)) => a == b,
+            (ResourceName::Name(a), ResourceName::Name(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+impl std::fmt::Display for ResourceName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceName::Name(name) => write!(f, "{name:?}"),
+            ResourceName::Id(id) => write!(f, "#{id}"),
+        }
+    }
+}
+
 impl IMAGE_RESOURCE_DIRECTORY_ENTRY {
-    fn name(&self) -> ResourceName {
+    /// section: the resource section, which string names are relative to.
+    fn name(&self, section: &[u8]) -> Option<ResourceName> {
         let val = self.Name;
         if val >> 31 == 0 {
-            ResourceName::Id(val)
-        } else {
-            ResourceName::Name(format!("unhandled name {val:x} in res dir"))
+            return Some(ResourceName::Id(val));
         }
+        // A length-prefixed UTF-16 string.
+        let ofs = (val & 0x7FFF_FFFF) as usize;
+        let len = u16::from_le_bytes(section.get(ofs..ofs + 2)?.try_into().unwrap()) as usize;
+        let chars = section.get(ofs + 2..ofs + 2 + len * 2)?;
+        let chars: Vec<u16> = chars
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        Some(ResourceName::Name(String::from_utf16_lossy(&chars)))
     }
     fn is_directory(&self) -> bool {
         self.OffsetToData >> 31 == 1
@@ -315,44 +684,120 @@ struct IMAGE_RESOURCE_DATA_ENTRY {
 }
 unsafe impl x86::Pod for IMAGE_RESOURCE_DATA_ENTRY {}
 
+/// The entries of the directory at ofs within the resource section.
+fn resource_dir(section: &[u8], ofs: u32) -> Option<&[IMAGE_RESOURCE_DIRECTORY_ENTRY]> {
+    let ofs = ofs as usize;
+    let dir_size = size_of::<IMAGE_RESOURCE_DIRECTORY>();
+    let dir = section
+        .get(ofs..ofs + dir_size)?
+        .view::<IMAGE_RESOURCE_DIRECTORY>(0);
+    // Entries are in memory immediately after the directory.
+    let count = (dir.NumberOfIdEntries + dir.NumberOfNamedEntries) as usize;
+    let entries_size = count * size_of::<IMAGE_RESOURCE_DIRECTORY_ENTRY>();
+    let entries = section.get(ofs + dir_size..ofs + dir_size + entries_size)?;
+    Some(unsafe {
+        std::slice::from_raw_parts(
+            entries.as_ptr() as *const IMAGE_RESOURCE_DIRECTORY_ENTRY,
+            count,
+        )
+    })
+}
+
+/// Resources are structured as generic nested directories, but in practice there are
+/// always exactly three levels: type, then name, then language.
+/// Finds the subdirectory offset for a given type (and name) along that path.
+fn resource_subdir(section: &[u8], path: &[&ResourceName]) -> Option<u32> {
+    let mut ofs = 0;
+    for query in path {
+        let entry = resource_dir(section, ofs)?.iter().find(|entry| {
+            entry.is_directory() && entry.name(section).is_some_and(|name| name.matches(query))
+        })?;
+        ofs = entry.offset();
+    }
+    Some(ofs)
+}
+
+/// A resource's data, as found by find_resource().
+#[derive(Debug)]
+pub struct Resource {
+    /// Address of its IMAGE_RESOURCE_DATA_ENTRY relative to the image base; an HRSRC.
+    pub entry: u32,
+    /// Address of the data relative to the image base.
+    pub data: u32,
+    pub size: u32,
+}
+
+/// mem: memory starting at image base
+/// entry: the Resource.entry of a resource
+pub fn resource_at(mem: &[u8], entry: u32) -> Option<Resource> {
+    let size = size_of::<IMAGE_RESOURCE_DATA_ENTRY>();
+    let data = mem
+        .get(entry as usize..entry as usize + size)?
+        .view::<IMAGE_RESOURCE_DATA_ENTRY>(0);
+    Some(Resource {
+        entry,
+        data: data.OffsetToData,
+        size: data.Size,
+    })
+}
+
+/// mem: memory starting at image base
+/// section_base: address of the resource section relative to mem start
+/// lang: the language ID wanted; falls back to language-neutral, then to whichever
+/// comes first, as Windows does for a missing translation
+pub fn find_resource(
+    mem: &[u8],
+    section_base: u32,
+    query_type: &ResourceName,
+    query_name: &ResourceName,
+    lang: Option<u32>,
+) -> Option<Resource> {
+    let section = mem.get(section_base as usize..)?;
+    let dir = resource_subdir(section, &[query_type, query_name])?;
+    let entries = resource_dir(section, dir)?;
+    let by_lang = |lang: u32| {
+        entries
+            .iter()
+            .find(|entry| matches!(entry.name(section), Some(ResourceName::Id(id)) if id == lang))
+    };
+    const LANG_NEUTRAL: u32 = 0;
+    let entry = lang
+        .and_then(by_lang)
+        .or_else(|| by_lang(LANG_NEUTRAL))
+        .or_else(|| entries.first())?;
+    if entry.is_directory() {
+        return None;
+    }
+    resource_at(mem, section_base + entry.offset())
+}
+
+/// The names of the entries of a resource directory.
+/// mem, section_base: as in find_resource()
+/// path: empty to list resource types, or a type to list the names of that type
+pub fn resource_names(mem: &[u8], section_base: u32, path: &[&ResourceName]) -> Vec<ResourceName> {
+    let Some(section) = mem.get(section_base as usize..) else {
+        return Vec::new();
+    };
+    resource_subdir(section, path)
+        .and_then(|dir| resource_dir(section, dir))
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| entry.name(section))
+        .collect()
+}
+
 pub fn get_resource(
     mem: &[u8],
     section_base: u32,
     query_type: u32,
     query_id: u32,
 ) -> Option<&[u8]> {
-    let section = &mem[section_base as usize..];
-
-    // Resources are structured as generic nested directories, but in practice there
-    // are always exactly three levels with known semantics.
-    let dir = section.view::<IMAGE_RESOURCE_DIRECTORY>(0);
-    for type_entry in dir.entries(section) {
-        assert!(type_entry.is_directory());
-        match type_entry.name() {
-            ResourceName::Id(id) if id == query_type => {}
-            _ => continue,
-        };
-        let dir = section.view::<IMAGE_RESOURCE_DIRECTORY>(type_entry.offset());
-        for id_entry in dir.entries(&section[type_entry.offset() as usize..]) {
-            assert!(id_entry.is_directory());
-            match id_entry.name() {
-                ResourceName::Id(id) if id == query_id => {}
-                _ => continue,
-            };
-            let dir = section.view::<IMAGE_RESOURCE_DIRECTORY>(id_entry.offset());
-            let entries = dir.entries(&section[id_entry.offset() as usize..]);
-            if entries.len() > 1 {
-                log::warn!("multiple res entries, picking first");
-            }
-            for lang_entry in entries {
-                assert!(!lang_entry.is_directory());
-                let data = section.view::<IMAGE_RESOURCE_DATA_ENTRY>(lang_entry.offset());
-                let ofs = data.OffsetToData as usize;
-                let len = data.Size as usize;
-                let buf = &mem[ofs..ofs + len];
-                return Some(buf);
-            }
-        }
-    }
-    None
+    let resource = find_resource(
+        mem,
+        section_base,
+        &ResourceName::Id(query_type),
+        &ResourceName::Id(query_id),
+        None,
+    )?;
+    mem.get(resource.data as usize..(resource.data + resource.size) as usize)
 }
@@ -0,0 +1,277 @@
+//! Record/replay support, so a run can be reproduced exactly and stepped backwards.
+//!
+//! While recording, every nondeterministic host result is logged as it's consumed.
+//! Re-executing from load then reproduces the run, with the host returning logged
+//! results until it catches up with the log.  Periodic checkpoints of the whole
+//! Machine, in the snapshot format, shortcut that re-execution.
+//!
+//! Host windows and surfaces outlive a rewind: they're stashed, re-execution gets
+//! placeholders in their place, and the stashed ones are handed back as the objects
+//! that own them come to exist again.
+
+use crate::{
+    host::{Host, Surface, SurfaceOptions, Window},
+    machine::Machine,
+    snapshot,
+    winapi::{self, HostObjects},
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// A nondeterministic result from the host.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Input {
+    Time(u32),
+    ReadFile {
+        path: String,
+        contents: Option<Vec<u8>>,
+    },
+}
+
+#[derive(Default)]
+struct Inputs {
+    log: Vec<Input>,
+    /// Index of the next input to consume; inputs before this have been replayed.
+    cursor: usize,
+    /// When set, suppress output and host side effects (e.g. while re-executing past
+    /// already-seen output).
+    muted: bool,
+}
+
+/// Host wrapper that records inputs, or replays them when re-executing.
+/// Clones share the underlying host and input log.
+#[derive(Clone)]
+pub struct ReplayHost {
+    inner: Rc<RefCell<Box<dyn Host>>>,
+    inputs: Rc<RefCell<Inputs>>,
+}
+
+impl ReplayHost {
+    pub fn new(inner: Box<dyn Host>) -> Self {
+        ReplayHost {
+            inner: Rc::new(RefCell::new(inner)),
+            inputs: Default::default(),
+        }
+    }
+
+    /// Return the value of the next logged input, as extracted by take, or if the log is
+    /// used up, get a new value from the host via read and log it as wrapped by wrap.
+    fn input<T: Clone>(
+        &self,
+        read: impl FnOnce(&dyn Host) -> T,
+        wrap: impl FnOnce(T) -> Input,
+        take: impl FnOnce(&Input) -> Option<T>,
+    ) -> T {
+        let mut inputs = self.inputs.borrow_mut();
+        let cursor = inputs.cursor;
+        inputs.cursor += 1;
+        if let Some(logged) = inputs.log.get(cursor) {
+            if let Some(value) = take(logged) {
+                return value;
+            }
+            // Re-execution asked for something other than what the run did.
+            log::error!("replay diverged from the log at input {cursor}; recording afresh");
+            inputs.log.truncate(cursor);
+        }
+        let value = read(self.inner.borrow().as_ref());
+        inputs.log.push(wrap(value.clone()));
+        value
+    }
+
+    pub fn inputs(&self) -> Vec<Input> {
+        self.inputs.borrow().log.clone()
+    }
+
+    fn cursor(&self) -> usize {
+        self.inputs.borrow().cursor
+    }
+
+    fn set_cursor(&self, cursor: usize) {
+        self.inputs.borrow_mut().cursor = cursor;
+    }
+
+    fn set_muted(&self, muted: bool) {
+        self.inputs.borrow_mut().muted = muted;
+    }
+}
+
+impl Host for ReplayHost {
+    fn exit(&mut self, code: u32) {
+        // The run already exited here once.
+        if self.inputs.borrow().muted {
+            return;
+        }
+        self.inner.borrow_mut().exit(code)
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        if self.inputs.borrow().muted {
+            return buf.len();
+        }
+        self.inner.borrow().write(buf)
+    }
+
+    fn time(&self) -> u32 {
+        self.input(
+            |host| host.time(),
+            Input::Time,
+            |input| match input {
+                Input::Time(time) => Some(*time),
+                _ => None,
+            },
+        )
+    }
+
+    fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        self.input(
+            |host| host.read_file(path),
+            |contents| Input::ReadFile {
+                path: path.to_string(),
+                contents,
+            },
+            |input| match input {
+                Input::ReadFile {
+                    path: logged,
+                    contents,
+                } if logged == path => Some(contents.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    fn create_window(&mut self) -> Box<dyn Window> {
+        // The window already exists on the host, and is stashed in the Replay.
+        if self.inputs.borrow().muted {
+            return snapshot::detached_window();
+        }
+        self.inner.borrow_mut().create_window()
+    }
+
+    fn create_surface(&mut self, opts: &SurfaceOptions) -> Box<dyn Surface> {
+        if self.inputs.borrow().muted {
+            return snapshot::detached_surface();
+        }
+        self.inner.borrow_mut().create_surface(opts)
+    }
+}
+
+/// Machine state at some point in a recorded run.
+pub struct Checkpoint {
+    pub instr_count: usize,
+    pub shim_calls: usize,
+    input_cursor: usize,
+    /// The Machine as saved by snapshot::save(), which elides and compresses memory,
+    /// so that keeping many checkpoints doesn't mean keeping many copies of memory.
+    snap: Vec<u8>,
+}
+
+/// Maximum number of checkpoints kept; older ones are dropped first.
+const MAX_CHECKPOINTS: usize = 16;
+
+pub struct Replay {
+    pub host: ReplayHost,
+    /// The loaded exe, for re-executing from the start.
+    pub exe: Vec<u8>,
+    pub cmdline: String,
+    /// Number of instructions between checkpoints.
+    pub interval: usize,
+    /// Sorted by instr_count.
+    checkpoints: Vec<Checkpoint>,
+    /// Furthest point the run has reached; output before here has already been seen.
+    pub high_water: usize,
+    /// Host objects of windows and surfaces that don't exist at the current point.
+    stash: HostObjects,
+}
+
+impl Replay {
+    pub fn new(host: ReplayHost, interval: usize) -> Self {
+        Replay {
+            host,
+            exe: Vec::new(),
+            cmdline: String::new(),
+            interval,
+            checkpoints: Vec::new(),
+            high_water: 0,
+            stash: HostObjects::default(),
+        }
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Whether a checkpoint is due at this point in the run.
+    pub fn due(&self, instr_count: usize) -> bool {
+        match self.checkpoints.last() {
+            Some(last) => instr_count >= last.instr_count + self.interval,
+            None => true,
+        }
+    }
+
+    pub fn checkpoint(
+        &mut self,
+        machine: &Machine,
+        instr_count: usize,
+        shim_calls: usize,
+    ) -> anyhow::Result<()> {
+        if self.checkpoints.len() == MAX_CHECKPOINTS {
+            self.checkpoints.remove(0);
+        }
+        self.checkpoints.push(Checkpoint {
+            instr_count,
+            shim_calls,
+            input_cursor: self.host.cursor(),
+            snap: snapshot::save(machine)?,
+        });
+        Ok(())
+    }
+
+    /// Find the latest checkpoint at or before target, returning its index.
+    pub fn find(&self, target: usize) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .rposition(|cp| cp.instr_count <= target)
+    }
+
+    /// Prepare to resume from the checkpoint at index (or the start of the run, if None),
+    /// given that the run is currently at instr_count with the given state, whose host
+    /// objects are stashed.  Returns the checkpoint and its parsed snapshot.
+    pub fn rewind(
+        &mut self,
+        instr_count: usize,
+        state: &mut winapi::State,
+        index: Option<usize>,
+    ) -> anyhow::Result<Option<(&Checkpoint, snapshot::Snapshot)>> {
+        self.high_water = self.high_water.max(instr_count);
+        self.host.set_muted(true);
+        state.stash_host(&mut self.stash);
+        Ok(match index {
+            Some(index) => {
+                // Later checkpoints will be retaken as execution proceeds again.
+                self.checkpoints.truncate(index + 1);
+                let cp = &self.checkpoints[index];
+                self.host.set_cursor(cp.input_cursor);
+                Some((cp, snapshot::load(&cp.snap)?))
+            }
+            None => {
+                self.checkpoints.clear();
+                self.host.set_cursor(0);
+                None
+            }
+        })
+    }
+
+    /// Called as execution passes instr_count, to unmute output past the high water mark.
+    pub fn update_muted(&self, instr_count: usize) {
+        self.host.set_muted(instr_count < self.high_water);
+    }
+
+    /// Hand stashed host objects back to the windows and surfaces of state that own them.
+    pub fn unstash(&mut self, state: &mut winapi::State) {
+        if self.stash.is_empty() {
+            return;
+        }
+        // Redrawing needs real host surfaces, not the placeholders of a muted host.
+        let mut host = self.host.inner.borrow_mut();
+        state.unstash_host(&mut **host, &mut self.stash);
+    }
+}
@@ -0,0 +1,344 @@
+//! Snapshots of a whole Machine, in a versioned container format.
+//!
+//! Layout, all integers little-endian:
+//!   header:  magic "R32SNAP\0", u32 version, u32 section count
+//!   table:   per section: [u8; 4] tag, u32 encoding, u64 stored length, u64 raw length
+//!   data:    the sections' stored bytes, back to back in table order
+//!
+//! Sections (unknown tags are skipped on load):
+//!   "CPU\0": CBOR Registers
+//!   "MEM\0": u32 memory size, then (u32 page index, page bytes) for each page that
+//!            isn't all zeros; pages are PAGE_SIZE bytes except possibly the last
+//!   "W32\0": CBOR (winapi::State, shim names)
+//!   "HOST":  CBOR winapi::HostState, for recreating windows and surfaces
+//!
+//! Encoding 0 stores a section as is; encoding 1 is raw deflate.
+//!
+//! CBOR is self-describing, so the serialized structs can evolve without a new
+//! version: unknown fields are ignored, and a field added to them needs
+//! #[serde(default)] so that snapshots from before it still load.  Changes that
+//! can't be expressed that way need a new version and a migration in load().
+//!
+//! Versions:
+//!   0: no header at all, just bincode of the X86 (memory and registers).  Loads the
+//!      CPU state only, leaving the winapi state alone.
+//!   1: the above.
+//!
+//! Host objects (windows, surfaces) deserialize as detached placeholders and are
+//! recreated through the Host when the snapshot is loaded.
+
+use crate::{
+    host,
+    machine::{Machine, Shims},
+    winapi,
+};
+use anyhow::{anyhow, bail};
+
+const MAGIC: &[u8; 8] = b"R32SNAP\0";
+pub const VERSION: u32 = 1;
+
+const TAG_CPU: [u8; 4] = *b"CPU\0";
+const TAG_MEM: [u8; 4] = *b"MEM\0";
+const TAG_W32: [u8; 4] = *b"W32\0";
+const TAG_HOST: [u8; 4] = *b"HOST";
+
+const ENCODING_RAW: u32 = 0;
+const ENCODING_DEFLATE: u32 = 1;
+
+/// Deflate can't expand data by more than this factor, which bounds the raw length
+/// a compressed section may claim.
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+/// Compression level for deflate; favors speed, as replay takes frequent snapshots.
+const DEFLATE_LEVEL: u8 = 1;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Largest memory a snapshot may declare, as zero page elision means its size
+/// isn't otherwise bounded by the file's.
+const MAX_MEM_SIZE: usize = 1 << 30;
+
+/// Stands in for a host object until attach_host() replaces it.
+struct Detached;
+
+impl host::Window for Detached {
+    fn set_title(&mut self, _title: &str) {}
+    fn set_size(&mut self, _width: u32, _height: u32) {}
+}
+
+impl host::Surface for Detached {
+    fn write_pixels(&mut self, _pixels: &[[u8; 4]]) {}
+    fn get_attached(&self) -> Box<dyn host::Surface> {
+        Box::new(Detached)
+    }
+    fn flip(&mut self) {}
+    fn bit_blt(
+        &mut self,
+        _dx: u32,
+        _dy: u32,
+        _src: &dyn host::Surface,
+        _sx: u32,
+        _sy: u32,
+        _w: u32,
+        _h: u32,
+    ) {
+    }
+}
+
+pub(crate) fn detached_window() -> Box<dyn host::Window> {
+    Box::new(Detached)
+}
+
+pub(crate) fn detached_surface() -> Box<dyn host::Surface> {
+    Box::new(Detached)
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out)?;
+    Ok(out)
+}
+
+fn decode<T: serde::de::DeserializeOwned>(raw: &[u8]) -> anyhow::Result<T> {
+    Ok(ciborium::from_reader(raw)?)
+}
+
+fn inflate(stored: &[u8], raw_len: usize) -> anyhow::Result<Vec<u8>> {
+    if raw_len > stored.len().saturating_mul(MAX_DEFLATE_RATIO) {
+        bail!(
+            "compressed section claims {raw_len} bytes from {}",
+            stored.len()
+        );
+    }
+    let raw = miniz_oxide::inflate::decompress_to_vec_with_limit(stored, raw_len)
+        .map_err(|err| anyhow!("bad compressed section: {err}"))?;
+    if raw.len() != raw_len {
+        bail!("decompressed {} bytes, expected {raw_len}", raw.len());
+    }
+    Ok(raw)
+}
+
+fn encode_mem(mem: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(mem.len() as u32).to_le_bytes());
+    for (index, page) in mem.chunks(PAGE_SIZE).enumerate() {
+        if page.iter().all(|&b| b == 0) {
+            continue;
+        }
+        out.extend_from_slice(&(index as u32).to_le_bytes());
+        out.extend_from_slice(page);
+    }
+    out
+}
+
+fn decode_mem(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = Reader(data);
+    let size = reader.u32()? as usize;
+    if size > MAX_MEM_SIZE {
+        bail!("snapshot memory size {size:#x} too large");
+    }
+    let mut mem = vec![0; size];
+    while !reader.0.is_empty() {
+        let index = reader.u32()?;
+        // usize is 32 bits on wasm, where this could otherwise wrap around.
+        let page = (index as usize)
+            .checked_mul(PAGE_SIZE)
+            .filter(|&start| start < mem.len())
+            .map(|start| &mut mem[start..])
+            .ok_or_else(|| anyhow!("page {index:#x} beyond memory"))?;
+        let len = page.len().min(PAGE_SIZE);
+        page[..len].copy_from_slice(reader.bytes(len)?);
+    }
+    Ok(mem)
+}
+
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("snapshot truncated");
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+/// Serializes the machine's state into the container format.
+pub fn save(machine: &Machine) -> anyhow::Result<Vec<u8>> {
+    let shims: Vec<&str> = machine.shims.names().collect();
+    let sections = [
+        (TAG_CPU, encode(&machine.x86.regs)?),
+        (TAG_MEM, encode_mem(&machine.x86.mem)),
+        (TAG_W32, encode(&(&machine.state, shims))?),
+        (TAG_HOST, encode(&machine.state.host_state())?),
+    ];
+
+    let count = sections.len() as u32;
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    for (tag, raw) in sections {
+        let deflated = miniz_oxide::deflate::compress_to_vec(&raw, DEFLATE_LEVEL);
+        let (encoding, stored) = if deflated.len() < raw.len() {
+            (ENCODING_DEFLATE, &deflated)
+        } else {
+            (ENCODING_RAW, &raw)
+        };
+        table.extend_from_slice(&tag);
+        table.extend_from_slice(&encoding.to_le_bytes());
+        table.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+        table.extend_from_slice(&(raw.len() as u64).to_le_bytes());
+        data.extend_from_slice(stored);
+    }
+
+    let mut out = Vec::with_capacity(16 + table.len() + data.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&table);
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+/// A snapshot parsed by load(), for Machine::load_snapshot().
+pub struct Snapshot {
+    x86: x86::Snapshot,
+    /// The winapi state and shim names, absent from version 0 snapshots.
+    win32: Option<(winapi::State, Vec<String>)>,
+    host: winapi::HostState,
+}
+
+/// Parses a snapshot in any supported version of the format.
+pub fn load(bytes: &[u8]) -> anyhow::Result<Snapshot> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return load_v0(bytes);
+    };
+    let mut reader = Reader(rest);
+    let version = reader.u32()?;
+    if version > VERSION {
+        bail!("snapshot version {version} is newer than supported version {VERSION}");
+    }
+    let count = reader.u32()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let tag: [u8; 4] = reader.bytes(4)?.try_into().unwrap();
+        let encoding = reader.u32()?;
+        let stored_len = reader.u64()? as usize;
+        let raw_len = reader.u64()? as usize;
+        entries.push((tag, encoding, stored_len, raw_len));
+    }
+
+    let (mut regs, mut mem, mut win32, mut host) = (None, None, None, None);
+    for (tag, encoding, stored_len, raw_len) in entries {
+        let stored = reader.bytes(stored_len)?;
+        let raw = match encoding {
+            ENCODING_RAW => stored.to_vec(),
+            ENCODING_DEFLATE => inflate(stored, raw_len)?,
+            _ => bail!("unknown encoding {encoding} for section {tag:?}"),
+        };
+        match tag {
+            TAG_CPU => regs = Some(decode(&raw)?),
+            TAG_MEM => mem = Some(decode_mem(&raw)?),
+            TAG_W32 => win32 = Some(decode(&raw)?),
+            TAG_HOST => host = Some(decode(&raw)?),
+            _ => log::warn!("skipping unknown snapshot section {tag:?}"),
+        }
+    }
+
+    let missing = |name: &str| anyhow!("snapshot missing {name} section");
+    Ok(Snapshot {
+        x86: x86::Snapshot {
+            mem: mem.ok_or_else(|| missing("memory"))?,
+            regs: regs.ok_or_else(|| missing("cpu"))?,
+        },
+        win32: Some(win32.ok_or_else(|| missing("win32"))?),
+        host: host.unwrap_or_default(),
+    })
+}
+
+/// Version 0's serialization of the X86.
+#[derive(serde::Deserialize)]
+struct X86V0 {
+    #[serde(with = "serde_bytes")]
+    mem: Vec<u8>,
+    regs: RegistersV0,
+}
+
+/// Registers as of version 0, which predates the FPU control word.
+#[derive(serde::Deserialize)]
+struct RegistersV0 {
+    gp: [u32; 8],
+    eip: u32,
+    segments: [u16; 6],
+    fs_addr: u32,
+    flags: x86::Flags,
+    st: [f64; 8],
+    st_top: usize,
+    fpu_status: x86::FPUStatus,
+    mm: [u64; 8],
+}
+
+impl From<RegistersV0> for x86::Registers {
+    fn from(v0: RegistersV0) -> Self {
+        let [eax, ecx, edx, ebx, esp, ebp, esi, edi] = v0.gp;
+        let [cs, ds, es, fs, gs, ss] = v0.segments;
+        x86::Registers {
+            eax,
+            ecx,
+            edx,
+            ebx,
+            esp,
+            ebp,
+            esi,
+            edi,
+            eip: v0.eip,
+            cs,
+            ds,
+            es,
+            fs,
+            gs,
+            ss,
+            fs_addr: v0.fs_addr,
+            flags: v0.flags,
+            st: v0.st,
+            st_top: v0.st_top,
+            fpu_status: v0.fpu_status,
+            mm: v0.mm,
+            ..x86::Registers::new()
+        }
+    }
+}
+
+/// Migrates a version 0 snapshot, which was only the X86.
+fn load_v0(bytes: &[u8]) -> anyhow::Result<Snapshot> {
+    let v0: X86V0 = bincode::deserialize(bytes)
+        .map_err(|err| anyhow!("not a snapshot (no header, and not version 0: {err})"))?;
+    Ok(Snapshot {
+        x86: x86::Snapshot {
+            mem: v0.mem,
+            regs: v0.regs.into(),
+        },
+        win32: None,
+        host: Default::default(),
+    })
+}
+
+impl Machine {
+    pub fn load_snapshot(&mut self, snap: Snapshot) {
+        self.x86.load_snapshot(snap.x86);
+        match snap.win32 {
+            Some((state, shims)) => {
+                self.state = state;
+                self.state.attach_host(&mut *self.host, snap.host);
+                self.shims = Shims::from_names(shims);
+            }
+            None => log::warn!("old snapshot has no win32 state; loaded cpu only"),
+        }
+    }
+}
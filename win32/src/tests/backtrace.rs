@@ -0,0 +1,86 @@
+use super::{exe_machine, Image, TestHost, CODE};
+use crate::machine::Machine;
+
+const BASE: u32 = 0x40_0000;
+const MAIN: u32 = BASE + 0x1000;
+const INNER: u32 = BASE + 0x1020;
+
+/// Loads an exe with two functions, main calling inner, then lays out a stack as
+/// if inner were running: one frame on the EBP chain, then one without a frame pointer.
+fn machine() -> Machine {
+    let mut code = vec![
+        0xe8, 0x1b, 0x00, 0x00, 0x00, // main: call inner
+        0xff, 0x54, 0x24, 0x08, // call dword ptr [esp+8]
+        0xeb, 0xfe, // jmp $
+    ];
+    code.resize((INNER - MAIN) as usize, 0x90);
+    code.push(0xc3); // inner: ret
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, code);
+
+    let mut machine = exe_machine(TestHost::default(), &image.build());
+    machine.labels.insert(MAIN, "main".into());
+    machine.labels.insert(INNER, "inner".into());
+
+    let esp = machine.x86.regs.esp - 0x100;
+    let ebp = esp + 0x10;
+    let words = [
+        // esp: locals, including values that aren't return addresses.
+        (esp, 0x1234_5678),
+        (esp + 4, MAIN + 7),
+        // The frame inner's prologue set up: the caller's ebp, and where to return.
+        (ebp, ebp + 0x10),
+        (ebp + 4, MAIN + 5),
+        // main's frame, whose saved ebp ends the chain.
+        (ebp + 0x10, 0),
+        (ebp + 0x14, 0),
+        // A return address in a frame without a frame pointer.
+        (ebp + 0x18, MAIN + 9),
+    ];
+    for (addr, value) in words {
+        machine.x86.write_u32(addr, value);
+    }
+    machine.x86.regs.eip = INNER;
+    machine.x86.regs.esp = esp;
+    machine.x86.regs.ebp = ebp;
+    machine
+}
+
+#[test]
+fn unwind() {
+    let machine = machine();
+    let ebp = machine.x86.regs.ebp;
+    let frames: Vec<_> = machine
+        .backtrace()
+        .iter()
+        .map(|frame| {
+            (
+                frame.addr,
+                frame.stack_addr,
+                frame.symbol.clone(),
+                frame.call.clone(),
+                frame.scanned,
+            )
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (INNER, None, Some("inner".into()), None, false),
+            (
+                MAIN + 5,
+                Some(ebp + 4),
+                Some("main+0x5".into()),
+                Some("call inner".into()),
+                false
+            ),
+            (
+                MAIN + 9,
+                Some(ebp + 0x18),
+                Some("main+0x9".into()),
+                Some("call dword ptr [esp+8]".into()),
+                true
+            ),
+        ]
+    );
+}
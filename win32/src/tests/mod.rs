@@ -0,0 +1,303 @@
+//! Tests that load hand-built PE images, or feed the PE parsers hand-built tables.
+
+mod backtrace;
+mod pe;
+mod replay;
+mod resources;
+mod snapshot;
+
+use crate::host::{Host, Surface, SurfaceOptions, Window};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+/// Section characteristics for Image::section().
+const CODE: u32 = 0x6000_0020;
+const DATA: u32 = 0xC000_0040;
+const RDATA: u32 = 0x4000_0040;
+
+const SECTION_ALIGNMENT: u32 = 0x1000;
+const FILE_ALIGNMENT: u32 = 0x200;
+
+fn align(x: u32, align: u32) -> u32 {
+    (x + align - 1) & !(align - 1)
+}
+
+fn put_u16(buf: &mut [u8], ofs: usize, value: u16) {
+    buf[ofs..ofs + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], ofs: usize, value: u32) {
+    buf[ofs..ofs + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+struct Section {
+    name: &'static str,
+    rva: u32,
+    data: Vec<u8>,
+    flags: u32,
+    virtual_size: u32,
+}
+
+/// Builds a minimal PE image: headers, then sections laid out one after another
+/// from RVA 0x1000.
+struct Image {
+    base: u32,
+    dll: bool,
+    entry: u32,
+    /// (rva, size) of each data directory.
+    dirs: [(u32, u32); 16],
+    sections: Vec<Section>,
+}
+
+impl Image {
+    fn new(base: u32) -> Self {
+        Image {
+            base,
+            dll: false,
+            entry: 0,
+            dirs: [(0, 0); 16],
+            sections: Vec::new(),
+        }
+    }
+
+    /// The RVA the next section will be placed at.
+    fn next_rva(&self) -> u32 {
+        match self.sections.last() {
+            Some(sec) => align(sec.rva + sec.virtual_size, SECTION_ALIGNMENT),
+            None => SECTION_ALIGNMENT,
+        }
+    }
+
+    /// Add a section at next_rva(), returning its RVA.
+    fn section(&mut self, name: &'static str, flags: u32, data: Vec<u8>) -> u32 {
+        let virtual_size = data.len() as u32;
+        self.section_sized(name, flags, data, virtual_size)
+    }
+
+    /// Like section(), but with a VirtualSize that may differ from the data's length.
+    fn section_sized(
+        &mut self,
+        name: &'static str,
+        flags: u32,
+        data: Vec<u8>,
+        virtual_size: u32,
+    ) -> u32 {
+        let rva = self.next_rva();
+        self.sections.push(Section {
+            name,
+            rva,
+            data,
+            flags,
+            virtual_size,
+        });
+        rva
+    }
+
+    fn build(&self) -> Vec<u8> {
+        let headers_size = align(
+            0x40 + 4 + 20 + 0xE0 + 40 * self.sections.len() as u32,
+            FILE_ALIGNMENT,
+        );
+        let mut out = vec![0u8; headers_size as usize];
+        out[0..2].copy_from_slice(b"MZ");
+        put_u32(&mut out, 0x3C, 0x40);
+        out[0x40..0x44].copy_from_slice(b"PE\0\0");
+
+        // IMAGE_FILE_HEADER
+        let file = 0x44;
+        put_u16(&mut out, file, 0x14C);
+        put_u16(&mut out, file + 2, self.sections.len() as u16);
+        put_u16(&mut out, file + 16, 0xE0);
+        put_u16(
+            &mut out,
+            file + 18,
+            0x0102 | if self.dll { 0x2000 } else { 0 },
+        );
+
+        // IMAGE_OPTIONAL_HEADER32
+        let opt = file + 20;
+        put_u16(&mut out, opt, 0x10B);
+        put_u32(&mut out, opt + 16, self.entry);
+        put_u32(&mut out, opt + 28, self.base);
+        put_u32(&mut out, opt + 32, SECTION_ALIGNMENT);
+        put_u32(&mut out, opt + 36, FILE_ALIGNMENT);
+        put_u16(&mut out, opt + 40, 4);
+        put_u16(&mut out, opt + 48, 4);
+        put_u32(&mut out, opt + 56, self.next_rva());
+        put_u32(&mut out, opt + 60, headers_size);
+        put_u16(&mut out, opt + 68, 3);
+        put_u32(&mut out, opt + 72, 0x10_0000);
+        put_u32(&mut out, opt + 76, 0x1000);
+        put_u32(&mut out, opt + 80, 0x10_0000);
+        put_u32(&mut out, opt + 84, 0x1000);
+        put_u32(&mut out, opt + 92, 16);
+        for (i, &(rva, size)) in self.dirs.iter().enumerate() {
+            put_u32(&mut out, opt + 96 + 8 * i, rva);
+            put_u32(&mut out, opt + 100 + 8 * i, size);
+        }
+
+        // Section table, with each section's raw data appended to the file.
+        let mut header = opt + 0xE0;
+        for sec in &self.sections {
+            let raw_size = align(sec.data.len() as u32, FILE_ALIGNMENT);
+            let raw_ptr = if raw_size == 0 { 0 } else { out.len() as u32 };
+            out[header..header + sec.name.len()].copy_from_slice(sec.name.as_bytes());
+            put_u32(&mut out, header + 8, sec.virtual_size);
+            put_u32(&mut out, header + 12, sec.rva);
+            put_u32(&mut out, header + 16, raw_size);
+            put_u32(&mut out, header + 20, raw_ptr);
+            put_u32(&mut out, header + 36, sec.flags);
+            header += 40;
+            out.extend_from_slice(&sec.data);
+            out.resize((raw_ptr + raw_size) as usize, 0);
+        }
+        out
+    }
+}
+
+/// An exe whose code just loops forever.
+fn idle_exe() -> Vec<u8> {
+    let mut image = Image::new(0x40_0000);
+    image.entry = image.section(".text", CODE, vec![0xeb, 0xfe]); // jmp $
+    image.build()
+}
+
+/// A Machine using host, with exe loaded.
+fn exe_machine(host: TestHost, exe: &[u8]) -> crate::machine::Machine {
+    let mut machine = crate::machine::Machine::new(Box::new(host));
+    crate::windows::load_exe(&mut machine, exe, "a.exe".into()).unwrap();
+    machine
+}
+
+/// Build an import directory to be placed at rva, importing symbols by name from
+/// each DLL.  Returns the directory and the RVA of each symbol's IAT slot, in order.
+fn imports(rva: u32, dlls: &[(&str, &[&str])]) -> (Vec<u8>, Vec<u32>) {
+    let mut data = vec![0u8; 20 * (dlls.len() + 1)];
+    let mut iat = Vec::new();
+    for (i, &(dll, symbols)) in dlls.iter().enumerate() {
+        let thunks = align(data.len() as u32, 4) as usize;
+        data.resize(thunks + 4 * (symbols.len() + 1), 0);
+        for (j, symbol) in symbols.iter().enumerate() {
+            let hint = data.len() as u32;
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(symbol.as_bytes());
+            data.push(0);
+            data.resize(align(data.len() as u32, 2) as usize, 0);
+            put_u32(&mut data, thunks + 4 * j, rva + hint);
+            iat.push(rva + (thunks + 4 * j) as u32);
+        }
+        let name = data.len() as u32;
+        data.extend_from_slice(dll.as_bytes());
+        data.push(0);
+        put_u32(&mut data, 20 * i + 12, rva + name);
+        put_u32(&mut data, 20 * i + 16, rva + thunks as u32);
+    }
+    (data, iat)
+}
+
+/// Pixels of a TestSurface, shared with the test.
+type Pixels = Rc<RefCell<Vec<[u8; 4]>>>;
+
+struct TestSurface {
+    pixels: Pixels,
+    width: u32,
+    height: u32,
+}
+
+impl Surface for TestSurface {
+    fn write_pixels(&mut self, pixels: &[[u8; 4]]) {
+        *self.pixels.borrow_mut() = pixels.to_vec();
+    }
+
+    /// Like the cli's, the back buffer shares the primary's pixels.
+    fn get_attached(&self) -> Box<dyn Surface> {
+        Box::new(TestSurface {
+            pixels: self.pixels.clone(),
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    fn flip(&mut self) {}
+
+    fn bit_blt(&mut self, dx: u32, dy: u32, src: &dyn Surface, sx: u32, sy: u32, w: u32, h: u32) {
+        // Like the real hosts, rely on all surfaces being this host's.
+        let src = unsafe { &*(src as *const dyn Surface as *const TestSurface) };
+        let src_pixels = src.pixels.borrow().clone();
+        let mut pixels = self.pixels.borrow_mut();
+        pixels.resize((self.width * self.height) as usize, [0; 4]);
+        for row in 0..h {
+            let dst = ((dy + row) * self.width + dx) as usize;
+            let from = ((sy + row) * src.width + sx) as usize;
+            pixels[dst..dst + w as usize].copy_from_slice(&src_pixels[from..from + w as usize]);
+        }
+    }
+}
+
+struct TestWindow {
+    titles: Rc<RefCell<Vec<String>>>,
+    index: usize,
+}
+
+impl Window for TestWindow {
+    fn set_title(&mut self, title: &str) {
+        self.titles.borrow_mut()[self.index] = title.to_string();
+    }
+
+    fn set_size(&mut self, _width: u32, _height: u32) {}
+}
+
+/// A Host whose time advances by 10 on each call, with files served from a map,
+/// and which keeps track of the windows and surfaces it creates.
+#[derive(Default)]
+struct TestHost {
+    time: Cell<u32>,
+    files: HashMap<String, Vec<u8>>,
+    /// Titles of the created windows, in order of creation.
+    windows: Rc<RefCell<Vec<String>>>,
+    /// Pixels of the created surfaces, in order of creation.
+    surfaces: Rc<RefCell<Vec<Pixels>>>,
+    /// Codes passed to exit().
+    exits: Rc<RefCell<Vec<u32>>>,
+}
+
+impl Host for TestHost {
+    fn exit(&mut self, code: u32) {
+        self.exits.borrow_mut().push(code);
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        buf.len()
+    }
+
+    fn time(&self) -> u32 {
+        self.time.set(self.time.get() + 10);
+        self.time.get()
+    }
+
+    fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.get(path).cloned()
+    }
+
+    fn create_window(&mut self) -> Box<dyn Window> {
+        let mut titles = self.windows.borrow_mut();
+        titles.push(String::new());
+        Box::new(TestWindow {
+            titles: self.windows.clone(),
+            index: titles.len() - 1,
+        })
+    }
+
+    fn create_surface(&mut self, opts: &SurfaceOptions) -> Box<dyn Surface> {
+        let pixels = Pixels::default();
+        self.surfaces.borrow_mut().push(pixels.clone());
+        Box::new(TestSurface {
+            pixels,
+            width: opts.width,
+            height: opts.height,
+        })
+    }
+}
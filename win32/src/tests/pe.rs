@@ -0,0 +1,545 @@
+use super::{
+    align, exe_machine, idle_exe, imports, put_u16, put_u32, Image, TestHost, CODE, DATA, RDATA,
+};
+use crate::{
+    machine::Machine,
+    pe::{self, Export, ImageSectionFlags},
+    winapi::{self, kernel32, types::HMODULE, ImportSymbol},
+    windows::{load_dll, load_exe, resolve_export},
+};
+use x86::Memory;
+
+const TABLE: usize = 0x800;
+
+/// Memory holding recognizable values at 0x100, 0x204 and (unaligned) 0x301, followed at TABLE by
+/// the given relocation blocks, each (page VirtualAddress, entries).
+fn relocated(blocks: &[(u32, &[u16])]) -> (Vec<u8>, usize) {
+    let mut mem = vec![0u8; 0x1000];
+    put_u32(&mut mem, 0, 0x1111_1111);
+    put_u32(&mut mem, 0x100, 0x40_1000);
+    put_u32(&mut mem, 0x204, 0x40_2000);
+    put_u32(&mut mem, 0x301, 0x40_3000);
+    let mut ofs = TABLE;
+    for &(page, entries) in blocks {
+        put_u32(&mut mem, ofs, page);
+        put_u32(&mut mem, ofs + 4, 8 + 2 * entries.len() as u32);
+        ofs += 8;
+        for &entry in entries {
+            mem[ofs..ofs + 2].copy_from_slice(&entry.to_le_bytes());
+            ofs += 2;
+        }
+    }
+    (mem, ofs - TABLE)
+}
+
+fn read_u32(mem: &[u8], ofs: usize) -> u32 {
+    u32::from_le_bytes(mem[ofs..ofs + 4].try_into().unwrap())
+}
+
+#[test]
+fn relocations() {
+    // Each block padded to a multiple of 4 bytes with an ABSOLUTE entry, whose offset
+    // (0) must be left alone.
+    let (mut mem, size) = relocated(&[
+        (0, &[0x3100, 0x0000]),
+        (0x200, &[0x3004, 0x0000]),
+        // Unaligned, like the address operand of an instruction.
+        (0x300, &[0x3001, 0x0000]),
+    ]);
+    pe::apply_relocations(&mut mem, TABLE, size, 0x10_0000).unwrap();
+    assert_eq!(read_u32(&mem, 0x100), 0x50_1000);
+    assert_eq!(read_u32(&mem, 0x204), 0x50_2000);
+    assert_eq!(read_u32(&mem, 0x301), 0x50_3000);
+    assert_eq!(read_u32(&mem, 0), 0x1111_1111);
+
+    // Negative deltas wrap.
+    pe::apply_relocations(&mut mem, TABLE, size, 0x10_0000u32.wrapping_neg()).unwrap();
+    assert_eq!(read_u32(&mem, 0x100), 0x40_1000);
+}
+
+#[test]
+fn relocation_block_overrun() {
+    let (mut mem, size) = relocated(&[(0, &[0x3100, 0x3104])]);
+    // The table ends partway through the block's entries.
+    let err = pe::apply_relocations(&mut mem, TABLE, size - 2, 0x1000).unwrap_err();
+    assert!(err.to_string().contains("overruns"), "{err}");
+    // Nothing is patched when the table is bad.
+    assert_eq!(read_u32(&mem, 0x100), 0x40_1000);
+}
+
+#[test]
+fn relocation_out_of_bounds() {
+    // The last bytes of the patched value would be past the end of the image.
+    let (mut mem, size) = relocated(&[(0, &[0x3100, 0x3FFE])]);
+    let err = pe::apply_relocations(&mut mem, TABLE, size, 0x1000).unwrap_err();
+    assert_eq!(err.to_string(), "relocation at 0xffe out of bounds");
+}
+
+#[test]
+fn relocation_unknown_type() {
+    // IMAGE_REL_BASED_DIR64, which a 32-bit image shouldn't have.
+    let (mut mem, size) = relocated(&[(0, &[0x3100, 0xA204])]);
+    let err = pe::apply_relocations(&mut mem, TABLE, size, 0x1000).unwrap_err();
+    assert_eq!(err.to_string(), "unhandled relocation type 10 at 0x204");
+    assert_eq!(read_u32(&mem, 0x100), 0x40_1000);
+}
+
+/// Build an export directory to be placed at rva.  fns are function RVAs, or for
+/// forwarded entries the forwarder string; names map to indexes into fns.
+fn export_dir(
+    rva: u32,
+    ordinal_base: u32,
+    fns: &[Result<u32, &str>],
+    names: &[(&str, u16)],
+) -> Vec<u8> {
+    let functions = 40;
+    let name_ptrs = functions + 4 * fns.len();
+    let ordinals = name_ptrs + 4 * names.len();
+    let mut data = vec![0u8; align((ordinals + 2 * names.len()) as u32, 4) as usize];
+    put_u32(&mut data, 16, ordinal_base);
+    put_u32(&mut data, 20, fns.len() as u32);
+    put_u32(&mut data, 24, names.len() as u32);
+    put_u32(&mut data, 28, rva + functions as u32);
+    put_u32(&mut data, 32, rva + name_ptrs as u32);
+    put_u32(&mut data, 36, rva + ordinals as u32);
+    let string = |data: &mut Vec<u8>, text: &str| {
+        let addr = rva + data.len() as u32;
+        data.extend_from_slice(text.as_bytes());
+        data.push(0);
+        addr
+    };
+    for (i, f) in fns.iter().enumerate() {
+        let addr = match *f {
+            Ok(addr) => addr,
+            Err(forward) => string(&mut data, forward),
+        };
+        put_u32(&mut data, functions + 4 * i, addr);
+    }
+    for (i, &(name, index)) in names.iter().enumerate() {
+        let addr = string(&mut data, name);
+        put_u32(&mut data, name_ptrs + 4 * i, addr);
+        put_u16(&mut data, ordinals + 2 * i, index);
+    }
+    data
+}
+
+fn name(name: &str) -> ImportSymbol {
+    ImportSymbol::Name(name.to_string())
+}
+
+#[test]
+fn exports() {
+    const DIR: usize = 0x100;
+    let data = export_dir(
+        DIR as u32,
+        5,
+        &[Ok(0x1000), Ok(0), Err("OTHER.Gamma"), Ok(0x1010)],
+        &[("Alpha", 0), ("Beta", 3), ("Fwd", 2), ("123", 3)],
+    );
+    let mut mem = vec![0u8; 0x1000];
+    mem[DIR..][..data.len()].copy_from_slice(&data);
+    let exports = pe::parse_exports(&mem, DIR, data.len()).unwrap();
+
+    let resolve = |sym: ImportSymbol| match exports.resolve(&sym) {
+        Some(Export::Addr(addr)) => Some(Ok(addr)),
+        Some(Export::Forward(forward)) => Some(Err(forward.to_string())),
+        None => None,
+    };
+    assert_eq!(resolve(name("Alpha")), Some(Ok(0x1000)));
+    assert_eq!(resolve(name("Beta")), Some(Ok(0x1010)));
+    assert_eq!(resolve(ImportSymbol::Ordinal(5)), Some(Ok(0x1000)));
+    assert_eq!(resolve(ImportSymbol::Ordinal(8)), Some(Ok(0x1010)));
+    assert_eq!(resolve(name("Fwd")), Some(Err("OTHER.Gamma".into())));
+    assert_eq!(
+        resolve(ImportSymbol::Ordinal(7)),
+        Some(Err("OTHER.Gamma".into()))
+    );
+    // A numeric name is still a name.
+    assert_eq!(resolve(name("123")), Some(Ok(0x1010)));
+    // An unused ordinal, ordinals outside the table, and an unknown name.
+    assert_eq!(resolve(ImportSymbol::Ordinal(6)), None);
+    assert_eq!(resolve(ImportSymbol::Ordinal(4)), None);
+    assert_eq!(resolve(ImportSymbol::Ordinal(9)), None);
+    assert_eq!(resolve(name("Delta")), None);
+}
+
+#[test]
+fn exports_out_of_bounds() {
+    const DIR: usize = 0x100;
+    let mut data = export_dir(DIR as u32, 1, &[Ok(0x1000), Ok(0x1010)], &[]);
+    // AddressOfFunctions runs off the end of memory.
+    put_u32(&mut data, 28, 0xFFC);
+    let mut mem = vec![0u8; 0x1000];
+    mem[DIR..][..data.len()].copy_from_slice(&data);
+    let err = pe::parse_exports(&mem, DIR, data.len()).unwrap_err();
+    assert_eq!(err.to_string(), "export table at 0xffc out of bounds");
+}
+
+#[test]
+fn forwarders() {
+    assert_eq!(
+        pe::parse_forwarder("NTDLL.RtlAllocateHeap"),
+        Some(("NTDLL.dll".into(), name("RtlAllocateHeap")))
+    );
+    assert_eq!(
+        pe::parse_forwarder("api-ms-win-core.1.0.#12"),
+        Some(("api-ms-win-core.1.0.dll".into(), ImportSymbol::Ordinal(12)))
+    );
+    assert_eq!(pe::parse_forwarder("NTDLL.#x"), None);
+    assert_eq!(pe::parse_forwarder("NTDLL"), None);
+}
+
+#[test]
+fn import_symbol_display() {
+    for sym in [
+        name("GetTickCount"),
+        name("123"),
+        ImportSymbol::Ordinal(123),
+    ] {
+        assert_eq!(ImportSymbol::parse(&sym.to_string()), sym);
+    }
+    assert_eq!(ImportSymbol::Ordinal(123).to_string(), "#123");
+}
+
+#[test]
+fn builtin_ordinals() {
+    // DirectSoundCreate is declared as dsound.dll's ordinal 1.
+    assert!(winapi::resolve("dsound.dll", &ImportSymbol::Ordinal(1)).is_some());
+    assert!(winapi::resolve_shim("dsound.dll!#1").is_ok());
+    assert!(winapi::resolve("dsound.dll", &ImportSymbol::Ordinal(99)).is_none());
+    // Nothing in ddraw.dll declares an ordinal.
+    assert!(winapi::resolve("ddraw.dll", &ImportSymbol::Ordinal(1)).is_none());
+}
+
+const DLL_BASE: u32 = 0x1000_0000;
+
+/// A machine running an idle exe, with a test.dll loaded that exports a local function
+/// by name and ordinal, and forwards to kernel32 and to itself.
+fn dll_machine() -> (Machine, u32) {
+    let mut dll = Image::new(DLL_BASE);
+    dll.dll = true;
+    let text = dll.section(".text", CODE, vec![0xc3; 0x20]);
+    let edata = dll.next_rva();
+    let dir = export_dir(
+        edata,
+        1,
+        &[
+            Ok(text + 0x10),
+            Err("KERNEL32.GetTickCount"),
+            Err("test.#1"),
+            Err("test.Loop"),
+        ],
+        &[
+            ("Local", 0),
+            ("Tick", 1),
+            ("Again", 2),
+            ("Loop", 3),
+            ("7", 0),
+        ],
+    );
+    dll.dirs[0] = (edata, dir.len() as u32);
+    dll.section(".edata", RDATA, dir);
+
+    let mut host = TestHost::default();
+    host.files.insert("test.dll".into(), dll.build());
+    let mut machine = exe_machine(host, &idle_exe());
+    machine.dll_paths = vec![String::new()];
+    let base = load_dll(&mut machine, "test.dll", &mut Vec::new())
+        .unwrap()
+        .unwrap();
+    (machine, base + text + 0x10)
+}
+
+#[test]
+fn resolve_exports() {
+    let (mut machine, local) = dll_machine();
+    let mut resolve =
+        |sym: ImportSymbol| resolve_export(&mut machine, "test.dll", &sym, &mut Vec::new());
+    assert_eq!(resolve(name("Local")).unwrap(), Some(local));
+    assert_eq!(resolve(ImportSymbol::Ordinal(1)).unwrap(), Some(local));
+    // "7" is a name, not ordinal 7, which doesn't exist.
+    assert_eq!(resolve(name("7")).unwrap(), Some(local));
+    assert_eq!(resolve(ImportSymbol::Ordinal(7)).unwrap(), None);
+    // Forwarded to this DLL by ordinal.
+    assert_eq!(resolve(name("Again")).unwrap(), Some(local));
+    assert_eq!(resolve(name("Missing")).unwrap(), None);
+    // A forwarder to itself never resolves.
+    assert!(resolve(name("Loop")).is_err());
+
+    // Forwarded to a builtin, which gets a shim.
+    let tick = resolve(name("Tick")).unwrap().unwrap();
+    assert_eq!(machine.shims.name(tick), Some("kernel32.dll!GetTickCount"));
+
+    let hmodule = HMODULE::from_raw(DLL_BASE);
+    assert_eq!(kernel32::GetProcAddress(&mut machine, hmodule, 1), local);
+    // A name pointer outside of memory.
+    assert_eq!(
+        kernel32::GetProcAddress(&mut machine, hmodule, 0xFFFF_0000),
+        0
+    );
+}
+
+const A_BASE: u32 = 0x1000_0000;
+const B_BASE: u32 = 0x1100_0000;
+/// Where each chain_dll() has its entry point and export.
+const ENTRY: u32 = 0x1000;
+const EXPORT: u32 = 0x1010;
+
+/// A DLL at base exporting the given name, with its imports.  Returns the image and
+/// the RVAs of its IAT slots.
+fn chain_dll(base: u32, export: &str, deps: &[(&str, &[&str])]) -> (Vec<u8>, Vec<u32>) {
+    let mut dll = Image::new(base);
+    dll.dll = true;
+    dll.entry = dll.section(".text", CODE, vec![0xc3; 0x20]);
+    let idata = dll.next_rva();
+    let (data, iat) = imports(idata, deps);
+    dll.dirs[1] = (idata, 20 * (deps.len() as u32 + 1));
+    dll.section(".idata", RDATA, data);
+    let edata = dll.next_rva();
+    let dir = export_dir(edata, 1, &[Ok(EXPORT)], &[(export, 0)]);
+    dll.dirs[0] = (edata, dir.len() as u32);
+    dll.section(".edata", RDATA, dir);
+    (dll.build(), iat)
+}
+
+#[test]
+fn dll_chain() {
+    const BASE: u32 = 0x40_0000;
+    let mut exe = Image::new(BASE);
+    exe.entry = exe.section(".text", CODE, vec![0xeb, 0xfe]); // jmp $
+    let idata = exe.next_rva();
+    let (data, iat) = imports(idata, &[("a.dll", &["FnA"])]);
+    exe.dirs[1] = (idata, 20 * 2);
+    exe.section(".idata", RDATA, data);
+
+    let mut host = TestHost::default();
+    let (a, a_iat) = chain_dll(A_BASE, "FnA", &[("b.dll", &["FnB"])]);
+    let (b, _) = chain_dll(B_BASE, "FnB", &[]);
+    host.files.insert("a.dll".into(), a);
+    host.files.insert("b.dll".into(), b);
+    let mut machine = Machine::new(Box::new(host));
+    machine.dll_paths = vec![String::new()];
+    load_exe(&mut machine, &exe.build(), "app.exe".into()).unwrap();
+
+    let mem = &machine.x86.mem;
+    assert_eq!(mem.read_u32(BASE + iat[0]), A_BASE + EXPORT);
+    assert_eq!(mem.read_u32(A_BASE + a_iat[0]), B_BASE + EXPORT);
+
+    // b.dll's DllMain runs first, returning into a.dll's, which returns into the exe.
+    assert_eq!(machine.x86.regs.eip, B_BASE + ENTRY);
+    let esp = machine.x86.regs.esp;
+    let stack: Vec<u32> = (0..8).map(|i| mem.read_u32(esp + 4 * i)).collect();
+    assert_eq!(
+        stack,
+        [
+            A_BASE + ENTRY,
+            B_BASE,
+            1, // DLL_PROCESS_ATTACH
+            0,
+            BASE + exe.entry,
+            A_BASE,
+            1,
+            0,
+        ]
+    );
+}
+
+#[test]
+fn dll_import_cycle() {
+    let mut host = TestHost::default();
+    let (a, a_iat) = chain_dll(A_BASE, "FnA", &[("b.dll", &["FnB"])]);
+    let (b, b_iat) = chain_dll(B_BASE, "FnB", &[("a.dll", &["FnA"])]);
+    host.files.insert("a.dll".into(), a);
+    host.files.insert("b.dll".into(), b);
+    let mut machine = exe_machine(host, &idle_exe());
+    machine.dll_paths = vec![String::new()];
+
+    let mut inits = Vec::new();
+    let base = load_dll(&mut machine, "a.dll", &mut inits).unwrap();
+    assert_eq!(base, Some(A_BASE));
+    // Each is initialized once, b.dll first as a.dll was still loading when b.dll
+    // imported it.
+    assert_eq!(inits, [(B_BASE + ENTRY, B_BASE), (A_BASE + ENTRY, A_BASE)]);
+    let mem = &machine.x86.mem;
+    assert_eq!(mem.read_u32(A_BASE + a_iat[0]), B_BASE + EXPORT);
+    assert_eq!(mem.read_u32(B_BASE + b_iat[0]), A_BASE + EXPORT);
+}
+
+/// Memory holding one delay import descriptor at DIR for user32.dll, importing
+/// MessageBoxA by name and ordinal 5, with its addresses offset by va_base.
+fn delay_imports(attributes: u32, va_base: u32) -> Vec<u8> {
+    const DIR: usize = 0x100;
+    let mut mem = vec![0u8; 0x400];
+    let fields = [
+        attributes,
+        0x200 + va_base, // DllNameRVA
+        0x210 + va_base, // ModuleHandleRVA
+        0x220 + va_base, // ImportAddressTableRVA
+        0x240 + va_base, // ImportNameTableRVA
+    ];
+    for (i, &field) in fields.iter().enumerate() {
+        put_u32(&mut mem, DIR + 4 * i, field);
+    }
+    // The following descriptor is zero, ending the table.
+    mem[0x200..][..10].copy_from_slice(b"USER32.dll");
+    put_u32(&mut mem, 0x240, 0x260 + va_base);
+    put_u32(&mut mem, 0x244, 0x8000_0005);
+    // Hint, then name.
+    mem[0x262..][..11].copy_from_slice(b"MessageBoxA");
+    mem
+}
+
+#[test]
+fn delay_imports_rva_based() {
+    let mem = delay_imports(1, 0);
+    let imports = pe::parse_delay_imports(&mem, 0x100, 0x40_0000).unwrap();
+    assert_eq!(imports.len(), 1);
+    assert_eq!(imports[0].dll, "USER32.dll");
+    assert_eq!(imports[0].hmodule_addr, 0x210);
+    assert_eq!(
+        imports[0].symbols,
+        [
+            (name("MessageBoxA"), 0x220),
+            (ImportSymbol::Ordinal(5), 0x224)
+        ]
+    );
+}
+
+#[test]
+fn delay_imports_va_based() {
+    // VC6 wrote virtual addresses, with Attributes 0.
+    let mem = delay_imports(0, 0x40_0000);
+    let imports = pe::parse_delay_imports(&mem, 0x100, 0x40_0000).unwrap();
+    assert_eq!(imports.len(), 1);
+    assert_eq!(imports[0].dll, "USER32.dll");
+    assert_eq!(imports[0].hmodule_addr, 0x210);
+    assert_eq!(
+        imports[0].symbols,
+        [
+            (name("MessageBoxA"), 0x220),
+            (ImportSymbol::Ordinal(5), 0x224)
+        ]
+    );
+
+    // Loaded somewhere else, the addresses fall below the image base.
+    let Err(err) = pe::parse_delay_imports(&mem, 0x100, 0x50_0000) else {
+        panic!("expected an error");
+    };
+    assert!(err.to_string().contains("below image base"), "{err}");
+}
+
+/// Memory holding a TLS directory at 0x100 with the given fields, and a callback
+/// array at 0x200.
+fn tls_dir(start: u32, end: u32, zero_fill: u32, callbacks: &[u32]) -> Vec<u8> {
+    let mut mem = vec![0u8; 0x400];
+    for (i, field) in [start, end, 0x300, 0x200, zero_fill]
+        .into_iter()
+        .enumerate()
+    {
+        put_u32(&mut mem, 0x100 + 4 * i, field);
+    }
+    for (i, &callback) in callbacks.iter().enumerate() {
+        put_u32(&mut mem, 0x200 + 4 * i, callback);
+    }
+    mem
+}
+
+#[test]
+fn tls() {
+    let mem = tls_dir(0x180, 0x190, 0x20, &[0x40_1000, 0x40_1010]);
+    let tls = pe::parse_tls(&mem, 0x100).unwrap();
+    assert_eq!((tls.start, tls.end, tls.zero_fill), (0x180, 0x190, 0x20));
+    assert_eq!(tls.index_addr, 0x300);
+    assert_eq!(tls.callbacks, [0x40_1000, 0x40_1010]);
+
+    let err = pe::parse_tls(&tls_dir(0x190, 0x180, 0, &[]), 0x100).unwrap_err();
+    assert_eq!(err.to_string(), "bad TLS data range 0x190..0x180");
+    let err = pe::parse_tls(&tls_dir(0x180, 0x1000, 0, &[]), 0x100).unwrap_err();
+    assert_eq!(err.to_string(), "bad TLS data range 0x180..0x1000");
+
+    // A callback array without its terminator, running to the end of memory.
+    let mut mem = tls_dir(0x180, 0x190, 0, &[]);
+    mem[0x200..].fill(0xCC);
+    let err = pe::parse_tls(&mem, 0x100).unwrap_err();
+    assert_eq!(err.to_string(), "TLS callbacks at 0x200 overrun memory");
+}
+
+/// An idle exe with static TLS: initial data "abcd" followed by zero_fill zero bytes.
+fn tls_exe(zero_fill: u32) -> Vec<u8> {
+    const BASE: u32 = 0x40_0000;
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, vec![0xeb, 0xfe]); // jmp $
+    let rva = image.next_rva();
+    let va = BASE + rva;
+    // Initial data, then the index slot, an empty callback array, and the directory.
+    let mut data = vec![0u8; 16 + 24];
+    data[..4].copy_from_slice(b"abcd");
+    put_u32(&mut data, 8, 0xFFFF_FFFF);
+    for (i, field) in [va, va + 4, va + 8, va + 12, zero_fill]
+        .into_iter()
+        .enumerate()
+    {
+        put_u32(&mut data, 16 + 4 * i, field);
+    }
+    image.dirs[9] = (rva + 16, 24);
+    image.section(".data", DATA, data);
+    image.build()
+}
+
+#[test]
+fn static_tls() {
+    let machine = exe_machine(TestHost::default(), &tls_exe(4));
+    let mem = &machine.x86.mem;
+    let index = mem.read_u32(0x40_2008);
+    assert_eq!(index, 0);
+    // fs:[2Ch] is the thread's array of TLS blocks.
+    let blocks = mem.read_u32(machine.x86.regs.fs_addr + 0x2c);
+    let block = mem.read_u32(blocks + index * 4) as usize;
+    assert_eq!(&mem[block..block + 8], b"abcd\0\0\0\0");
+}
+
+#[test]
+fn static_tls_too_large() {
+    let mut machine = Machine::new(Box::new(TestHost::default()));
+    let err = load_exe(&mut machine, &tls_exe(0x1000_0000), "app.exe".into()).unwrap_err();
+    assert!(err.to_string().contains("too large"), "{err}");
+}
+
+#[test]
+fn section_sizes() {
+    const BASE: u32 = 0x40_0000;
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, vec![0xeb, 0xfe]); // jmp $
+
+    // A .bss-like tail: 0x3000 bytes in memory, only the first 0x200 from the file.
+    let data = image.section_sized(".data", DATA, vec![0xAA; 0x200], 0x3000);
+    // No VirtualSize, so it gets SizeOfRawData.
+    let rdata = image.section_sized(".rdata", RDATA, vec![0xBB; 0x10], 0);
+
+    let mut machine = Machine::new(Box::new(TestHost::default()));
+    // Garbage where the image will go, which the loader must clear.
+    machine.x86.mem.resize(0x50_0000, 0);
+    machine.x86.mem[BASE as usize..].fill(0xCC);
+    load_exe(&mut machine, &image.build(), "app.exe".into()).unwrap();
+
+    let mappings = &machine.state.kernel32.mappings;
+    // The size of the mapping at addr, and whether it holds initialized data.
+    let mapping = |addr: u32| {
+        let mapping = mappings.vec().iter().find(|m| m.addr == addr).unwrap();
+        (
+            mapping.size,
+            mapping.flags.contains(ImageSectionFlags::INITIALIZED_DATA),
+        )
+    };
+    assert_eq!(mapping(BASE + data), (0x3000, true));
+    assert_eq!(mapping(BASE + rdata), (0x1000, true));
+
+    let mem = &machine.x86.mem;
+    let data = (BASE + data) as usize;
+    assert!(mem[data..data + 0x200].iter().all(|&b| b == 0xAA));
+    assert!(mem[data + 0x200..data + 0x3000].iter().all(|&b| b == 0));
+    let rdata = (BASE + rdata) as usize;
+    assert!(mem[rdata..rdata + 0x10].iter().all(|&b| b == 0xBB));
+    // The file's padding of the raw data, then the rest of the page.
+    assert!(mem[rdata + 0x10..rdata + 0x1000].iter().all(|&b| b == 0));
+}
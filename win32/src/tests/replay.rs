@@ -0,0 +1,172 @@
+use super::{imports, Image, TestHost, CODE, DATA, RDATA};
+use crate::machine::Runner;
+use std::{cell::RefCell, rc::Rc};
+use x86::Memory;
+
+const BASE: u32 = 0x40_0000;
+const VAR: u32 = BASE + 0x2000;
+/// Address of the instruction in the loop that writes VAR.
+const STORE: u32 = BASE + 0x1006;
+
+/// An exe that loops forever storing GetTickCount() to VAR and counting iterations
+/// in VAR+4.
+fn exe() -> Vec<u8> {
+    let (idata, iat) = imports(0x3000, &[("kernel32.dll", &["GetTickCount"])]);
+    let mut code = vec![0xff, 0x15]; // call [GetTickCount]
+    code.extend_from_slice(&(BASE + iat[0]).to_le_bytes());
+    code.push(0xa3); // mov [VAR], eax
+    code.extend_from_slice(&VAR.to_le_bytes());
+    code.extend_from_slice(&[0xff, 0x05]); // inc dword ptr [VAR+4]
+    code.extend_from_slice(&(VAR + 4).to_le_bytes());
+    code.extend_from_slice(&[0xeb, 0xed]); // jmp back to the call
+
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, code);
+    image.section(".data", DATA, vec![0; 8]);
+    let idata_rva = image.section(".idata", RDATA, idata);
+    image.dirs[1] = (idata_rva, 20 * 2);
+    image.build()
+}
+
+fn recorded() -> Runner {
+    // A short interval, so that checkpoints are both used and dropped.
+    let mut runner = Runner::new_recorded(Box::new(TestHost::default()), 7);
+    runner.load_exe(&exe(), "app.exe".into()).unwrap();
+    runner
+}
+
+/// The parts of the machine state the exe touches.
+fn state(runner: &Runner) -> [u32; 5] {
+    let x86 = &runner.machine.x86;
+    [
+        x86.regs.eip,
+        x86.regs.eax,
+        x86.regs.esp,
+        x86.mem.read_u32(VAR),
+        x86.mem.read_u32(VAR + 4),
+    ]
+}
+
+/// Run forward count instructions, returning the state at each instruction count.
+fn record(runner: &mut Runner, count: usize) -> Vec<[u32; 5]> {
+    let mut states = vec![state(runner)];
+    for _ in 0..count {
+        assert!(runner.step().unwrap());
+        states.push(state(runner));
+    }
+    states
+}
+
+#[test]
+fn step_back() {
+    let mut runner = recorded();
+    let states = record(&mut runner, 200);
+    while runner.instr_count > 0 {
+        runner.step_back().unwrap();
+        assert_eq!(state(&runner), states[runner.instr_count]);
+    }
+    assert!(runner.step_back().is_err());
+}
+
+#[test]
+fn seek() {
+    let mut runner = recorded();
+    let states = record(&mut runner, 200);
+    for target in [150, 3, 199, 0, 120, 200] {
+        runner.seek(target).unwrap();
+        assert_eq!(runner.instr_count, target);
+        assert_eq!(state(&runner), states[target]);
+    }
+    // GetTickCount results were replayed from the log rather than asked for again.
+    assert_eq!(runner.replay.as_ref().unwrap().host.inputs().len(), 50);
+}
+
+#[test]
+fn run_back_to_write() {
+    let mut runner = recorded();
+    let states = record(&mut runner, 102);
+    let last_store = (0..102).rev().find(|&i| states[i][0] == STORE).unwrap();
+
+    assert!(runner.run_back_to_write(VAR..VAR + 4).unwrap());
+    assert_eq!(runner.instr_count, last_store);
+    assert_eq!(state(&runner), states[last_store]);
+
+    // The counter is also written by the loop, but just after the store.
+    assert!(runner.run_back_to_write(VAR + 4..VAR + 8).unwrap());
+    assert_eq!(runner.instr_count, last_store - 3);
+
+    // Nothing writes past the variables, which leaves the position alone.
+    let pos = runner.instr_count;
+    assert!(!runner.run_back_to_write(VAR + 8..VAR + 12).unwrap());
+    assert_eq!(runner.instr_count, pos);
+    assert_eq!(state(&runner), states[pos]);
+}
+
+/// An exe that creates a window titled "replay", exits with code 7, then loops.
+fn window_exe() -> Vec<u8> {
+    let title = BASE + 0x2000;
+    let (idata, iat) = imports(
+        0x3000,
+        &[
+            ("kernel32.dll", &["ExitProcess"]),
+            ("user32.dll", &["CreateWindowExA"]),
+        ],
+    );
+    let mut code = Vec::new();
+    // CreateWindowExA(0, NULL, title, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+    for _ in 0..9 {
+        code.extend_from_slice(&[0x6a, 0x00]); // push 0
+    }
+    code.push(0x68); // push title
+    code.extend_from_slice(&title.to_le_bytes());
+    code.extend_from_slice(&[0x6a, 0x00, 0x6a, 0x00]);
+    code.extend_from_slice(&[0xff, 0x15]); // call [CreateWindowExA]
+    code.extend_from_slice(&(BASE + iat[1]).to_le_bytes());
+    code.extend_from_slice(&[0x6a, 0x07]); // push 7
+    code.extend_from_slice(&[0xff, 0x15]); // call [ExitProcess]
+    code.extend_from_slice(&(BASE + iat[0]).to_le_bytes());
+    code.extend_from_slice(&[0xeb, 0xfe]); // jmp $
+
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, code);
+    image.section(".data", DATA, b"replay\0".to_vec());
+    let idata_rva = image.section(".idata", RDATA, idata);
+    image.dirs[1] = (idata_rva, 20 * 3);
+    image.build()
+}
+
+#[test]
+fn host_objects_survive_rewind() {
+    let exits = Rc::new(RefCell::new(Vec::new()));
+    let host = TestHost {
+        exits: exits.clone(),
+        ..Default::default()
+    };
+    let windows = host.windows.clone();
+    let mut runner = Runner::new_recorded(Box::new(host), 7);
+    runner.load_exe(&window_exe(), "app.exe".into()).unwrap();
+    while exits.borrow().is_empty() {
+        runner.step().unwrap();
+    }
+    let end = runner.instr_count;
+    assert_eq!(*windows.borrow(), ["replay"]);
+
+    // Rewinding through every position, which restores both from checkpoints and
+    // from a fresh load, doesn't create the window or exit again.
+    while runner.instr_count > 0 {
+        runner.step_back().unwrap();
+    }
+    runner.seek(end).unwrap();
+    assert_eq!(*windows.borrow(), ["replay"]);
+    assert_eq!(*exits.borrow(), [7]);
+
+    // The window handed back after all that is the host's.
+    runner
+        .machine
+        .state
+        .user32
+        .get_window(1)
+        .host
+        .set_title("live");
+    assert_eq!(*windows.borrow(), ["live"]);
+}
@@ -0,0 +1,246 @@
+use super::{align, exe_machine, imports, put_u16, put_u32, Image, TestHost, CODE, DATA, RDATA};
+use crate::{
+    machine::Runner,
+    pe::{self, ResourceName},
+    winapi::{kernel32, types::HMODULE},
+};
+use x86::Memory;
+
+/// A resource type, name or language in a resource tree.
+#[derive(Clone, Copy)]
+enum Key {
+    Id(u32),
+    Name(&'static str),
+}
+
+/// Each name's data by language, as (language, data).
+type Langs = &'static [(u32, &'static [u8])];
+
+const RT_BITMAP: u32 = 2;
+
+/// Bitmaps 5, 7 and 123, where 5 has only a Japanese translation, 7 a neutral one,
+/// and 123 English and German; and a custom "CUSTOM" type holding "Data".
+const TREE: &[(Key, &[(Key, Langs)])] = &[
+    (
+        Key::Id(RT_BITMAP),
+        &[
+            (Key::Id(123), &[(0x409, b"en"), (0x407, b"de")]),
+            (Key::Id(5), &[(0x411, b"ja")]),
+            (Key::Id(7), &[(0x409, b"en7"), (0, b"neutral")]),
+        ],
+    ),
+    (
+        Key::Name("CUSTOM"),
+        &[(Key::Name("Data"), &[(0, b"custom")])],
+    ),
+];
+
+/// Build a resource section from TREE, to be placed at rva.
+fn resources(rva: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    // Appends a directory of count entries, returning the offset of the entries.
+    let dir = |out: &mut Vec<u8>, count: usize| {
+        out.resize(align(out.len() as u32, 4) as usize, 0);
+        let ofs = out.len();
+        out.resize(ofs + 16 + 8 * count, 0);
+        put_u16(out, ofs + 14, count as u16);
+        ofs
+    };
+    // Fills in an entry, appending its name if it's a string.
+    let entry = |out: &mut Vec<u8>, at: usize, key: Key, target: u32| {
+        let name = match key {
+            Key::Id(id) => id,
+            Key::Name(name) => {
+                let ofs = out.len() as u32;
+                out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                for c in name.encode_utf16() {
+                    out.extend_from_slice(&c.to_le_bytes());
+                }
+                ofs | 1 << 31
+            }
+        };
+        put_u32(out, at, name);
+        put_u32(out, at + 4, target);
+    };
+
+    let types = dir(&mut out, TREE.len());
+    for (i, &(typ, names)) in TREE.iter().enumerate() {
+        let names_dir = dir(&mut out, names.len());
+        entry(
+            &mut out,
+            types + 16 + 8 * i,
+            typ,
+            names_dir as u32 | 1 << 31,
+        );
+        for (j, &(name, langs)) in names.iter().enumerate() {
+            let langs_dir = dir(&mut out, langs.len());
+            entry(
+                &mut out,
+                names_dir + 16 + 8 * j,
+                name,
+                langs_dir as u32 | 1 << 31,
+            );
+            for (k, &(lang, data)) in langs.iter().enumerate() {
+                out.resize(align(out.len() as u32, 4) as usize, 0);
+                let data_entry = out.len();
+                out.resize(data_entry + 16, 0);
+                let data_addr = rva + out.len() as u32;
+                put_u32(&mut out, data_entry, data_addr);
+                put_u32(&mut out, data_entry + 4, data.len() as u32);
+                out.extend_from_slice(data);
+                entry(
+                    &mut out,
+                    langs_dir + 16 + 8 * k,
+                    Key::Id(lang),
+                    data_entry as u32,
+                );
+            }
+        }
+    }
+    out
+}
+
+const RSRC: u32 = 0x1000;
+
+/// Memory starting at an image base, with the resource section at RSRC.
+fn image_mem() -> Vec<u8> {
+    let mut mem = vec![0u8; RSRC as usize];
+    mem.extend_from_slice(&resources(RSRC));
+    mem
+}
+
+/// The data of a resource found with find_resource().
+fn find(mem: &[u8], typ: ResourceName, name: ResourceName, lang: Option<u32>) -> Option<&[u8]> {
+    let resource = pe::find_resource(mem, RSRC, &typ, &name, lang)?;
+    mem.get(resource.data as usize..(resource.data + resource.size) as usize)
+}
+
+fn id(id: u32) -> ResourceName {
+    ResourceName::Id(id)
+}
+
+fn name(name: &str) -> ResourceName {
+    ResourceName::Name(name.to_string())
+}
+
+#[test]
+fn languages() {
+    let mem = image_mem();
+    let bitmap = || id(RT_BITMAP);
+    assert_eq!(find(&mem, bitmap(), id(123), Some(0x407)), Some(&b"de"[..]));
+    assert_eq!(find(&mem, bitmap(), id(123), Some(0x409)), Some(&b"en"[..]));
+    // A missing translation falls back to the neutral one if there is one...
+    assert_eq!(
+        find(&mem, bitmap(), id(7), Some(0x40c)),
+        Some(&b"neutral"[..])
+    );
+    assert_eq!(find(&mem, bitmap(), id(7), None), Some(&b"neutral"[..]));
+    // ...and otherwise to the first.
+    assert_eq!(find(&mem, bitmap(), id(123), Some(0x40c)), Some(&b"en"[..]));
+    assert_eq!(find(&mem, bitmap(), id(5), None), Some(&b"ja"[..]));
+    assert_eq!(find(&mem, bitmap(), id(6), None), None);
+}
+
+#[test]
+fn names() {
+    let mem = image_mem();
+    assert_eq!(
+        find(&mem, name("CUSTOM"), name("Data"), None),
+        Some(&b"custom"[..])
+    );
+    // String names match case-insensitively, and never match IDs.
+    assert_eq!(
+        find(&mem, name("custom"), name("DATA"), None),
+        Some(&b"custom"[..])
+    );
+    assert_eq!(find(&mem, name("2"), id(123), None), None);
+
+    let names = |path: &[&ResourceName]| -> Vec<String> {
+        pe::resource_names(&mem, RSRC, path)
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    };
+    assert_eq!(names(&[]), ["#2", "\"CUSTOM\""]);
+    assert_eq!(names(&[&id(RT_BITMAP)]), ["#123", "#5", "#7"]);
+    assert_eq!(names(&[&name("Custom")]), ["\"Data\""]);
+    assert!(names(&[&id(3)]).is_empty());
+}
+
+#[test]
+fn find_resource_args() {
+    const BASE: u32 = 0x40_0000;
+    let mut image = Image::new(BASE);
+    image.entry = image.section(".text", CODE, vec![0xeb, 0xfe]); // jmp $
+    let rsrc = image.next_rva();
+    let data = resources(rsrc);
+    image.dirs[2] = (rsrc, data.len() as u32);
+    image.section(".rsrc", RDATA, data);
+    let strings = image.section(".data", DATA, b"#123\0custom\0Data\0".to_vec());
+
+    let mut machine = exe_machine(TestHost::default(), &image.build());
+    let exe = HMODULE::null();
+    let str_at = |ofs: u32| BASE + strings + ofs;
+
+    // "#123" means the ID 123.
+    let by_string = kernel32::FindResourceA(&mut machine, exe, str_at(0), RT_BITMAP);
+    let by_id = kernel32::FindResourceA(&mut machine, exe, 123, RT_BITMAP);
+    assert_ne!(by_id, 0);
+    assert_eq!(by_string, by_id);
+    assert_ne!(
+        kernel32::FindResourceA(&mut machine, exe, str_at(12), str_at(5)),
+        0
+    );
+
+    // Pointers outside of memory aren't found.
+    assert_eq!(
+        kernel32::FindResourceA(&mut machine, exe, 0xFFFF_0000, RT_BITMAP),
+        0
+    );
+    assert_eq!(
+        kernel32::FindResourceW(&mut machine, exe, 123, 0xFFFF_0000),
+        0
+    );
+}
+
+#[test]
+fn enum_names_freed() {
+    const BASE: u32 = 0x40_0000;
+    // Sections: imports, then code, then var.
+    const CODE_RVA: u32 = 0x2000;
+    const CALLBACK: u32 = BASE + CODE_RVA + 0x20;
+    const VAR: u32 = BASE + 0x3000;
+    let mut image = Image::new(BASE);
+    let (idata, iat) = imports(0x1000, &[("kernel32.dll", &["EnumResourceTypesA"])]);
+    image.dirs[1] = (image.section(".idata", RDATA, idata), 20 * 2);
+
+    let mut code = vec![0x6a, 0x00, 0x68]; // push 0; push callback
+    code.extend_from_slice(&CALLBACK.to_le_bytes());
+    code.extend_from_slice(&[0x6a, 0x00, 0xff, 0x15]); // push 0; call [EnumResourceTypesA]
+    code.extend_from_slice(&(BASE + iat[0]).to_le_bytes());
+    let done = BASE + CODE_RVA + code.len() as u32;
+    code.extend_from_slice(&[0xeb, 0xfe]); // jmp $
+    code.resize(0x20, 0x90);
+    // callback(hModule, lpType, lParam): store lpType to VAR and return TRUE.
+    code.extend_from_slice(&[0x8b, 0x44, 0x24, 0x08, 0xa3]);
+    code.extend_from_slice(&VAR.to_le_bytes());
+    code.extend_from_slice(&[0xb8, 1, 0, 0, 0, 0xc2, 0x0c, 0x00]);
+    image.entry = image.section(".text", CODE, code);
+    image.section(".data", DATA, vec![0; 4]);
+    let rsrc = image.next_rva();
+    let data = resources(rsrc);
+    image.dirs[2] = (rsrc, data.len() as u32);
+    image.section(".rsrc", RDATA, data);
+
+    let mut runner = Runner::new(Box::new(TestHost::default()));
+    runner.load_exe(&image.build(), "app.exe".into()).unwrap();
+    while runner.machine.x86.regs.eip != done {
+        assert!(runner.step().unwrap());
+    }
+    // The last type passed was the copy of "CUSTOM", whose block was freed after the
+    // callbacks and so is handed out again.
+    let machine = &mut runner.machine;
+    let name = machine.x86.mem.read_u32(VAR);
+    let heap = kernel32::GetProcessHeap(machine);
+    assert_eq!(kernel32::HeapAlloc(machine, heap, 0, 7), name);
+}
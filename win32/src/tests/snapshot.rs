@@ -0,0 +1,222 @@
+use super::{exe_machine, idle_exe, TestHost};
+use crate::{
+    machine::Machine,
+    snapshot,
+    winapi::{
+        ddraw::{Surface, SurfaceParams},
+        kernel32, user32,
+    },
+    SurfaceOptions,
+};
+
+const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+const BLUE: [u8; 4] = [0, 0, 0xFF, 0xFF];
+
+fn surface(machine: &mut Machine, primary: bool, color: [u8; 4]) -> Surface {
+    let host = machine.host.create_surface(&SurfaceOptions {
+        width: 2,
+        height: 2,
+        primary,
+    });
+    let mut surface = Surface {
+        host,
+        width: 2,
+        height: 2,
+        pixels: Vec::new(),
+        params: SurfaceParams {
+            primary,
+            attached_to: None,
+        },
+    };
+    surface.write_pixels(&[color; 4]);
+    surface
+}
+
+#[test]
+fn round_trip() {
+    let mut machine = exe_machine(TestHost::default(), &idle_exe());
+    let heap = kernel32::HeapCreate(&mut machine, 0, 0x1000, 0);
+    let block = kernel32::HeapAlloc(&mut machine, heap, 0, 24);
+    user32::CreateWindowExA(
+        &mut machine,
+        0,
+        None,
+        Some("snap"),
+        0,
+        0,
+        0,
+        320,
+        200,
+        0,
+        0,
+        0,
+        0,
+    );
+    let offscreen = surface(&mut machine, false, RED);
+    let primary = surface(&mut machine, true, BLUE);
+    machine.state.ddraw.surfaces.insert(0x100, offscreen);
+    machine.state.ddraw.surfaces.insert(0x200, primary);
+
+    let bytes = snapshot::save(&machine).unwrap();
+    let host = TestHost::default();
+    let (windows, surfaces) = (host.windows.clone(), host.surfaces.clone());
+    let mut loaded = Machine::new(Box::new(host));
+    loaded.load_snapshot(snapshot::load(&bytes).unwrap());
+
+    assert_eq!(loaded.x86.mem, machine.x86.mem);
+    assert_eq!(loaded.x86.regs.eip, machine.x86.regs.eip);
+    assert_eq!(loaded.x86.regs.esp, machine.x86.regs.esp);
+    assert!(loaded.shims.names().eq(machine.shims.names()));
+    let mappings = |machine: &Machine| format!("{:?}", machine.state.kernel32.mappings.vec());
+    assert_eq!(mappings(&loaded), mappings(&machine));
+
+    // The heap carries on where it left off.
+    assert_eq!(kernel32::HeapSize(&mut loaded, heap, 0, block), 24);
+    assert_eq!(
+        kernel32::HeapAlloc(&mut loaded, heap, 0, 8),
+        kernel32::HeapAlloc(&mut machine, heap, 0, 8)
+    );
+
+    // Host objects were recreated, with surfaces redrawn.
+    assert_eq!(*windows.borrow(), ["snap"]);
+    let surface = |addr: u32| loaded.state.ddraw.surfaces[&addr].pixels.clone();
+    assert_eq!(surface(0x100), [RED; 4]);
+    assert_eq!(surface(0x200), [BLUE; 4]);
+    let colors: Vec<[u8; 4]> = surfaces
+        .borrow()
+        .iter()
+        .filter_map(|pixels| pixels.borrow().first().copied())
+        .collect();
+    assert!(colors.contains(&RED));
+    assert!(colors.contains(&BLUE));
+}
+
+/// A snapshot section: tag, encoding, stored bytes and raw length.
+type Section = ([u8; 4], u32, Vec<u8>, u64);
+
+/// Split a snapshot into its sections.
+fn sections(bytes: &[u8]) -> Vec<Section> {
+    let u32_at = |ofs: usize| u32::from_le_bytes(bytes[ofs..ofs + 4].try_into().unwrap());
+    let u64_at = |ofs: usize| u64::from_le_bytes(bytes[ofs..ofs + 8].try_into().unwrap());
+    let count = u32_at(12) as usize;
+    let mut data = 16 + 24 * count;
+    (0..count)
+        .map(|i| {
+            let entry = 16 + 24 * i;
+            let stored_len = u64_at(entry + 8) as usize;
+            let stored = bytes[data..data + stored_len].to_vec();
+            data += stored_len;
+            let tag = bytes[entry..entry + 4].try_into().unwrap();
+            (tag, u32_at(entry + 4), stored, u64_at(entry + 16))
+        })
+        .collect()
+}
+
+/// Assemble a version 1 snapshot from sections.
+fn container(sections: &[Section]) -> Vec<u8> {
+    let mut out = b"R32SNAP\0".to_vec();
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    for (tag, encoding, stored, raw_len) in sections {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&encoding.to_le_bytes());
+        out.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+        out.extend_from_slice(&raw_len.to_le_bytes());
+    }
+    for (_, _, stored, _) in sections {
+        out.extend_from_slice(stored);
+    }
+    out
+}
+
+fn idle_machine() -> Machine {
+    exe_machine(TestHost::default(), &idle_exe())
+}
+
+#[test]
+fn unknown_section() {
+    let machine = idle_machine();
+    let mut sections = sections(&snapshot::save(&machine).unwrap());
+    sections.insert(1, (*b"XTRA", 0, b"future".to_vec(), 6));
+
+    let mut loaded = Machine::new(Box::new(TestHost::default()));
+    loaded.load_snapshot(snapshot::load(&container(&sections)).unwrap());
+    assert_eq!(loaded.x86.mem, machine.x86.mem);
+    assert_eq!(loaded.x86.regs.eip, machine.x86.regs.eip);
+}
+
+#[test]
+fn truncated() {
+    let bytes = snapshot::save(&idle_machine()).unwrap();
+    for len in [0, 4, 12, 20, 40, bytes.len() / 2, bytes.len() - 1] {
+        assert!(snapshot::load(&bytes[..len]).is_err(), "{len}");
+    }
+}
+
+#[test]
+fn untrusted_sizes() {
+    // Memory far larger than any program uses.
+    let mem = u32::MAX.to_le_bytes().to_vec();
+    let bytes = container(&[(*b"MEM\0", 0, mem, 4)]);
+    assert!(snapshot::load(&bytes).is_err());
+
+    // A page index that would overflow, or wrap around on 32-bit hosts.
+    let mut mem = 0x2000u32.to_le_bytes().to_vec();
+    mem.extend_from_slice(&u32::MAX.to_le_bytes());
+    mem.extend_from_slice(&[1; 0x1000]);
+    let len = mem.len() as u64;
+    let bytes = container(&[(*b"MEM\0", 0, mem, len)]);
+    let Err(err) = snapshot::load(&bytes) else {
+        panic!("expected an error");
+    };
+    assert!(
+        format!("{err:#}").contains("page 0xffffffff beyond memory"),
+        "{err:#}"
+    );
+
+    // A compressed section claiming more than deflate can expand to.
+    let bytes = container(&[(*b"W32\0", 1, vec![0; 8], 1 << 40)]);
+    assert!(snapshot::load(&bytes).is_err());
+}
+
+#[test]
+fn version_0() {
+    // Version 0 was bincode of the X86: memory as a length-prefixed byte string,
+    // then the registers field by field.
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&0x2000u64.to_le_bytes());
+    bytes.extend((0..0x2000).map(|i| i as u8));
+    for gp in [1u32, 2, 3, 4, 0x1ff0, 0x1ff8, 7, 8] {
+        bytes.extend_from_slice(&gp.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0x1234u32.to_le_bytes()); // eip
+    for segment in [0x1bu16, 0x23, 0x23, 0x3b, 0, 0x23] {
+        bytes.extend_from_slice(&segment.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0x1000u32.to_le_bytes()); // fs_addr
+    bytes.extend_from_slice(&0x41u32.to_le_bytes()); // flags: ZF | CF
+    for st in 0..8 {
+        bytes.extend_from_slice(&(st as f64 + 0.5).to_le_bytes());
+    }
+    bytes.extend_from_slice(&7u64.to_le_bytes()); // st_top
+    bytes.extend_from_slice(&0x4000u16.to_le_bytes()); // fpu_status: C3
+    for mm in 0..8u64 {
+        bytes.extend_from_slice(&(mm << 32).to_le_bytes());
+    }
+
+    let mut machine = Machine::new(Box::new(TestHost::default()));
+    machine.load_snapshot(snapshot::load(&bytes).unwrap());
+    let regs = &machine.x86.regs;
+    assert_eq!(machine.x86.mem.len(), 0x2000);
+    assert_eq!(machine.x86.mem[0x1ff], 0xff);
+    assert_eq!((regs.eax, regs.esp, regs.edi), (1, 0x1ff0, 8));
+    assert_eq!(
+        (regs.eip, regs.cs, regs.fs, regs.fs_addr),
+        (0x1234, 0x1b, 0x3b, 0x1000)
+    );
+    assert_eq!(regs.flags, x86::Flags::ZF | x86::Flags::CF);
+    assert_eq!((regs.st[7], regs.st_top), (7.5, 7));
+    assert_eq!(regs.fpu_status, x86::FPUStatus::C3);
+    assert_eq!(regs.fpu_control, x86::FPU_CONTROL_DEFAULT);
+    assert_eq!(regs.mm[1], 1 << 32);
+}
@@ -7,6 +7,14 @@ pub trait Alloc {
     fn free(&mut self, addr: u32);
 }
 
+/// The space taken by an allocation of size bytes, including the size stored before it.
+/// Rounded up to keep allocations (and so FreeNodes) 4-byte aligned, and to leave room
+/// for a FreeNode once freed.
+fn block_size(size: u32) -> u32 {
+    ((size + 4 + 3) & !3).max(8)
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ArenaInfo {
     pub addr: u32,
     pub size: u32,
@@ -32,7 +40,7 @@ pub struct Arena<'a> {
 
 impl<'a> Alloc for Arena<'a> {
     fn alloc(&mut self, size: u32) -> u32 {
-        let alloc_size = size + 4; // TODO: align?
+        let alloc_size = block_size(size);
         if self.info.next + alloc_size > self.info.size {
             log::error!(
                 "Arena::alloc cannot allocate {:x}, using {:x}/{:x}",
@@ -58,6 +66,7 @@ impl<'a> Alloc for Arena<'a> {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct HeapInfo {
     pub addr: u32,
     pub size: u32,
@@ -126,7 +135,7 @@ impl FreeNode {
 
 impl<'a> Alloc for Heap<'a> {
     fn alloc(&mut self, size: u32) -> u32 {
-        let alloc_size = size + 4;
+        let alloc_size = block_size(size);
 
         // Find a FreeNode large enough to accommodate alloc_size.
         // To use it, update the previous node to point past it.
@@ -185,7 +194,7 @@ impl<'a> Alloc for Heap<'a> {
     }
 
     fn free(&mut self, addr: u32) {
-        let free_size = self.size(addr) + 4;
+        let free_size = block_size(self.size(addr));
         let addr = addr - 4;
 
         let mut prev = 0;
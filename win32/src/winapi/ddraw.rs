@@ -19,12 +19,99 @@ struct RECT {
 }
 unsafe impl x86::Pod for RECT {}
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Surface {
+    #[serde(skip, default = "crate::snapshot::detached_surface")]
     pub host: Box<dyn host::Surface>,
     pub width: u32,
     pub height: u32,
+    /// Copy of the pixels drawn to the host surface, so they can be redrawn into a
+    /// recreated one.  Empty until the surface is first drawn to.
+    #[serde(default)]
+    pub pixels: Vec<[u8; 4]>,
+    /// Snapshotted as host state, separately from the winapi state.
+    #[serde(skip)]
+    pub params: SurfaceParams,
 }
 
+/// What an undrawn surface is taken to contain.
+const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+
+impl Surface {
+    fn new(host: Box<dyn host::Surface>, width: u32, height: u32, params: SurfaceParams) -> Self {
+        Surface {
+            host,
+            width,
+            height,
+            pixels: Vec::new(),
+            params,
+        }
+    }
+
+    /// Replace the whole surface's pixels.
+    pub fn write_pixels(&mut self, pixels: &[[u8; 4]]) {
+        self.host.write_pixels(pixels);
+        self.pixels = pixels.to_vec();
+    }
+
+    /// Copy rect of src to (dx, dy).
+    fn bit_blt(&mut self, dx: u32, dy: u32, src: &Surface, rect: &RECT) {
+        let (sx, sy) = (rect.left, rect.top);
+        let (w, h) = (rect.right - sx, rect.bottom - sy);
+        self.host.bit_blt(dx, dy, src.host.as_ref(), sx, sy, w, h);
+        if self.pixels.is_empty() {
+            self.pixels = vec![BLACK; (self.width * self.height) as usize];
+        }
+        let w = w
+            .min(src.width.saturating_sub(sx))
+            .min(self.width.saturating_sub(dx)) as usize;
+        for row in 0..h {
+            let (sy, dy) = (sy + row, dy + row);
+            if sy >= src.height || dy >= self.height {
+                break;
+            }
+            let dst = (dy * self.width + dx) as usize;
+            let dst = &mut self.pixels[dst..dst + w];
+            if src.pixels.is_empty() {
+                dst.fill(BLACK);
+            } else {
+                let src_ofs = (sy * src.width + sx) as usize;
+                dst.copy_from_slice(&src.pixels[src_ofs..src_ofs + w]);
+            }
+        }
+    }
+
+    /// Redraw the saved pixels into a freshly created host surface.
+    fn redraw(&mut self, host: &mut dyn host::Host) {
+        if self.pixels.is_empty() {
+            return;
+        }
+        if !self.params.primary && self.params.attached_to.is_none() {
+            self.host.write_pixels(&self.pixels);
+            return;
+        }
+        // Hosts may not support writing pixels to the screen directly, so draw them
+        // into an offscreen surface and blit from that.
+        let mut scratch = host.create_surface(&host::SurfaceOptions {
+            width: self.width,
+            height: self.height,
+            primary: false,
+        });
+        scratch.write_pixels(&self.pixels);
+        self.host
+            .bit_blt(0, 0, scratch.as_ref(), 0, 0, self.width, self.height);
+    }
+}
+
+/// How a surface's host side was created.
+#[derive(Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct SurfaceParams {
+    pub primary: bool,
+    /// For back buffers, the surface they were attached to.
+    pub attached_to: Option<u32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     hheap: u32,
     vtable_IDirectDraw: u32,
@@ -53,6 +140,81 @@ impl State {
         }
     }
 
+    pub fn host_params(&self) -> Vec<(u32, SurfaceParams)> {
+        self.surfaces
+            .iter()
+            .map(|(&addr, surface)| (addr, surface.params))
+            .collect()
+    }
+
+    /// Recreate host surfaces after loading a snapshot, and redraw their pixels.
+    pub fn attach_host(&mut self, host: &mut dyn host::Host, params: Vec<(u32, SurfaceParams)>) {
+        let mut attached = Vec::new();
+        for (addr, params) in params {
+            let Some(surface) = self.surfaces.get_mut(&addr) else {
+                continue;
+            };
+            surface.params = params;
+            match params.attached_to {
+                Some(parent) => attached.push((addr, parent)),
+                None => {
+                    surface.host = host.create_surface(&host::SurfaceOptions {
+                        width: surface.width,
+                        height: surface.height,
+                        primary: params.primary,
+                    })
+                }
+            }
+        }
+        for (addr, parent) in attached {
+            let host = self.surfaces[&parent].host.get_attached();
+            self.surfaces.get_mut(&addr).unwrap().host = host;
+        }
+        self.redraw(host, |_| true);
+    }
+
+    /// Redraw the surfaces whose address passes filter.
+    fn redraw(&mut self, host: &mut dyn host::Host, filter: impl Fn(u32) -> bool) {
+        // A back buffer may share its host surface with the primary, so draw the
+        // primaries last so that they show what they showed before.
+        let mut surfaces: Vec<&mut Surface> = self
+            .surfaces
+            .iter_mut()
+            .filter(|(&addr, _)| filter(addr))
+            .map(|(_, surface)| surface)
+            .collect();
+        surfaces.sort_by_key(|surface| surface.params.primary);
+        for surface in surfaces {
+            surface.redraw(host);
+        }
+    }
+
+    /// Move host surfaces out into stash, keyed by address, leaving placeholders.
+    pub fn stash_host(&mut self, stash: &mut HashMap<u32, Box<dyn host::Surface>>) {
+        for (&addr, surface) in &mut self.surfaces {
+            let host = std::mem::replace(&mut surface.host, crate::snapshot::detached_surface());
+            stash.entry(addr).or_insert(host);
+        }
+    }
+
+    /// Take back stashed host surfaces, redrawing them with their current pixels.
+    pub fn unstash_host(
+        &mut self,
+        host: &mut dyn host::Host,
+        stash: &mut HashMap<u32, Box<dyn host::Surface>>,
+    ) {
+        let mut unstashed = Vec::new();
+        for (&addr, surface) in &mut self.surfaces {
+            if let Some(host) = stash.remove(&addr) {
+                surface.host = host;
+                unstashed.push(addr);
+            }
+        }
+        if !unstashed.is_empty() {
+            self.redraw(host, |addr| unstashed.contains(&addr));
+        }
+    }
+
     pub fn new_init(machine: &mut Machine) -> Self {
         let mut ddraw = State::new_empty();
         ddraw.hheap =
@@ -69,6 +231,17 @@ impl State {
     }
 }
 
+/// Look up a vtable method of one of the DirectDraw interfaces.
+pub fn resolve_method(iface: &str, method: &str) -> Option<crate::machine::ShimEntry> {
+    match iface {
+        "IDirectDraw" => IDirectDraw::resolve(method),
+        "IDirectDrawSurface" => IDirectDrawSurface::resolve(method),
+        "IDirectDraw7" => IDirectDraw7::resolve(method),
+        "IDirectDrawSurface7" => IDirectDrawSurface7::resolve(method),
+        _ => None,
+    }
+}
+
 const DD_OK: u32 = 0;
 // DD error codes are generated with this MAKE_HRESULT macro, maybe it doesn't matter too much.
 const DDERR_GENERIC: u32 = 0x80004005;
@@ -312,11 +485,15 @@ mod IDirectDraw {
         machine.x86.mem.write_u32(lplpDDSurface, x86_surface);
         machine.state.ddraw.surfaces.insert(
             x86_surface,
-            Surface {
-                host: surface,
-                width: opts.width,
-                height: opts.height,
-            },
+            Surface::new(
+                surface,
+                opts.width,
+                opts.height,
+                SurfaceParams {
+                    primary: opts.primary,
+                    attached_to: None,
+                },
+            ),
         );
 
         DD_OK
@@ -397,11 +574,15 @@ mod IDirectDrawSurface {
         let this_surface = machine.state.ddraw.surfaces.get(&this).unwrap();
         let host = this_surface.host.get_attached();
 
-        let surface = Surface {
+        let surface = Surface::new(
             host,
-            width: this_surface.width,
-            height: this_surface.height,
-        };
+            this_surface.width,
+            this_surface.height,
+            SurfaceParams {
+                primary: false,
+                attached_to: Some(this),
+            },
+        );
         let x86_surface = new(machine);
 
         machine.x86.mem.write_u32(lpDirectDrawSurface, x86_surface);
@@ -505,11 +686,15 @@ mod IDirectDraw7 {
         machine.x86.mem.write_u32(lpDirectDrawSurface7, x86_surface);
         machine.state.ddraw.surfaces.insert(
             x86_surface,
-            Surface {
-                host: surface,
-                width: opts.width,
-                height: opts.height,
-            },
+            Surface::new(
+                surface,
+                opts.width,
+                opts.height,
+                SurfaceParams {
+                    primary: opts.primary,
+                    attached_to: None,
+                },
+            ),
         );
 
         DD_OK
@@ -649,11 +834,7 @@ mod IDirectDrawSurface7 {
             (&mut *dst, &*src)
         };
         let rect = machine.x86.mem.view::<RECT>(lpRect);
-        let sx = rect.left;
-        let w = rect.right - sx;
-        let sy = rect.top;
-        let h = rect.bottom - sy;
-        dst.host.bit_blt(x, y, src.host.as_ref(), sx, sy, w, h);
+        dst.bit_blt(x, y, src, rect);
         DD_OK
     }
 
@@ -661,8 +842,16 @@ mod IDirectDrawSurface7 {
         if lpSurf != 0 || flags != 0 {
             log::warn!("{this:x}->Flip({lpSurf:x}, {flags:x})");
         }
-        let surface = machine.state.ddraw.surfaces.get_mut(&this).unwrap();
+        let surfaces = &mut machine.state.ddraw.surfaces;
+        let back = surfaces
+            .values()
+            .find(|surface| surface.params.attached_to == Some(this))
+            .map(|back| back.pixels.clone());
+        let surface = surfaces.get_mut(&this).unwrap();
         surface.host.flip();
+        if let Some(pixels) = back {
+            surface.pixels = pixels;
+        }
         DD_OK
     }
 
@@ -677,11 +866,15 @@ mod IDirectDrawSurface7 {
         let this_surface = machine.state.ddraw.surfaces.get(&this).unwrap();
         let host = this_surface.host.get_attached();
 
-        let surface = Surface {
+        let surface = Surface::new(
             host,
-            width: this_surface.width,
-            height: this_surface.height,
-        };
+            this_surface.width,
+            this_surface.height,
+            SurfaceParams {
+                primary: false,
+                attached_to: Some(this),
+            },
+        );
         let x86_surface = new(machine);
 
         machine.x86.mem.write_u32(lpDirectDrawSurface7, x86_surface);
@@ -27,12 +27,12 @@ pub mod ddraw {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "DirectDrawCreate" => DirectDrawCreate,
                 "DirectDrawCreateEx" => DirectDrawCreateEx,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(_) => return None,
         })
     }
 }
@@ -48,11 +48,14 @@ pub mod dsound {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "DirectSoundCreate" => DirectSoundCreate,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(ordinal) => match ordinal {
+                1 => DirectSoundCreate,
+                _ => return None,
+            },
         })
     }
 }
@@ -114,7 +117,7 @@ pub mod gdi32 {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "GetStockObject" => GetStockObject,
                 "SelectObject" => SelectObject,
                 "GetObjectA" => GetObjectA,
@@ -124,7 +127,7 @@ pub mod gdi32 {
                 "StretchBlt" => StretchBlt,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(_) => return None,
         })
     }
 }
@@ -299,6 +302,10 @@ pub mod kernel32 {
         let filename: Option<&str> = unsafe { from_x86(&mut machine.x86) };
         machine.x86.regs.eax = winapi::kernel32::LoadLibraryA(machine, filename).to_raw();
     }
+    pub fn LoadLibraryW(machine: &mut Machine) {
+        let lpLibFileName: Option<Str16> = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax = winapi::kernel32::LoadLibraryW(machine, lpLibFileName).to_raw();
+    }
     pub fn LoadLibraryExW(machine: &mut Machine) {
         let lpLibFileName: Option<Str16> = unsafe { from_x86(&mut machine.x86) };
         let hFile: HFILE = unsafe { from_x86(&mut machine.x86) };
@@ -306,6 +313,93 @@ pub mod kernel32 {
         machine.x86.regs.eax =
             winapi::kernel32::LoadLibraryExW(machine, lpLibFileName, hFile, dwFlags).to_raw();
     }
+    pub fn FreeLibrary(machine: &mut Machine) {
+        let hLibModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax = winapi::kernel32::FreeLibrary(machine, hLibModule).to_raw();
+    }
+    pub fn GetProcAddress(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpProcName: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::GetProcAddress(machine, hModule, lpProcName).to_raw();
+    }
+    pub fn FindResourceA(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpName: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::FindResourceA(machine, hModule, lpName, lpType).to_raw();
+    }
+    pub fn FindResourceW(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpName: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::FindResourceW(machine, hModule, lpName, lpType).to_raw();
+    }
+    pub fn FindResourceExA(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpName: u32 = unsafe { from_x86(&mut machine.x86) };
+        let wLanguage: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::FindResourceExA(machine, hModule, lpType, lpName, wLanguage).to_raw();
+    }
+    pub fn FindResourceExW(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpName: u32 = unsafe { from_x86(&mut machine.x86) };
+        let wLanguage: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::FindResourceExW(machine, hModule, lpType, lpName, wLanguage).to_raw();
+    }
+    pub fn LoadResource(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let hResInfo: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax = winapi::kernel32::LoadResource(machine, hModule, hResInfo).to_raw();
+    }
+    pub fn LockResource(machine: &mut Machine) {
+        let hResData: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax = winapi::kernel32::LockResource(machine, hResData).to_raw();
+    }
+    pub fn SizeofResource(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let hResInfo: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::SizeofResource(machine, hModule, hResInfo).to_raw();
+    }
+    pub fn EnumResourceTypesA(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpEnumFunc: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lParam: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::EnumResourceTypesA(machine, hModule, lpEnumFunc, lParam).to_raw();
+    }
+    pub fn EnumResourceTypesW(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpEnumFunc: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lParam: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::EnumResourceTypesW(machine, hModule, lpEnumFunc, lParam).to_raw();
+    }
+    pub fn EnumResourceNamesA(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpEnumFunc: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lParam: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::EnumResourceNamesA(machine, hModule, lpType, lpEnumFunc, lParam)
+                .to_raw();
+    }
+    pub fn EnumResourceNamesW(machine: &mut Machine) {
+        let hModule: HMODULE = unsafe { from_x86(&mut machine.x86) };
+        let lpType: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lpEnumFunc: u32 = unsafe { from_x86(&mut machine.x86) };
+        let lParam: u32 = unsafe { from_x86(&mut machine.x86) };
+        machine.x86.regs.eax =
+            winapi::kernel32::EnumResourceNamesW(machine, hModule, lpType, lpEnumFunc, lParam)
+                .to_raw();
+    }
     pub fn SetHandleCount(machine: &mut Machine) {
         let uNumber: u32 = unsafe { from_x86(&mut machine.x86) };
         machine.x86.regs.eax = winapi::kernel32::SetHandleCount(machine, uNumber).to_raw();
@@ -486,7 +580,7 @@ pub mod kernel32 {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "SetLastError" => SetLastError,
                 "GetLastError" => GetLastError,
                 "ExitProcess" => ExitProcess,
@@ -525,7 +619,21 @@ pub mod kernel32 {
                 "HeapDestroy" => HeapDestroy,
                 "GetProcessHeap" => GetProcessHeap,
                 "LoadLibraryA" => LoadLibraryA,
+                "LoadLibraryW" => LoadLibraryW,
                 "LoadLibraryExW" => LoadLibraryExW,
+                "FreeLibrary" => FreeLibrary,
+                "GetProcAddress" => GetProcAddress,
+                "FindResourceA" => FindResourceA,
+                "FindResourceW" => FindResourceW,
+                "FindResourceExA" => FindResourceExA,
+                "FindResourceExW" => FindResourceExW,
+                "LoadResource" => LoadResource,
+                "LockResource" => LockResource,
+                "SizeofResource" => SizeofResource,
+                "EnumResourceTypesA" => EnumResourceTypesA,
+                "EnumResourceTypesW" => EnumResourceTypesW,
+                "EnumResourceNamesA" => EnumResourceNamesA,
+                "EnumResourceNamesW" => EnumResourceNamesW,
                 "SetHandleCount" => SetHandleCount,
                 "CreateFileW" => CreateFileW,
                 "WriteFile" => WriteFile,
@@ -550,7 +658,7 @@ pub mod kernel32 {
                 "SetThreadPriority" => SetThreadPriority,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(_) => return None,
         })
     }
 }
@@ -664,7 +772,7 @@ pub mod user32 {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "RegisterClassA" => RegisterClassA,
                 "CreateWindowExA" => CreateWindowExA,
                 "UpdateWindow" => UpdateWindow,
@@ -680,7 +788,7 @@ pub mod user32 {
                 "GetSystemMetrics" => GetSystemMetrics,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(_) => return None,
         })
     }
 }
@@ -705,11 +813,11 @@ pub mod winmm {
     }
     pub fn resolve(sym: &winapi::ImportSymbol) -> Option<fn(&mut Machine)> {
         Some(match *sym {
-            winapi::ImportSymbol::Name(name) => match name {
+            winapi::ImportSymbol::Name(ref name) => match name.as_str() {
                 "timeSetEvent" => timeSetEvent,
                 _ => return None,
             },
-            _ => return None,
+            winapi::ImportSymbol::Ordinal(_) => return None,
         })
     }
 }
@@ -13,6 +13,16 @@ const E_FAIL: u32 = 0x80004005;
 #[allow(unused)]
 pub const DSERR_GENERIC: u32 = E_FAIL;
 
+/// Look up a vtable method of one of the DirectSound interfaces.
+pub fn resolve_method(iface: &str, method: &str) -> Option<crate::machine::ShimEntry> {
+    match iface {
+        "IDirectSound" => IDirectSound::resolve(method),
+        "IDirectSoundBuffer" => IDirectSoundBuffer::resolve(method),
+        _ => None,
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     hheap: u32,
     vtable_IDirectSound: u32,
@@ -161,7 +171,7 @@ mod IDirectSoundBuffer {
     ];
 }
 
-#[win32_derive::dllexport]
+#[win32_derive::dllexport(ordinal = 1)]
 pub fn DirectSoundCreate(machine: &mut Machine, _lpGuid: u32, ppDS: u32, _pUnkOuter: u32) -> u32 {
     if machine.state.dsound.hheap == 0 {
         machine.state.dsound = State::new_init(machine);
@@ -3,12 +3,12 @@
 use crate::{machine::Machine, winapi::user32};
 
 /// GDI Object, as identified by HANDLEs.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum Object {
     Bitmap(user32::Bitmap),
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct DC {
     // TODO: it's unclear to me what the representation of a DC ought to be.
     // The SelectObject() API returns the previously selected object of a given
@@ -28,6 +28,7 @@ impl DC {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     dcs: Vec<DC>,
     pub objects: Vec<Object>,
@@ -156,7 +157,7 @@ pub fn BitBlt(
     assert!(cx == surface.width && cy == surface.height);
     assert!(surface.width == bitmap.width && surface.height == bitmap.height);
 
-    surface.host.write_pixels(&bitmap.pixels);
+    surface.write_pixels(&bitmap.pixels);
     1 // success
 }
 
@@ -1,7 +1,12 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 
-use crate::{machine::Machine, pe::ImageSectionFlags};
+use crate::{
+    machine::Machine,
+    pe::{self, ImageSectionFlags},
+    winapi::{self, ImportSymbol},
+    windows,
+};
 use bitflags::bitflags;
 use num_traits::FromPrimitive;
 use std::collections::HashMap;
@@ -22,7 +27,7 @@ pub const STDOUT_HFILE: HFILE = HFILE::from_raw(0xF11E_0101);
 pub const STDERR_HFILE: HFILE = HFILE::from_raw(0xF11E_0102);
 
 /// Memory span as managed by the kernel.  Some come from the exe and others are allocated dynamically.
-#[derive(Debug, tsify::Tsify, serde::Serialize)]
+#[derive(Debug, tsify::Tsify, serde::Serialize, serde::Deserialize)]
 pub struct Mapping {
     pub addr: u32,
     pub size: u32,
@@ -30,8 +35,12 @@ pub struct Mapping {
     pub flags: ImageSectionFlags,
 }
 
+/// Largest span Mappings::alloc() will allocate.
+const MAX_ALLOC: u32 = 1 << 20;
+
 /// The set of Mappings managed by the kernel.
 /// These get visualized in the debugger when you hover a pointer.
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Mappings(Vec<Mapping>);
 impl Mappings {
     fn new() -> Self {
@@ -60,17 +69,41 @@ impl Mappings {
         self.0.insert(pos, mapping);
     }
 
+    /// Whether [addr, addr+size) overlaps no existing mapping.
+    pub fn is_free(&self, addr: u32, size: u32) -> bool {
+        let end = addr as u64 + size as u64;
+        self.0
+            .iter()
+            .all(|m| end <= m.addr as u64 || m.addr as u64 + m.size as u64 <= addr as u64)
+    }
+
+    /// Find an address, aligned to align, where a span of size bytes would fit.
+    pub fn find_space(&self, size: u32, align: u32) -> u32 {
+        let align_up = |addr: u32| (addr + align - 1) & !(align - 1);
+        let mut prev_end = 0;
+        for mapping in &self.0 {
+            let addr = align_up(prev_end);
+            if addr as u64 + size as u64 <= mapping.addr as u64 {
+                return addr;
+            }
+            prev_end = prev_end.max(mapping.addr + mapping.size);
+        }
+        align_up(prev_end)
+    }
+
     pub fn alloc(&mut self, size: u32, desc: String, mem: &mut Vec<u8>) -> &Mapping {
-        if size > 1 << 20 {
+        if size > MAX_ALLOC {
             log::error!("new mapping {:?} {size:x} bytes", desc);
-            assert!(size <= 1 << 20);
+            assert!(size <= MAX_ALLOC);
         }
         let mut prev_end = 0;
         let pos = self
             .0
             .iter()
             .position(|mapping| {
-                let space = mapping.addr - prev_end;
+                // Image sections needn't be page aligned, so rounding prev_end
+                // up can pass the next mapping.
+                let space = mapping.addr.saturating_sub(prev_end);
                 if space > size {
                     return true;
                 }
@@ -125,6 +158,32 @@ impl Mappings {
     }
 }
 
+/// A DLL loaded from disk, as opposed to one implemented by shims.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Dll {
+    /// Lowercased file name, e.g. "msvcrt.dll".
+    pub name: String,
+    pub base: u32,
+    pub exports: pe::Exports,
+    /// Address of its resource directory relative to base, or 0 if it has none.
+    #[serde(default)]
+    pub resources: u32,
+}
+
+/// A module's static TLS image, which each thread gets its own copy of.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TlsTemplate {
+    /// Initial contents of the block: [start, end) in the module's image.
+    pub start: u32,
+    pub end: u32,
+    /// Zero bytes following that in the block.
+    pub zero_fill: u32,
+}
+
+/// Capacity of a thread's array of static TLS blocks, TEB.ThreadLocalStoragePointer.
+const MAX_STATIC_TLS: u32 = 64;
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     /// Memory for kernel32 data structures.
     arena: ArenaInfo,
@@ -133,6 +192,12 @@ pub struct State {
     /// Address of TEB (what FS register-relative addresses refer to).
     pub teb: u32,
     pub mappings: Mappings,
+    /// DLLs loaded from disk, in load order.
+    #[serde(default)]
+    pub dlls: Vec<Dll>,
+    /// Static TLS of loaded modules, indexed by TLS index.
+    #[serde(default)]
+    tls: Vec<TlsTemplate>,
     /// Heaps created by HeapAlloc().
     heaps: HashMap<u32, HeapInfo>,
 
@@ -150,6 +215,8 @@ impl State {
             image_base: 0,
             teb: 0,
             mappings: Mappings::new(),
+            dlls: Vec::new(),
+            tls: Vec::new(),
             heaps: HashMap::new(),
             env: 0,
             cmdline: 0,
@@ -157,6 +224,11 @@ impl State {
         }
     }
 
+    /// Look up a loaded DLL by lowercased file name.
+    pub fn dll(&self, name: &str) -> Option<&Dll> {
+        self.dlls.iter().find(|dll| dll.name == name)
+    }
+
     pub fn init(&mut self, mem: &mut Vec<u8>, cmdline: String) {
         let mapping = self.mappings.alloc(0x1000, "kernel32 data".into(), mem);
         self.arena = ArenaInfo::new(mapping.addr, mapping.size);
@@ -236,15 +308,49 @@ impl State {
             .arena
             .get(mem)
             .alloc(std::cmp::max(std::mem::size_of::<TEB>() as u32, 0x100));
+        let tls_addr = self.arena.get(mem).alloc(MAX_STATIC_TLS * 4);
+
         let teb = mem.view_mut::<TEB>(teb_addr);
         teb.Tib.ExceptionList = seh_addr;
         teb.Tib._Self = teb_addr; // Confusing: it points to itself.
         teb.Peb = peb_addr;
+        teb.ThreadLocalStoragePointer = tls_addr;
 
         self.teb = teb_addr;
         // log::info!("params {params_addr:x} peb {peb_addr:x} teb {teb_addr:x}");
     }
 
+    /// Register a module's static TLS, returning its TLS index.  desc names its blocks'
+    /// mappings.
+    pub fn add_static_tls(
+        &mut self,
+        mem: &mut Vec<u8>,
+        template: TlsTemplate,
+        desc: String,
+    ) -> anyhow::Result<u32> {
+        let index = self.tls.len() as u32;
+        if index >= MAX_STATIC_TLS {
+            anyhow::bail!("too many modules with static TLS");
+        }
+        // Every thread needs a block for every module; as there's only the initial
+        // thread, that's just one new block.
+        let init = (template.end - template.start) as usize;
+        let size = match (init as u32).checked_add(template.zero_fill) {
+            Some(size) if size <= MAX_ALLOC => size,
+            _ => anyhow::bail!(
+                "static TLS block of {init:#x}+{:#x} bytes is too large",
+                template.zero_fill
+            ),
+        };
+        let block = self.mappings.alloc(size.max(4), desc, mem).addr as usize;
+        mem.copy_within(template.start as usize..template.end as usize, block);
+        mem[block + init..block + size as usize].fill(0);
+        let blocks = mem.view::<TEB>(self.teb).ThreadLocalStoragePointer;
+        mem.write_u32(blocks + index * 4, block as u32);
+        self.tls.push(template);
+        Ok(index)
+    }
+
     pub fn new_private_heap(&mut self, mem: &mut Vec<u8>, size: usize, desc: String) -> HeapInfo {
         let mapping = self.mappings.alloc(size as u32, desc, mem);
         HeapInfo::new(mem, mapping.addr, mapping.size)
@@ -492,14 +598,49 @@ pub fn GetModuleFileNameW(
     0 // fail
 }
 
+/// Reduce a module name as passed to LoadLibrary() or GetModuleHandle() to the file name
+/// it refers to, e.g. "C:\\WINDOWS\\KERNEL32" => "KERNEL32.dll".
+fn module_file_name(name: &str) -> String {
+    let name = name.rsplit(['\\', '/']).next().unwrap();
+    // A trailing dot means "no extension"; otherwise .dll is implied.
+    if let Some(name) = name.strip_suffix('.') {
+        name.to_string()
+    } else if name.contains('.') {
+        name.to_string()
+    } else {
+        format!("{name}.dll")
+    }
+}
+
+/// Look up a loaded module by its handle, returning its lowercased file name.
+fn module_name(machine: &Machine, hModule: HMODULE) -> Option<String> {
+    let base = hModule.to_raw();
+    if let Some(name) = winapi::builtin_name(base) {
+        return Some(name.to_string());
+    }
+    let dll = machine
+        .state
+        .kernel32
+        .dlls
+        .iter()
+        .find(|dll| dll.base == base)?;
+    Some(dll.name.clone())
+}
+
 #[win32_derive::dllexport]
 pub fn GetModuleHandleA(machine: &mut Machine, lpModuleName: Option<&str>) -> HMODULE {
-    if let Some(name) = lpModuleName {
-        log::error!("unimplemented: GetModuleHandle({name:?})");
-        return HMODULE::null();
+    let Some(name) = lpModuleName else {
+        // HMODULE is base address of current module.
+        return HMODULE::from_raw(machine.state.kernel32.image_base);
+    };
+    let name = module_file_name(name).to_ascii_lowercase();
+    if let Some(hmodule) = winapi::builtin_hmodule(&name) {
+        return HMODULE::from_raw(hmodule);
+    }
+    match machine.state.kernel32.dll(&name) {
+        Some(dll) => HMODULE::from_raw(dll.base),
+        None => HMODULE::null(),
     }
-    // HMODULE is base address of current module.
-    HMODULE::from_raw(machine.state.kernel32.image_base)
 }
 
 #[win32_derive::dllexport]
@@ -841,21 +982,406 @@ pub fn GetProcessHeap(machine: &mut Machine) -> u32 {
     heap
 }
 
+/// Shim name of guest_calls_return().
+pub const GUEST_CALLS_RETURN: &str = "retrowin32!GuestCallsReturn";
+
+/// The shim that ends a chain of calls started by call_guest(): a stdcall function
+/// taking (result, count, addrs...), which frees the count process heap blocks at
+/// addrs and returns result.
+pub fn guest_calls_return(machine: &mut Machine) {
+    let result = x86::ops::pop(&mut machine.x86);
+    let count = x86::ops::pop(&mut machine.x86);
+    let heap = GetProcessHeap(machine);
+    for _ in 0..count {
+        let addr = x86::ops::pop(&mut machine.x86);
+        HeapFree(machine, heap, 0, addr);
+    }
+    machine.x86.regs.eax = result;
+}
+
+/// Call into guest code (e.g. DllMains, or enumeration callbacks) as in
+/// windows::chain_calls(), before the running shim returns result.  The calls'
+/// return values are ignored.  frees are process heap blocks the calls use, which
+/// are freed once they're done.
+fn call_guest(machine: &mut Machine, calls: &[(u32, Vec<u32>)], result: u32, frees: &[u32]) -> u32 {
+    if calls.is_empty() {
+        return result;
+    }
+    // While a shim runs eip holds its return address (see Runner::check_shim_call()),
+    // so point that at the calls instead, which finish by passing result through
+    // guest_calls_return() on the way back to the caller.
+    let ret = machine.x86.regs.eip;
+    for &addr in frees.iter().rev() {
+        x86::ops::push(&mut machine.x86, addr);
+    }
+    x86::ops::push(&mut machine.x86, frees.len() as u32);
+    x86::ops::push(&mut machine.x86, result);
+    x86::ops::push(&mut machine.x86, ret);
+    let guest_calls_return = machine
+        .shims
+        .get_or_add(GUEST_CALLS_RETURN.into(), Ok(guest_calls_return));
+    machine.x86.regs.eip = windows::chain_calls(machine, calls, guest_calls_return);
+    result
+}
+
 #[win32_derive::dllexport]
-pub fn LoadLibraryA(_machine: &mut Machine, filename: Option<&str>) -> u32 {
-    log::error!("LoadLibrary({filename:?})");
-    0 // fail
+pub fn LoadLibraryA(machine: &mut Machine, filename: Option<&str>) -> HMODULE {
+    let Some(filename) = filename else {
+        return HMODULE::null();
+    };
+    let file_name = module_file_name(filename);
+    if let Some(hmodule) = winapi::builtin_hmodule(&file_name.to_ascii_lowercase()) {
+        return HMODULE::from_raw(hmodule);
+    }
+    let mut inits = Vec::new();
+    match windows::load_dll(machine, &file_name, &mut inits) {
+        Ok(Some(base)) => {
+            let calls = windows::dll_main_calls(&inits);
+            HMODULE::from_raw(call_guest(machine, &calls, base, &[]))
+        }
+        Ok(None) => HMODULE::null(),
+        Err(err) => {
+            log::error!("LoadLibrary({filename:?}): {err}");
+            HMODULE::null()
+        }
+    }
+}
+
+#[win32_derive::dllexport]
+pub fn LoadLibraryW(machine: &mut Machine, lpLibFileName: Option<Str16>) -> HMODULE {
+    let ascii = lpLibFileName.map(|str| str.to_string());
+    LoadLibraryA(machine, ascii.as_deref())
 }
 
 #[win32_derive::dllexport]
 pub fn LoadLibraryExW(
-    _machine: &mut Machine,
+    machine: &mut Machine,
     lpLibFileName: Option<Str16>,
     hFile: HFILE,
     dwFlags: u32,
+) -> HMODULE {
+    if dwFlags != 0 {
+        log::warn!("LoadLibraryExW({lpLibFileName:?}, {hFile:x?}, {dwFlags:x}): ignoring flags");
+    }
+    LoadLibraryW(machine, lpLibFileName)
+}
+
+#[win32_derive::dllexport]
+pub fn FreeLibrary(machine: &mut Machine, hLibModule: HMODULE) -> bool {
+    // DLLs stay loaded until exit.
+    module_name(machine, hLibModule).is_some()
+}
+
+#[win32_derive::dllexport]
+pub fn GetProcAddress(machine: &mut Machine, hModule: HMODULE, lpProcName: u32) -> u32 {
+    let Some(dll) = module_name(machine, hModule) else {
+        log::warn!("GetProcAddress({hModule:x?}): unknown module");
+        return 0;
+    };
+    // Like resource IDs, values below 64k are ordinals rather than pointers to names.
+    let sym = if lpProcName >> 16 == 0 {
+        ImportSymbol::Ordinal(lpProcName)
+    } else {
+        match machine.x86.mem.get(lpProcName as usize..) {
+            Some(name) => ImportSymbol::Name(name.read_strz().to_string()),
+            None => {
+                log::warn!("GetProcAddress({dll}, {lpProcName:x}): bad name pointer");
+                return 0;
+            }
+        }
+    };
+    let mut inits = Vec::new();
+    match windows::resolve_export(machine, &dll, &sym, &mut inits) {
+        Ok(Some(addr)) => call_guest(machine, &windows::dll_main_calls(&inits), addr, &[]),
+        Ok(None) => {
+            log::warn!("GetProcAddress({dll}, {sym}): not found");
+            0
+        }
+        Err(err) => {
+            log::error!("GetProcAddress({dll}, {sym}): {err}");
+            0
+        }
+    }
+}
+
+/// The image base of hModule (null meaning the exe) and the address of its resource
+/// directory relative to that, if it has one.
+fn module_resources(machine: &Machine, hModule: HMODULE) -> Option<(u32, u32)> {
+    let kernel32 = &machine.state.kernel32;
+    let (base, resources) = if hModule.is_null() || hModule.to_raw() == kernel32.image_base {
+        (kernel32.image_base, machine.state.user32.resources_base)
+    } else {
+        let dll = kernel32
+            .dlls
+            .iter()
+            .find(|dll| dll.base == hModule.to_raw())?;
+        (dll.base, dll.resources)
+    };
+    if resources == 0 {
+        return None;
+    }
+    Some((base, resources))
+}
+
+/// Read a resource type or name argument.  Like GetProcAddress's name, values below 64k
+/// are integer IDs (MAKEINTRESOURCE) rather than pointers, and a string "#123" also
+/// means the ID 123.  Returns None for a pointer outside of memory.
+fn resource_name(machine: &Machine, ptr: u32, wide: bool) -> Option<pe::ResourceName> {
+    if ptr >> 16 == 0 {
+        return Some(pe::ResourceName::Id(ptr));
+    }
+    let mem = machine.x86.mem.get(ptr as usize..)?;
+    let name = if wide {
+        let chars: Vec<u16> = mem
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        String::from_utf16_lossy(&chars)
+    } else {
+        mem.read_strz().to_string()
+    };
+    Some(
+        match name.strip_prefix('#').and_then(|id| id.parse().ok()) {
+            Some(id) => pe::ResourceName::Id(id),
+            None => pe::ResourceName::Name(name),
+        },
+    )
+}
+
+/// The inverse of resource_name(), for passing names to enumeration callbacks:
+/// strings are copied to the process heap, and their addresses added to allocs.
+fn alloc_resource_name(
+    machine: &mut Machine,
+    name: &pe::ResourceName,
+    wide: bool,
+    allocs: &mut Vec<u32>,
 ) -> u32 {
-    log::error!("LoadLibraryExW({lpLibFileName:?}, {hFile:x?}, {dwFlags:x})");
-    0 // fail
+    let name = match name {
+        pe::ResourceName::Id(id) => return *id,
+        pe::ResourceName::Name(name) => name,
+    };
+    let bytes: Vec<u8> = if wide {
+        name.encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    } else {
+        name.bytes().chain(std::iter::once(0)).collect()
+    };
+    let heap = GetProcessHeap(machine);
+    let addr = HeapAlloc(machine, heap, 0, bytes.len() as u32);
+    machine.x86.mem[addr as usize..addr as usize + bytes.len()].copy_from_slice(&bytes);
+    allocs.push(addr);
+    addr
+}
+
+/// Returns an HRSRC, the address of the resource's data entry.
+/// lpType, lpName, wide: as in resource_name()
+fn find_resource(
+    machine: &Machine,
+    hModule: HMODULE,
+    lpType: u32,
+    lpName: u32,
+    wide: bool,
+    lang: Option<u32>,
+) -> u32 {
+    let (Some(typ), Some(name)) = (
+        resource_name(machine, lpType, wide),
+        resource_name(machine, lpName, wide),
+    ) else {
+        log::warn!("FindResource({hModule:x?}, {lpType:x}, {lpName:x}): bad name pointer");
+        return 0;
+    };
+    let Some((base, resources)) = module_resources(machine, hModule) else {
+        log::warn!("FindResource({hModule:x?}, {typ}, {name}): no resources");
+        return 0;
+    };
+    match pe::find_resource(
+        &machine.x86.mem[base as usize..],
+        resources,
+        &typ,
+        &name,
+        lang,
+    ) {
+        Some(resource) => base + resource.entry,
+        None => {
+            log::warn!("FindResource({hModule:x?}, {typ}, {name}): not found");
+            0
+        }
+    }
+}
+
+#[win32_derive::dllexport]
+pub fn FindResourceA(machine: &mut Machine, hModule: HMODULE, lpName: u32, lpType: u32) -> u32 {
+    find_resource(machine, hModule, lpType, lpName, false, None)
+}
+
+#[win32_derive::dllexport]
+pub fn FindResourceW(machine: &mut Machine, hModule: HMODULE, lpName: u32, lpType: u32) -> u32 {
+    find_resource(machine, hModule, lpType, lpName, true, None)
+}
+
+#[win32_derive::dllexport]
+pub fn FindResourceExA(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpType: u32,
+    lpName: u32,
+    wLanguage: u32,
+) -> u32 {
+    find_resource(
+        machine,
+        hModule,
+        lpType,
+        lpName,
+        false,
+        Some(wLanguage & 0xFFFF),
+    )
+}
+
+#[win32_derive::dllexport]
+pub fn FindResourceExW(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpType: u32,
+    lpName: u32,
+    wLanguage: u32,
+) -> u32 {
+    find_resource(
+        machine,
+        hModule,
+        lpType,
+        lpName,
+        true,
+        Some(wLanguage & 0xFFFF),
+    )
+}
+
+/// Look up the resource for an HRSRC, returning it with addresses made absolute.
+fn resource_info(machine: &Machine, hModule: HMODULE, hResInfo: u32) -> Option<pe::Resource> {
+    let (base, _) = module_resources(machine, hModule)?;
+    let resource = pe::resource_at(
+        &machine.x86.mem[base as usize..],
+        hResInfo.checked_sub(base)?,
+    )?;
+    Some(pe::Resource {
+        entry: hResInfo,
+        data: base + resource.data,
+        size: resource.size,
+    })
+}
+
+#[win32_derive::dllexport]
+pub fn LoadResource(machine: &mut Machine, hModule: HMODULE, hResInfo: u32) -> u32 {
+    // Resources are already in memory, so the HGLOBAL is just their address.
+    match resource_info(machine, hModule, hResInfo) {
+        Some(resource) => resource.data,
+        None => {
+            log::warn!("LoadResource({hModule:x?}, {hResInfo:x}): no such resource");
+            0
+        }
+    }
+}
+
+#[win32_derive::dllexport]
+pub fn LockResource(_machine: &mut Machine, hResData: u32) -> u32 {
+    hResData
+}
+
+#[win32_derive::dllexport]
+pub fn SizeofResource(machine: &mut Machine, hModule: HMODULE, hResInfo: u32) -> u32 {
+    match resource_info(machine, hModule, hResInfo) {
+        Some(resource) => resource.size,
+        None => {
+            log::warn!("SizeofResource({hModule:x?}, {hResInfo:x}): no such resource");
+            0
+        }
+    }
+}
+
+/// Call lpEnumFunc(hModule, [lpType,] name, lParam) for each entry of a resource
+/// directory.  The callbacks run after the shim returns, so unlike on Windows one
+/// returning FALSE doesn't stop the enumeration.
+fn enum_resources(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpType: Option<u32>,
+    lpEnumFunc: u32,
+    lParam: u32,
+    wide: bool,
+) -> bool {
+    let Some((base, resources)) = module_resources(machine, hModule) else {
+        return false;
+    };
+    let typ = match lpType {
+        Some(lpType) => {
+            let Some(typ) = resource_name(machine, lpType, wide) else {
+                log::warn!("EnumResourceNames({hModule:x?}, {lpType:x}): bad type pointer");
+                return false;
+            };
+            Some(typ)
+        }
+        None => None,
+    };
+    let path: Vec<&pe::ResourceName> = typ.iter().collect();
+    let names = pe::resource_names(&machine.x86.mem[base as usize..], resources, &path);
+    let mut allocs = Vec::new();
+    let calls: Vec<(u32, Vec<u32>)> = names
+        .iter()
+        .map(|name| {
+            let name = alloc_resource_name(machine, name, wide, &mut allocs);
+            let args = std::iter::once(base)
+                .chain(lpType)
+                .chain([name, lParam])
+                .collect();
+            (lpEnumFunc, args)
+        })
+        .collect();
+    // The names are only valid during the callbacks.
+    call_guest(machine, &calls, !calls.is_empty() as u32, &allocs) != 0
+}
+
+#[win32_derive::dllexport]
+pub fn EnumResourceTypesA(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpEnumFunc: u32,
+    lParam: u32,
+) -> bool {
+    enum_resources(machine, hModule, None, lpEnumFunc, lParam, false)
+}
+
+#[win32_derive::dllexport]
+pub fn EnumResourceTypesW(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpEnumFunc: u32,
+    lParam: u32,
+) -> bool {
+    enum_resources(machine, hModule, None, lpEnumFunc, lParam, true)
+}
+
+#[win32_derive::dllexport]
+pub fn EnumResourceNamesA(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpType: u32,
+    lpEnumFunc: u32,
+    lParam: u32,
+) -> bool {
+    enum_resources(machine, hModule, Some(lpType), lpEnumFunc, lParam, false)
+}
+
+#[win32_derive::dllexport]
+pub fn EnumResourceNamesW(
+    machine: &mut Machine,
+    hModule: HMODULE,
+    lpType: u32,
+    lpEnumFunc: u32,
+    lParam: u32,
+) -> bool {
+    enum_resources(machine, hModule, Some(lpType), lpEnumFunc, lParam, true)
 }
 
 #[win32_derive::dllexport]
@@ -1,4 +1,5 @@
-use crate::machine::Machine;
+use crate::machine::{Machine, ShimEntry, SHIM_BASE};
+use std::collections::HashMap;
 
 mod alloc;
 pub mod ddraw;
@@ -33,12 +34,24 @@ macro_rules! vtable {
         unsafe impl x86::Pod for Vtable {}
         impl Vtable {
             fn new(shims: &mut crate::machine::Shims) -> Self {
+                let iface = module_path!().rsplit("::").next().unwrap();
                 Vtable {
-                    $($fn: shims.add($crate::winapi::vtable_entry!($shims $fn $status)).into()),*
+                    $($fn: shims.add(
+                        format!("{iface}::{}", stringify!($fn)),
+                        $crate::winapi::vtable_entry!($shims $fn $status),
+                    ).into()),*
                 }
             }
         }
 
+        /// Look up a method of this interface by name.
+        pub fn resolve(method: &str) -> Option<crate::machine::ShimEntry> {
+            Some(match method {
+                $(stringify!($fn) => $crate::winapi::vtable_entry!($shims $fn $status),)*
+                _ => return None,
+            })
+        }
+
         pub fn vtable(ddraw: &mut State, machine: &mut Machine) -> u32 {
             let addr = machine.state.kernel32.get_heap(&mut machine.x86.mem, ddraw.hheap).unwrap().alloc(
                 std::mem::size_of::<Vtable>() as u32,
@@ -51,30 +64,85 @@ macro_rules! vtable {
 }
 pub(crate) use vtable;
 
-pub enum ImportSymbol<'a> {
-    Name(&'a str),
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportSymbol {
+    Name(String),
     Ordinal(u32),
 }
-impl<'a> std::fmt::Display for ImportSymbol<'a> {
+impl ImportSymbol {
+    /// The inverse of the Display impl: "#123" is an ordinal, anything else a name.
+    pub fn parse(sym: &str) -> Self {
+        match sym
+            .strip_prefix('#')
+            .and_then(|ordinal| ordinal.parse().ok())
+        {
+            Some(ordinal) => ImportSymbol::Ordinal(ordinal),
+            None => ImportSymbol::Name(sym.to_string()),
+        }
+    }
+}
+impl std::fmt::Display for ImportSymbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ImportSymbol::Name(name) => f.write_str(name),
-            ImportSymbol::Ordinal(ord) => f.write_fmt(format_args!("{}", ord)),
+            ImportSymbol::Ordinal(ord) => f.write_fmt(format_args!("#{}", ord)),
         }
     }
 }
 
+/// Look up a shim by the name it was registered under in the Shims table:
+/// either "dll!symbol" for imports or "Interface::Method" for vtable entries.
+pub fn resolve_shim(name: &str) -> ShimEntry {
+    let handler = if name == kernel32::GUEST_CALLS_RETURN {
+        Some(Ok(kernel32::guest_calls_return as fn(&mut Machine)))
+    } else if let Some((iface, method)) = name.split_once("::") {
+        ddraw::resolve_method(iface, method).or_else(|| dsound::resolve_method(iface, method))
+    } else if let Some((dll, sym)) = name.split_once('!') {
+        resolve(dll, &ImportSymbol::parse(sym)).map(Ok)
+    } else {
+        None
+    };
+    handler.unwrap_or_else(|| Err(format!("unimplemented: {name}")))
+}
+
+/// The DLLs implemented here, as opposed to ones to load from disk.
+const BUILTIN_DLLS: [&str; 6] = [
+    "ddraw.dll",
+    "dsound.dll",
+    "gdi32.dll",
+    "kernel32.dll",
+    "user32.dll",
+    "winmm.dll",
+];
+
+/// Builtin DLLs have no image in memory, so their module handles are placeholders,
+/// one page apart from here, below the shims.
+const BUILTIN_HMODULE_BASE: u32 = SHIM_BASE - 0x1_0000;
+
+/// Whether dll is one implemented here, as opposed to one to load from disk.
+pub fn is_builtin(dll: &str) -> bool {
+    BUILTIN_DLLS.contains(&dll)
+}
+
+/// The module handle of a builtin DLL.
+pub fn builtin_hmodule(dll: &str) -> Option<u32> {
+    let index = BUILTIN_DLLS.iter().position(|&builtin| builtin == dll)?;
+    Some(BUILTIN_HMODULE_BASE + (index as u32) * 0x1000)
+}
+
+/// The name of the builtin DLL with a given module handle.
+pub fn builtin_name(hmodule: u32) -> Option<&'static str> {
+    let index = hmodule.checked_sub(BUILTIN_HMODULE_BASE)?;
+    if index % 0x1000 != 0 {
+        return None;
+    }
+    BUILTIN_DLLS.get((index / 0x1000) as usize).copied()
+}
+
 pub fn resolve(dll: &str, sym: &ImportSymbol) -> Option<fn(&mut Machine)> {
     match dll {
         "ddraw.dll" => dll::ddraw::resolve(sym),
-        "dsound.dll" => {
-            // TODO: no support for ordinals yet in dll generation machinery.
-            match *sym {
-                ImportSymbol::Ordinal(1) => return Some(dll::dsound::DirectSoundCreate),
-                _ => {}
-            };
-            dll::dsound::resolve(sym)
-        }
+        "dsound.dll" => dll::dsound::resolve(sym),
         "gdi32.dll" => dll::gdi32::resolve(sym),
         "kernel32.dll" => dll::kernel32::resolve(sym),
         "user32.dll" => dll::user32::resolve(sym),
@@ -87,6 +155,27 @@ pub fn resolve(dll: &str, sym: &ImportSymbol) -> Option<fn(&mut Machine)> {
     // })
 }
 
+/// Parameters of the host-side objects referenced by State, for recreating them.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct HostState {
+    windows: Vec<user32::WindowParams>,
+    surfaces: Vec<(u32, ddraw::SurfaceParams)>,
+}
+
+/// Host-side objects taken out of State, keyed by hwnd and surface address, which
+/// re-execution of the same run reproduces.
+#[derive(Default)]
+pub struct HostObjects {
+    windows: HashMap<u32, Box<dyn crate::host::Window>>,
+    surfaces: HashMap<u32, Box<dyn crate::host::Surface>>,
+}
+impl HostObjects {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty() && self.surfaces.is_empty()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     pub ddraw: ddraw::State,
     pub dsound: dsound::State,
@@ -104,4 +193,31 @@ impl State {
             user32: user32::State::new(),
         }
     }
+
+    pub fn host_state(&self) -> HostState {
+        HostState {
+            windows: self.user32.host_params(),
+            surfaces: self.ddraw.host_params(),
+        }
+    }
+
+    /// Recreate the host-side objects (windows, surfaces) of deserialized state.
+    pub fn attach_host(&mut self, host: &mut dyn crate::host::Host, state: HostState) {
+        self.user32.attach_host(host, state.windows);
+        self.ddraw.attach_host(host, state.surfaces);
+    }
+
+    /// Move the host-side objects out into objects, leaving placeholders.  Objects
+    /// already there are kept, as the ones here are then placeholders themselves.
+    pub fn stash_host(&mut self, objects: &mut HostObjects) {
+        self.user32.stash_host(&mut objects.windows);
+        self.ddraw.stash_host(&mut objects.surfaces);
+    }
+
+    /// Take back the host-side objects in objects for the windows and surfaces that
+    /// exist now, bringing them up to date with this state.
+    pub fn unstash_host(&mut self, host: &mut dyn crate::host::Host, objects: &mut HostObjects) {
+        self.user32.unstash_host(&mut objects.windows);
+        self.ddraw.unstash_host(host, &mut objects.surfaces);
+    }
 }
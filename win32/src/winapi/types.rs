@@ -20,6 +20,9 @@ macro_rules! declare_handle {
             pub const fn from_raw(raw: u32) -> Self {
                 $name(raw)
             }
+            pub const fn to_raw(self) -> u32 {
+                self.0
+            }
 
             // Handles have both null and invalid states, whoopsie.
             // https://devblogs.microsoft.com/oldnewthing/20040302-00/?p=40443
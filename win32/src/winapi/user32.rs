@@ -2,6 +2,7 @@
 
 use anyhow::bail;
 use bitflags::bitflags;
+use std::collections::HashMap;
 use x86::Memory;
 
 use crate::{host, machine::Machine, pe, winapi::gdi32};
@@ -12,10 +13,39 @@ fn IS_INTRESOURCE(x: u32) -> bool {
     x >> 16 == 0
 }
 
+/// What's needed to (re)create a window on the host.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct WindowParams {
+    pub title: String,
+    /// Requested size, or zero for the host's default.
+    pub width: u32,
+    pub height: u32,
+}
+impl WindowParams {
+    fn create_host(&self, host: &mut dyn host::Host) -> Box<dyn host::Window> {
+        let mut window = host.create_window();
+        self.apply(window.as_mut());
+        window
+    }
+
+    fn apply(&self, window: &mut dyn host::Window) {
+        window.set_title(&self.title);
+        if self.width > 0 && self.height > 0 {
+            window.set_size(self.width, self.height);
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Window {
+    #[serde(skip, default = "crate::snapshot::detached_window")]
     pub host: Box<dyn host::Window>,
+    /// Snapshotted as host state, separately from the winapi state.
+    #[serde(skip)]
+    pub params: WindowParams,
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct State {
     pub resources_base: u32,
     windows: Vec<Window>,
@@ -28,6 +58,36 @@ impl State {
         }
     }
 
+    pub fn host_params(&self) -> Vec<WindowParams> {
+        self.windows.iter().map(|w| w.params.clone()).collect()
+    }
+
+    /// Recreate host windows after loading a snapshot.
+    pub fn attach_host(&mut self, host: &mut dyn host::Host, params: Vec<WindowParams>) {
+        for (window, params) in self.windows.iter_mut().zip(params) {
+            window.host = params.create_host(host);
+            window.params = params;
+        }
+    }
+
+    /// Move host windows out into stash, keyed by hwnd, leaving placeholders.
+    pub fn stash_host(&mut self, stash: &mut HashMap<u32, Box<dyn host::Window>>) {
+        for (hwnd, window) in (1..).zip(&mut self.windows) {
+            let host = std::mem::replace(&mut window.host, crate::snapshot::detached_window());
+            stash.entry(hwnd).or_insert(host);
+        }
+    }
+
+    /// Take back stashed host windows, updated to match their params.
+    pub fn unstash_host(&mut self, stash: &mut HashMap<u32, Box<dyn host::Window>>) {
+        for (hwnd, window) in (1..).zip(&mut self.windows) {
+            if let Some(host) = stash.remove(&hwnd) {
+                window.host = host;
+                window.params.apply(window.host.as_mut());
+            }
+        }
+    }
+
     pub fn get_window(&mut self, hwnd: u32) -> &mut Window {
         &mut self.windows[hwnd as usize - 1]
     }
@@ -86,13 +146,20 @@ pub fn CreateWindowExA(
     //   https://devblogs.microsoft.com/oldnewthing/20050418-59/?p=35873
     log::warn!("CreateWindowExA({dwExStyle:x}, {className:?}, {windowName:?}, {style:?}, {X:x}, {Y:x}, {nWidth:x}, {nHeight:x}, {hWndParent:x}, {hMenu:x}, {hInstance:x}, {lpParam:x})");
 
-    let mut host_win = machine.host.create_window();
-    host_win.set_title(windowName.unwrap());
-    if nWidth > 0 && nHeight > 0 {
-        host_win.set_size(nWidth, nHeight);
-    }
-
-    let window = Window { host: host_win };
+    let (width, height) = if nWidth > 0 && nHeight > 0 {
+        (nWidth, nHeight)
+    } else {
+        (0, 0)
+    };
+    let params = WindowParams {
+        title: windowName.unwrap().to_string(),
+        width,
+        height,
+    };
+    let window = Window {
+        host: params.create_host(&mut *machine.host),
+        params,
+    };
     machine.state.user32.windows.push(window);
     machine.state.user32.windows.len() as u32
 }
@@ -214,6 +281,7 @@ impl BITMAPINFOHEADER {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Bitmap {
     pub width: u32,
     pub height: u32,
@@ -1,43 +1,387 @@
-use std::collections::HashMap;
+use crate::{
+    machine::Machine,
+    pe,
+    winapi::{self, ImportSymbol},
+};
+use anyhow::{anyhow, bail};
+use x86::Memory;
 
-use crate::{machine::Machine, pe, winapi};
+/// Images loaded away from their preferred base are placed on this alignment,
+/// matching Windows' allocation granularity.
+const IMAGE_ALIGNMENT: u32 = 0x1_0000;
 
-pub fn load_exe(
+const IMAGE_FILE_DLL: u16 = 0x2000;
+
+const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_DIRECTORY_ENTRY_TLS: usize = 9;
+const IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT: usize = 13;
+
+/// Map a PE image's headers and sections into memory, relocating it if needed.
+/// module names the image in mapping descriptions; None for the exe.
+/// Returns the base address it was loaded at.
+fn load_image(
     machine: &mut Machine,
+    file: &pe::File,
     buf: &[u8],
-    cmdline: String,
-) -> anyhow::Result<HashMap<u32, String>> {
-    let file = pe::parse(&buf)?;
+    module: Option<&str>,
+) -> anyhow::Result<u32> {
+    let preferred = file.opt_header.ImageBase;
+    let align = file.opt_header.SectionAlignment.max(1);
+    let align_up = |size: u32| size.div_ceil(align) * align;
+    // Like Windows, a section with no VirtualSize gets SizeOfRawData instead, and
+    // occupies whole multiples of SectionAlignment.
+    let virtual_size = |sec: &pe::IMAGE_SECTION_HEADER| {
+        align_up(match sec.VirtualSize {
+            0 => sec.SizeOfRawData,
+            size => size,
+        })
+    };
+    // SizeOfImage ought to cover all the sections, but don't trust it to.
+    let size = file
+        .sections
+        .iter()
+        .map(|sec| sec.VirtualAddress + virtual_size(sec))
+        .fold(align_up(file.opt_header.SizeOfImage), u32::max);
+    let relocs = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_BASERELOC];
+    let mappings = &machine.state.kernel32.mappings;
+    // Guest memory is one flat buffer, so rather than growing it out to a DLL's
+    // (typically high) preferred base, pack relocatable DLLs in low.
+    let pack = file.header.Characteristics & IMAGE_FILE_DLL != 0 && relocs.VirtualAddress != 0;
+    let base = if !pack && preferred != 0 && mappings.is_free(preferred, size) {
+        preferred
+    } else {
+        mappings.find_space(size, IMAGE_ALIGNMENT)
+    };
+    let end = (base + size) as usize;
+    if machine.x86.mem.len() < end {
+        machine.x86.mem.resize(end, 0);
+    }
+    // The space may have been used before, and everything not loaded from the
+    // file reads as zero.
+    machine.x86.mem[base as usize..end].fill(0);
 
-    let base = file.opt_header.ImageBase;
-    machine.state.kernel32.image_base = base;
-    machine
-        .x86
-        .mem
-        .resize((base + file.opt_header.SizeOfImage) as usize, 0);
-
-    for sec in file.sections {
-        let src = sec.PointerToRawData as usize;
-        let dst = (base + sec.VirtualAddress) as usize;
-        let size = sec.SizeOfRawData as usize;
+    let describe = |what: String| match module {
+        Some(module) => format!("{module} {what}"),
+        None => what,
+    };
+    let add_mapping = |machine: &mut Machine, mapping: winapi::kernel32::Mapping| {
+        let mappings = &mut machine.state.kernel32.mappings;
+        if !mappings.is_free(mapping.addr, mapping.size) {
+            bail!("{} overlaps another mapping", mapping.desc);
+        }
+        mappings.add(mapping);
+        Ok(())
+    };
+
+    // Programs find their own headers via GetModuleHandle(NULL) (their HINSTANCE),
+    // so those are mapped read-only at the base like on Windows.
+    let headers = (file.opt_header.SizeOfHeaders as usize).min(buf.len());
+    machine.x86.mem[base as usize..base as usize + headers].copy_from_slice(&buf[..headers]);
+    add_mapping(
+        machine,
+        winapi::kernel32::Mapping {
+            addr: base,
+            size: align_up(headers as u32),
+            desc: describe("headers".into()),
+            flags: pe::ImageSectionFlags::MEM_READ,
+        },
+    )?;
+
+    for sec in &file.sections {
+        let addr = base + sec.VirtualAddress;
+        let size = virtual_size(sec);
         let flags = sec.characteristics()?;
+        // Only the section's initialized part comes from the file; the rest up to
+        // its virtual size (e.g. all of .bss) is left zeroed.
         if !flags.contains(pe::ImageSectionFlags::UNINITIALIZED_DATA) {
-            machine.x86.mem[dst..dst + size].copy_from_slice(&buf[src..(src + size)]);
+            let src = sec.PointerToRawData as usize;
+            let len = sec.SizeOfRawData.min(size) as usize;
+            let data = buf
+                .get(src..src + len)
+                .ok_or_else(|| anyhow!("section {:?} extends past end of file", sec.name()))?;
+            machine.x86.mem[addr as usize..addr as usize + len].copy_from_slice(data);
         }
-        machine
-            .state
-            .kernel32
-            .mappings
-            .add(winapi::kernel32::Mapping {
-                addr: dst as u32,
-                size: size as u32,
-                desc: format!("{:?} ({:?})", sec.name(), flags),
+        add_mapping(
+            machine,
+            winapi::kernel32::Mapping {
+                addr,
+                size,
+                desc: describe(format!("{:?} ({:?})", sec.name(), flags)),
                 flags,
-            });
+            },
+        )?;
+    }
+
+    if base != preferred {
+        if relocs.VirtualAddress == 0 {
+            bail!("image wants base {preferred:#x}, which is taken, and has no relocations");
+        }
+        log::info!("relocating image from {preferred:#x} to {base:#x}");
+        pe::apply_relocations(
+            &mut machine.x86.mem[base as usize..end],
+            relocs.VirtualAddress as usize,
+            relocs.Size as usize,
+            base.wrapping_sub(preferred),
+        )?;
     }
 
+    Ok(base)
+}
+
+/// Set up a loaded image's static TLS, if any, returning its TLS callbacks.
+/// module is as in load_image().
+fn init_tls(
+    machine: &mut Machine,
+    file: &pe::File,
+    base: u32,
+    module: Option<&str>,
+) -> anyhow::Result<Vec<u32>> {
+    let tls_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_TLS];
+    if tls_data.VirtualAddress == 0 {
+        return Ok(Vec::new());
+    }
+    let tls = pe::parse_tls(&machine.x86.mem, base + tls_data.VirtualAddress)?;
+    let desc = match module {
+        Some(module) => format!("{module} tls"),
+        None => "tls".into(),
+    };
+    let index = machine.state.kernel32.add_static_tls(
+        &mut machine.x86.mem,
+        winapi::kernel32::TlsTemplate {
+            start: tls.start,
+            end: tls.end,
+            zero_fill: tls.zero_fill,
+        },
+        desc,
+    )?;
+    if tls.index_addr != 0 {
+        machine.x86.mem.write_u32(tls.index_addr, index);
+    }
+    Ok(tls.callbacks)
+}
+
+/// Point an image's IAT at the functions it imports, loading any DLLs it needs from disk.
+/// The entry points (and TLS callbacks) of DLLs loaded along the way are appended to
+/// inits, as (entry point, base address), dependencies first.
+fn bind_imports(
+    machine: &mut Machine,
+    file: &pe::File,
+    base: u32,
+    inits: &mut Vec<(u32, u32)>,
+) -> anyhow::Result<()> {
+    let imports_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_IMPORT];
+    if imports_data.VirtualAddress != 0 {
+        // Load dependencies by their name as written, which matters on case-sensitive hosts.
+        let dlls = pe::import_dlls(
+            &machine.x86.mem[base as usize..],
+            imports_data.VirtualAddress as usize,
+        )?;
+        for dll in dlls {
+            if !winapi::is_builtin(&dll.to_ascii_lowercase()) {
+                load_dll(machine, &dll, inits)?;
+            }
+        }
+
+        // Resolving may load further DLLs named by forwarders, which needs all of the
+        // machine, so gather up the imports before binding them.
+        let mut imports = Vec::new();
+        pe::parse_imports(
+            &mut machine.x86.mem[base as usize..],
+            imports_data.VirtualAddress as usize,
+            |dll, sym, iat_addr| {
+                imports.push((dll.to_string(), sym, iat_addr));
+                0
+            },
+        )?;
+        for (dll, sym, iat_addr) in imports {
+            bind_import(machine, &dll, &sym, base + iat_addr, inits)?;
+        }
+    }
+
+    // Delay-loaded imports are bound the same way, up front.
+    let delay_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT];
+    if delay_data.VirtualAddress != 0 {
+        let delay_imports = pe::parse_delay_imports(
+            &machine.x86.mem[base as usize..],
+            delay_data.VirtualAddress as usize,
+            base,
+        )?;
+        for delay in delay_imports {
+            let name = delay.dll.to_ascii_lowercase();
+            let hmodule = match winapi::builtin_hmodule(&name) {
+                Some(hmodule) => hmodule,
+                None => load_dll(machine, &delay.dll, inits)?.unwrap_or(0),
+            };
+            // Record the DLL as loaded, as the delay-load helper would have.
+            if delay.hmodule_addr != 0 {
+                machine
+                    .x86
+                    .mem
+                    .write_u32(base + delay.hmodule_addr, hmodule);
+            }
+            for (sym, iat_addr) in &delay.symbols {
+                bind_import(machine, &name, sym, base + iat_addr, inits)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Point the IAT entry at iat_addr at dll's export sym, or if there isn't one, at a shim
+/// that reports it as unimplemented.  inits is as in bind_imports().
+fn bind_import(
+    machine: &mut Machine,
+    dll: &str,
+    sym: &ImportSymbol,
+    iat_addr: u32,
+    inits: &mut Vec<(u32, u32)>,
+) -> anyhow::Result<()> {
+    machine.labels.insert(iat_addr, format!("{}@IAT", sym));
+    let addr = match resolve_export(machine, dll, sym, inits)? {
+        Some(addr) => addr,
+        None => {
+            let addr = machine.shims.add(
+                format!("{dll}!{sym}"),
+                Err(format!("unimplemented: {dll}!{sym}")),
+            );
+            machine.labels.insert(addr, sym.to_string());
+            addr
+        }
+    };
+    machine.x86.mem.write_u32(iat_addr, addr);
+    Ok(())
+}
+
+/// Forwarders chaining further than this are assumed to be cyclic.
+const MAX_FORWARDS: usize = 8;
+
+/// Find the address of a function exported by a builtin or already loaded DLL, following
+/// forwarders and loading the DLLs they name; builtin functions get a shim.
+/// Returns None if the DLL isn't loaded or doesn't export sym.
+/// inits is as in bind_imports().
+pub fn resolve_export(
+    machine: &mut Machine,
+    dll: &str,
+    sym: &ImportSymbol,
+    inits: &mut Vec<(u32, u32)>,
+) -> anyhow::Result<Option<u32>> {
+    // Owned, as each forward replaces them with ones parsed from the machine state.
+    let (mut dll, mut sym) = (dll.to_string(), sym.clone());
+    for hop in 0..MAX_FORWARDS {
+        let name = dll.to_ascii_lowercase();
+        if winapi::is_builtin(&name) {
+            let Some(handler) = winapi::resolve(&name, &sym) else {
+                return Ok(None);
+            };
+            let addr = machine
+                .shims
+                .get_or_add(format!("{name}!{sym}"), Ok(handler));
+            machine.labels.insert(addr, sym.to_string());
+            return Ok(Some(addr));
+        }
+
+        // Callers load the DLL they start from (and report it if missing), so that
+        // a missing DLL isn't searched for again on every one of its symbols.
+        if hop > 0 && load_dll(machine, &dll, inits)?.is_none() {
+            return Ok(None);
+        }
+        let Some(loaded) = machine.state.kernel32.dll(&name) else {
+            return Ok(None);
+        };
+        let base = loaded.base;
+        let forward = match loaded.exports.resolve(&sym) {
+            Some(pe::Export::Addr(addr)) => return Ok(Some(base + addr)),
+            Some(pe::Export::Forward(forward)) => forward,
+            None => {
+                log::warn!("{name} doesn't export {sym}");
+                return Ok(None);
+            }
+        };
+        let Some((next_dll, next_sym)) = pe::parse_forwarder(forward) else {
+            bail!("bad export forwarder {forward:?} in {name}");
+        };
+        (dll, sym) = (next_dll, next_sym);
+    }
+    bail!("too many export forwards, ending at {dll}!{sym}")
+}
+
+/// Load a DLL found in machine.dll_paths, along with the DLLs it imports, returning its
+/// base address, or None if it wasn't found.  inits is as in bind_imports().
+pub fn load_dll(
+    machine: &mut Machine,
+    file_name: &str,
+    inits: &mut Vec<(u32, u32)>,
+) -> anyhow::Result<Option<u32>> {
+    let name = file_name.to_ascii_lowercase();
+    if let Some(dll) = machine.state.kernel32.dll(&name) {
+        return Ok(Some(dll.base));
+    }
+    let buf = machine.dll_paths.iter().find_map(|dir| {
+        let path = if dir.is_empty() {
+            file_name.to_string()
+        } else {
+            format!("{dir}/{file_name}")
+        };
+        machine.host.read_file(&path)
+    });
+    let Some(buf) = buf else {
+        log::warn!("{file_name} not found in {:?}", machine.dll_paths);
+        return Ok(None);
+    };
+
+    let file = pe::parse(&buf)?;
+    let base = load_image(machine, &file, &buf, Some(&name))?;
+    let tls_callbacks = init_tls(machine, &file, base, Some(&name))?;
+    log::info!("loaded {name} at {base:#x}");
+
+    let exports_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_EXPORT];
+    let exports = if exports_data.VirtualAddress != 0 {
+        pe::parse_exports(
+            &machine.x86.mem[base as usize..],
+            exports_data.VirtualAddress as usize,
+            exports_data.Size as usize,
+        )?
+    } else {
+        pe::Exports::default()
+    };
+    for (export, &index) in &exports.names {
+        if exports.forwards.contains_key(&index) {
+            continue;
+        }
+        if let Some(&addr) = exports.fns.get(index) {
+            machine.labels.insert(base + addr, export.clone());
+        }
+    }
+    // Registered before binding its imports, so that import cycles terminate.
+    let resources = file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_RESOURCE].VirtualAddress;
+    machine.state.kernel32.dlls.push(winapi::kernel32::Dll {
+        name,
+        base,
+        exports,
+        resources,
+    });
+
+    bind_imports(machine, &file, base, inits)?;
+    // TLS callbacks take the same arguments as DllMain, and run just before it.
+    inits.extend(tls_callbacks.into_iter().map(|callback| (callback, base)));
+    if file.opt_header.AddressOfEntryPoint != 0 {
+        inits.push((base + file.opt_header.AddressOfEntryPoint, base));
+    }
+    Ok(Some(base))
+}
+
+pub fn load_exe(machine: &mut Machine, buf: &[u8], cmdline: String) -> anyhow::Result<()> {
+    let file = pe::parse(&buf)?;
+
+    let base = load_image(machine, &file, buf, None)?;
+    machine.state.kernel32.image_base = base;
+
     machine.state.kernel32.init(&mut machine.x86.mem, cmdline);
     machine.x86.regs.fs_addr = machine.state.kernel32.teb;
+    let tls_callbacks = init_tls(machine, &file, base, None)?;
 
     let mut stack_size = file.opt_header.SizeOfStackReserve;
     // Zig reserves 16mb stacks, just truncate for now.
@@ -58,33 +402,45 @@ pub fn load_exe(
     machine.x86.regs.esp = stack_end;
     machine.x86.regs.ebp = stack_end;
 
-    const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
-    let imports_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_IMPORT];
-    let mut labels: HashMap<u32, String> = HashMap::new();
-    pe::parse_imports(
-        &mut machine.x86.mem[base as usize..],
-        imports_data.VirtualAddress as usize,
-        |dll, sym, iat_addr| {
-            labels.insert(base + iat_addr, format!("{}@IAT", sym));
-
-            let entry = match winapi::resolve(dll, &sym) {
-                Some(f) => Ok(f),
-                None => Err(format!("unimplemented: {dll}!{sym}")),
-            };
-            let addr = machine.shims.add(entry);
-
-            labels.insert(addr, sym.to_string());
+    let mut inits = Vec::new();
+    bind_imports(machine, &file, base, &mut inits)?;
+    // The exe's TLS callbacks run after its DLLs are initialized, before its entry point.
+    inits.extend(tls_callbacks.into_iter().map(|callback| (callback, base)));
 
-            addr
-        },
-    )?;
-
-    const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
     let res_data = &file.opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_RESOURCE];
     machine.state.user32.resources_base = res_data.VirtualAddress;
 
-    let entry_point = base + file.opt_header.AddressOfEntryPoint;
-    machine.x86.regs.eip = entry_point;
+    machine.x86.regs.eip = chain_calls(
+        machine,
+        &dll_main_calls(&inits),
+        base + file.opt_header.AddressOfEntryPoint,
+    );
+
+    Ok(())
+}
+
+/// Arrange for each stdcall function in calls to be called in turn with its arguments
+/// before continuing at eip, returning the address to start at.
+pub fn chain_calls(machine: &mut Machine, calls: &[(u32, Vec<u32>)], mut eip: u32) -> u32 {
+    // The calls are chained on the stack: each returns to the next one's entry point,
+    // and as stdcall pops its own arguments, leaves the next call's frame on top.
+    for (func, args) in calls.iter().rev() {
+        for &arg in args.iter().rev() {
+            x86::ops::push(&mut machine.x86, arg);
+        }
+        x86::ops::push(&mut machine.x86, eip);
+        eip = *func;
+    }
+    eip
+}
 
-    Ok(labels)
+/// The DllMain(hinstDLL, DLL_PROCESS_ATTACH, 0) calls for inits (as gathered by
+/// bind_imports(), including TLS callbacks, which share DllMain's signature), for
+/// chain_calls().  Unlike on Windows, a DllMain returning FALSE doesn't stop anything.
+pub fn dll_main_calls(inits: &[(u32, u32)]) -> Vec<(u32, Vec<u32>)> {
+    const DLL_PROCESS_ATTACH: u32 = 1;
+    inits
+        .iter()
+        .map(|&(entry, hinst)| (entry, vec![hinst, DLL_PROCESS_ATTACH, 0]))
+        .collect()
 }
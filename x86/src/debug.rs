@@ -1,9 +1,35 @@
 //! Disassembler producing serde/JSON for use in displaying code in the debugger.
 
-use iced_x86::{Formatter, IntelFormatter};
-use std::fmt::Write;
+use iced_x86::{FlowControl, Formatter, FormatterTextKind, IntelFormatter, NumberKind, OpKind};
+use std::{collections::HashMap, fmt::Write};
 use tsify::Tsify;
 
+/// Names for addresses, e.g. "GetProcAddress@IAT" for an import table slot.
+#[derive(Clone, Default)]
+pub struct Symbols(HashMap<u32, String>);
+
+impl Symbols {
+    pub fn insert(&mut self, addr: u32, name: String) {
+        self.0.insert(addr, name);
+    }
+
+    pub fn get(&self, addr: u32) -> Option<&str> {
+        self.0.get(&addr).map(|name| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.0.iter().map(|(&addr, name)| (addr, name.as_str()))
+    }
+
+    /// Formats an address for messages, along with its name if it has one.
+    pub fn describe(&self, addr: u32) -> String {
+        match self.get(addr) {
+            Some(name) => format!("{addr:08x} ({name})"),
+            None => format!("{addr:08x}"),
+        }
+    }
+}
+
 #[derive(Tsify, serde::Serialize)]
 pub struct CodePart {
     pub kind: String,
@@ -18,19 +44,47 @@ pub struct Instruction {
     pub ops: Vec<String>,
 }
 
-struct FormatterOutput {
+struct FormatterOutput<'a> {
     code: Vec<CodePart>,
+    symbols: &'a Symbols,
 }
-impl iced_x86::FormatterOutput for FormatterOutput {
-    fn write(&mut self, text: &str, kind: iced_x86::FormatterTextKind) {
+impl iced_x86::FormatterOutput for FormatterOutput<'_> {
+    fn write(&mut self, text: &str, kind: FormatterTextKind) {
         self.code.push(CodePart {
             kind: format!("{:?}", kind),
             text: text.to_string(),
         })
     }
+
+    fn write_number(
+        &mut self,
+        instruction: &iced_x86::Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        text: &str,
+        value: u64,
+        _number_kind: NumberKind,
+        kind: FormatterTextKind,
+    ) {
+        // Name branch targets and memory displacements, but leave immediates alone.
+        let is_address = match kind {
+            FormatterTextKind::LabelAddress | FormatterTextKind::FunctionAddress => true,
+            _ => instruction_operand.is_some_and(|op| instruction.op_kind(op) == OpKind::Memory),
+        };
+        match self.symbols.get(value as u32) {
+            Some(name) if is_address => self.write(name, FormatterTextKind::Label),
+            _ => self.write(text, kind),
+        }
+    }
+}
+
+impl FormatterOutput<'_> {
+    fn text(&self) -> String {
+        self.code.iter().map(|part| part.text.as_str()).collect()
+    }
 }
 
-pub fn disassemble(mem: &[u8], addr: u32) -> Vec<Instruction> {
+pub fn disassemble(mem: &[u8], addr: u32, symbols: &Symbols) -> Vec<Instruction> {
     if addr as usize >= mem.len() {
         return Vec::new();
     }
@@ -52,7 +106,10 @@ pub fn disassemble(mem: &[u8], addr: u32) -> Vec<Instruction> {
             write!(&mut bytes, "{:02x}", b).unwrap();
         }
 
-        let mut output = FormatterOutput { code: Vec::new() };
+        let mut output = FormatterOutput {
+            code: Vec::new(),
+            symbols,
+        };
         formatter.format(&instruction, &mut output);
 
         instrs.push(Instruction {
@@ -68,3 +125,37 @@ pub fn disassemble(mem: &[u8], addr: u32) -> Vec<Instruction> {
     }
     instrs
 }
+
+/// Formats a single decoded instruction as Intel-syntax text.
+pub fn format_instr(instr: &iced_x86::Instruction, symbols: &Symbols) -> String {
+    let mut output = FormatterOutput {
+        code: Vec::new(),
+        symbols,
+    };
+    IntelFormatter::new().format(instr, &mut output);
+    output.text()
+}
+
+/// If the instruction just before addr is a call, as when addr is a return address, decode it.
+pub fn call_before(mem: &[u8], addr: u32) -> Option<iced_x86::Instruction> {
+    // Call instruction lengths: through a register or [reg], [reg+disp8],
+    // a SIB byte with disp8, rel32, [disp32] or [reg+disp32], a SIB byte with disp32.
+    for len in [2, 3, 4, 5, 6, 7] {
+        let Some(start) = addr.checked_sub(len) else {
+            continue;
+        };
+        let bytes = mem.get(start as usize..addr as usize)?;
+        let instr =
+            iced_x86::Decoder::with_ip(32, bytes, start as u64, iced_x86::DecoderOptions::NONE)
+                .decode();
+        if instr.len() == len as usize
+            && matches!(
+                instr.flow_control(),
+                FlowControl::Call | FlowControl::IndirectCall
+            )
+        {
+            return Some(instr);
+        }
+    }
+    None
+}
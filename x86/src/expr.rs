@@ -0,0 +1,340 @@
+//! A small expression language over registers and guest memory, used for
+//! conditional breakpoints and tracepoint messages.
+//!
+//! Values are u32 with wrapping arithmetic; comparisons are unsigned and produce 0 or 1.
+//! - operands: `123`, `0x7b`, registers (`eax`, `ax`, `al`, `eip`, `flags`), single flags
+//!   (`zf`, `cf`, ...), and memory: `[expr]` (a dword), `byte [expr]`, `word [expr]`, `dword [expr]`
+//! - operators, loosest to tightest: `||`, `&&`, `== != < <= > >=`, `|`, `^`, `&`, `<< >>`,
+//!   `+ -`, `* / %`, and unary `- ! ~`
+
+use crate::{registers::Flags, X86};
+use iced_x86::Register;
+
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<=", ">=", "<", ">"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(u32),
+    Ident(String),
+    Op(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    const OPS: &[&str] = &[
+        "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*",
+        "/", "%", "!", "~", "(", ")", "[", "]",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        let len = if c.is_ascii_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            tokens.push(if c.is_ascii_digit() {
+                let num = match word.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                Token::Num(num.map_err(|_| format!("bad number {word:?}"))?)
+            } else {
+                Token::Ident(word.to_ascii_lowercase())
+            });
+            len
+        } else {
+            let op = OPS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("unexpected {c:?}"))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Node {
+    Const(u32),
+    Reg32(Register),
+    Reg16(Register),
+    Reg8(Register),
+    Eip,
+    Flags,
+    Flag(Flags),
+    /// Memory read of the given size in bytes.
+    Mem(u32, Box<Node>),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+fn lookup_name(name: &str) -> Option<Node> {
+    const REGS: [(&str, Register); 20] = [
+        ("eax", Register::EAX),
+        ("ecx", Register::ECX),
+        ("edx", Register::EDX),
+        ("ebx", Register::EBX),
+        ("esp", Register::ESP),
+        ("ebp", Register::EBP),
+        ("esi", Register::ESI),
+        ("edi", Register::EDI),
+        ("ax", Register::AX),
+        ("cx", Register::CX),
+        ("dx", Register::DX),
+        ("bx", Register::BX),
+        ("al", Register::AL),
+        ("cl", Register::CL),
+        ("dl", Register::DL),
+        ("bl", Register::BL),
+        ("ah", Register::AH),
+        ("ch", Register::CH),
+        ("dh", Register::DH),
+        ("bh", Register::BH),
+    ];
+    const FLAGS: [(&str, Flags); 7] = [
+        ("cf", Flags::CF),
+        ("pf", Flags::PF),
+        ("af", Flags::AF),
+        ("zf", Flags::ZF),
+        ("sf", Flags::SF),
+        ("df", Flags::DF),
+        ("of", Flags::OF),
+    ];
+    if let Some(&(_, reg)) = REGS.iter().find(|(n, _)| *n == name) {
+        return Some(if reg.is_gpr32() {
+            Node::Reg32(reg)
+        } else if reg.is_gpr16() {
+            Node::Reg16(reg)
+        } else {
+            Node::Reg8(reg)
+        });
+    }
+    if let Some(&(_, flag)) = FLAGS.iter().find(|(n, _)| *n == name) {
+        return Some(Node::Flag(flag));
+    }
+    match name {
+        "eip" => Some(Node::Eip),
+        "flags" | "eflags" => Some(Node::Flags),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Op(o) if o == op => Ok(()),
+            token => Err(format!("expected {op:?}, got {token:?}")),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if !BINARY_LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Op(op @ ("-" | "!" | "~")) => Ok(Node::Unary(op, Box::new(self.unary()?))),
+            Token::Op("(") => {
+                let node = self.binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Op("[") => self.mem(4),
+            Token::Num(n) => Ok(Node::Const(n)),
+            Token::Ident(name) => {
+                let size = match name.as_str() {
+                    "byte" => 1,
+                    "word" => 2,
+                    "dword" => 4,
+                    _ => return lookup_name(&name).ok_or_else(|| format!("unknown name {name:?}")),
+                };
+                self.expect("[")?;
+                self.mem(size)
+            }
+            token => Err(format!("unexpected {token:?}")),
+        }
+    }
+
+    /// Parses the remainder of a memory reference, after its opening bracket.
+    fn mem(&mut self, size: u32) -> Result<Node, String> {
+        let addr = self.binary(0)?;
+        self.expect("]")?;
+        Ok(Node::Mem(size, Box::new(addr)))
+    }
+}
+
+fn eval(node: &Node, x86: &X86) -> Result<u32, String> {
+    Ok(match node {
+        &Node::Const(n) => n,
+        &Node::Reg32(reg) => x86.regs.get32(reg),
+        &Node::Reg16(reg) => x86.regs.get16(reg) as u32,
+        &Node::Reg8(reg) => x86.regs.get8(reg) as u32,
+        Node::Eip => x86.regs.eip,
+        Node::Flags => x86.regs.flags.bits(),
+        &Node::Flag(flag) => x86.regs.flags.contains(flag) as u32,
+        Node::Mem(size, addr) => {
+            let addr = eval(addr, x86)?;
+            if !x86.in_bounds(addr, *size) {
+                return Err(format!("bad address {addr:#x}"));
+            }
+            let mut bytes = [0u8; 4];
+            bytes[..*size as usize]
+                .copy_from_slice(&x86.mem[addr as usize..(addr + size) as usize]);
+            u32::from_le_bytes(bytes)
+        }
+        Node::Unary(op, x) => {
+            let x = eval(x, x86)?;
+            match *op {
+                "-" => x.wrapping_neg(),
+                "!" => (x == 0) as u32,
+                "~" => !x,
+                _ => unreachable!(),
+            }
+        }
+        Node::Binary("&&", x, y) => (eval(x, x86)? != 0 && eval(y, x86)? != 0) as u32,
+        Node::Binary("||", x, y) => (eval(x, x86)? != 0 || eval(y, x86)? != 0) as u32,
+        Node::Binary(op, x, y) => {
+            let (x, y) = (eval(x, x86)?, eval(y, x86)?);
+            match *op {
+                "==" => (x == y) as u32,
+                "!=" => (x != y) as u32,
+                "<" => (x < y) as u32,
+                "<=" => (x <= y) as u32,
+                ">" => (x > y) as u32,
+                ">=" => (x >= y) as u32,
+                "|" => x | y,
+                "^" => x ^ y,
+                "&" => x & y,
+                "<<" => x.wrapping_shl(y),
+                ">>" => x.wrapping_shr(y),
+                "+" => x.wrapping_add(y),
+                "-" => x.wrapping_sub(y),
+                "*" => x.wrapping_mul(y),
+                "/" => x.checked_div(y).ok_or("division by zero")?,
+                "%" => x.checked_rem(y).ok_or("division by zero")?,
+                _ => unreachable!(),
+            }
+        }
+    })
+}
+
+/// A parsed expression.
+#[derive(Debug)]
+pub struct Expr {
+    text: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let root = parser.binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {token:?} after expression"));
+        }
+        Ok(Expr {
+            text: text.trim().to_string(),
+            root,
+        })
+    }
+
+    /// Evaluates against the current machine state.  Fails on unmapped memory or
+    /// division by zero.
+    pub fn eval(&self, x86: &X86) -> Result<u32, String> {
+        eval(&self.root, x86)
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Expr(Expr),
+}
+
+/// Text with embedded `{expr}`s, which render as hex, e.g. "read {ecx} bytes".
+#[derive(Debug)]
+pub struct Template(Vec<Part>);
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed {{ in {text:?}"))?;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            parts.push(Part::Expr(Expr::parse(&rest[start + 1..start + end])?));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template(parts))
+    }
+
+    pub fn render(&self, x86: &X86) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Expr(expr) => match expr.eval(x86) {
+                    Ok(value) => out.push_str(&format!("{value:x}")),
+                    Err(err) => out.push_str(&format!("<{err}>")),
+                },
+            }
+        }
+        out
+    }
+}
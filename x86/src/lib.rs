@@ -1,11 +1,19 @@
 pub mod debug;
+pub mod expr;
 mod memory;
 pub mod ops;
 mod registers;
+#[cfg(test)]
+mod tests;
+pub mod trace;
+mod watch;
 mod x86;
 
 pub use memory::{Memory, Pod};
-pub use x86::{InstrCache, Snapshot, NULL_POINTER_REGION_SIZE, X86};
+pub use registers::{FPUStatus, Flags, Registers, FPU_CONTROL_DEFAULT};
+pub use trace::{TraceFilter, TraceFormat, Tracer, TRACE_MAGIC, TRACE_VERSION};
+pub use watch::{WatchHit, WatchKind, Watchpoint, Watchpoints};
+pub use x86::{Breakpoint, InstrCache, Snapshot, NULL_POINTER_REGION_SIZE, X86};
 
 #[derive(Debug)]
 pub enum StepError {
@@ -1,8 +1,11 @@
 use iced_x86::Instruction;
 
-use crate::{memory::Memory, registers::Flags, x86::X86, StepError, StepResult};
+use crate::{registers::Flags, x86::X86, StepError, StepResult};
 
-use super::helpers::*;
+use super::{
+    helpers::*,
+    math::{add, sub},
+};
 
 pub fn nop(_x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     Ok(())
@@ -31,6 +34,16 @@ pub fn pushd_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn pushw_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    push16(x86, instr.immediate8to16() as u16);
+    Ok(())
+}
+
+pub fn push_imm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    push16(x86, instr.immediate16());
+    Ok(())
+}
+
 pub fn push_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     push(x86, x86.regs.get32(instr.op0_register()));
     Ok(())
@@ -50,20 +63,20 @@ pub fn push_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 
 pub fn pop_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let value = pop(x86);
-    rm32_x(x86, instr, |_x86, _x| value);
+    rm32_set(x86, instr, value);
     Ok(())
 }
 
 pub fn pop_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let value = pop16(x86);
-    rm16_x(x86, instr, |_x86, _x| value);
+    rm16_set(x86, instr, value);
     Ok(())
 }
 
 pub fn mov_rm32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     // mov dword ptr [x], y
     // TODO: why is this 'rm32' when there is an r32 variant just below?
-    rm32_x(x86, instr, |_x86, _x| instr.immediate32());
+    rm32_set(x86, instr, instr.immediate32());
     Ok(())
 }
 
@@ -72,6 +85,12 @@ pub fn mov_r32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn mov_rm16_imm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate16();
+    rm16_set(x86, instr, y);
+    Ok(())
+}
+
 pub fn mov_moffs32_eax(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     // mov [x],eax
     x86.write_u32(x86_addr(x86, instr), x86.regs.eax);
@@ -84,9 +103,35 @@ pub fn mov_eax_moffs32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn mov_moffs16_ax(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    // mov [x],ax
+    x86.write_u16(x86_addr(x86, instr), x86.regs.eax as u16);
+    Ok(())
+}
+
+pub fn mov_ax_moffs16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    // mov ax,[x]
+    let value = x86.read_u16(x86_addr(x86, instr));
+    x86.regs.set16(iced_x86::Register::AX, value);
+    Ok(())
+}
+
+pub fn mov_moffs8_al(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    // mov [x],al
+    x86.write_u8(x86_addr(x86, instr), x86.regs.eax as u8);
+    Ok(())
+}
+
+pub fn mov_al_moffs8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    // mov al,[x]
+    let value = x86.read_u8(x86_addr(x86, instr));
+    x86.regs.set8(iced_x86::Register::AL, value);
+    Ok(())
+}
+
 pub fn mov_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let value = x86.regs.get32(instr.op1_register());
-    rm32_x(x86, instr, |_x86, _x| value);
+    rm32_set(x86, instr, value);
     Ok(())
 }
 
@@ -104,7 +149,7 @@ pub fn mov_r16_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 
 pub fn mov_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = x86.regs.get16(instr.op1_register());
-    rm16_x(x86, instr, |_x86, _x| y);
+    rm16_set(x86, instr, y);
     Ok(())
 }
 
@@ -116,49 +161,49 @@ pub fn mov_r8_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 
 pub fn mov_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = x86.regs.get8(instr.op1_register());
-    rm8_x(x86, instr, |_x86, _x| y);
+    rm8_set(x86, instr, y);
     Ok(())
 }
 
 pub fn mov_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate8();
-    rm8_x(x86, instr, |_x86, _x| y);
+    rm8_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movsx_r32_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm16(x86, instr) as i16 as u32;
-    rm32_x(x86, instr, |_x86, _x| y);
+    rm32_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movsx_r32_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm8(x86, instr) as i8 as u32;
-    rm32_x(x86, instr, |_x86, _x| y);
+    rm32_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movsx_r16_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm8(x86, instr) as i8 as u16;
-    rm16_x(x86, instr, |_x86, _x| y);
+    rm16_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movzx_r32_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm16(x86, instr) as u32;
-    rm32_x(x86, instr, |_x86, _x| y);
+    rm32_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movzx_r32_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm8(x86, instr) as u32;
-    rm32_x(x86, instr, |_x86, _x| y);
+    rm32_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movzx_r16_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm8(x86, instr) as u16;
-    rm16_x(x86, instr, |_x86, _x| y);
+    rm16_set(x86, instr, y);
     Ok(())
 }
 
@@ -172,21 +217,117 @@ pub fn xchg_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn xchg_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let r1 = instr.op1_register();
+    rm16_x(x86, instr, |x86, x| {
+        let tmp = x86.regs.get16(r1);
+        x86.regs.set16(r1, x);
+        tmp
+    });
+    Ok(())
+}
+
+pub fn xchg_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let r1 = instr.op1_register();
+    rm8_x(x86, instr, |x86, x| {
+        let tmp = x86.regs.get8(r1);
+        x86.regs.set8(r1, x);
+        tmp
+    });
+    Ok(())
+}
+
 pub fn cmpxchg_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = x86.regs.get32(instr.op1_register());
-    match instr.op0_kind() {
-        iced_x86::OpKind::Register => todo!(),
-        iced_x86::OpKind::Memory => {
-            let addr = x86_addr(x86, instr);
-            let x = x86.mem.read_u32(addr);
-            if x86.regs.eax == x {
-                x86.mem.write_u32(addr, y);
-            } else {
-                x86.regs.eax = y;
-            }
+    rm32_x(x86, instr, |x86, x| {
+        let acc = x86.regs.eax;
+        sub(x86, acc, x);
+        if acc == x {
+            y
+        } else {
+            // The destination is always written, with its own value in this case.
+            x86.regs.eax = x;
+            x
         }
-        _ => unreachable!(),
-    };
+    });
+    Ok(())
+}
+
+pub fn cmpxchg_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.get16(instr.op1_register());
+    rm16_x(x86, instr, |x86, x| {
+        let acc = x86.regs.eax as u16;
+        sub(x86, acc, x);
+        if acc == x {
+            y
+        } else {
+            x86.regs.set16(iced_x86::Register::AX, x);
+            x
+        }
+    });
+    Ok(())
+}
+
+pub fn cmpxchg_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.get8(instr.op1_register());
+    rm8_x(x86, instr, |x86, x| {
+        let acc = x86.regs.eax as u8;
+        sub(x86, acc, x);
+        if acc == x {
+            y
+        } else {
+            x86.regs.set8(iced_x86::Register::AL, x);
+            x
+        }
+    });
+    Ok(())
+}
+
+pub fn cmpxchg8b_m64(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let addr = x86_addr(x86, instr);
+    let lo = x86.read_u32(addr);
+    let hi = x86.read_u32(addr.wrapping_add(4));
+    if lo == x86.regs.eax && hi == x86.regs.edx {
+        x86.regs.flags.insert(Flags::ZF);
+        x86.write_u32(addr, x86.regs.ebx);
+        x86.write_u32(addr.wrapping_add(4), x86.regs.ecx);
+    } else {
+        x86.regs.flags.remove(Flags::ZF);
+        x86.regs.eax = lo;
+        x86.regs.edx = hi;
+    }
+    Ok(())
+}
+
+pub fn xadd_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let r1 = instr.op1_register();
+    let y = x86.regs.get32(r1);
+    rm32_x(x86, instr, |x86, x| {
+        // The source gets the old value, then the destination gets the sum;
+        // if both are the same register, the sum wins.
+        x86.regs.set32(r1, x);
+        add(x86, x, y)
+    });
+    Ok(())
+}
+
+pub fn xadd_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let r1 = instr.op1_register();
+    let y = x86.regs.get16(r1);
+    rm16_x(x86, instr, |x86, x| {
+        x86.regs.set16(r1, x);
+        add(x86, x, y)
+    });
+    Ok(())
+}
+
+pub fn xadd_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let r1 = instr.op1_register();
+    let y = x86.regs.get8(r1);
+    rm8_x(x86, instr, |x86, x| {
+        x86.regs.set8(r1, x);
+        add(x86, x, y)
+    });
     Ok(())
 }
 
@@ -196,21 +337,28 @@ pub fn lea_r32_m(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn lea_r16_m(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    // lea ax,[esp+10h]
+    x86.regs
+        .set16(instr.op0_register(), x86_addr(x86, instr) as u16);
+    Ok(())
+}
+
 pub fn sete_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let value = x86.regs.flags.contains(Flags::ZF) as u8;
-    rm8_x(x86, instr, |_x86, _x| value);
+    rm8_set(x86, instr, value);
     Ok(())
 }
 
 pub fn setne_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let value = !x86.regs.flags.contains(Flags::ZF) as u8;
-    rm8_x(x86, instr, |_x86, _x| value);
+    rm8_set(x86, instr, value);
     Ok(())
 }
 
 pub fn setge_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let value = (x86.regs.flags.contains(Flags::ZF) == x86.regs.flags.contains(Flags::OF)) as u8;
-    rm8_x(x86, instr, |_x86, _x| value);
+    let value = (x86.regs.flags.contains(Flags::SF) == x86.regs.flags.contains(Flags::OF)) as u8;
+    rm8_set(x86, instr, value);
     Ok(())
 }
 
@@ -239,32 +387,37 @@ pub fn popad(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+/// The EFLAGS value as pushed by pushf, where reserved bit 1 always reads as 1.
+fn flags_image(x86: &X86) -> u32 {
+    x86.regs.flags.bits() | 0x2
+}
+
 pub fn pushfd(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
-    push(x86, x86.regs.flags.bits());
+    push(x86, flags_image(x86));
     Ok(())
 }
 
 pub fn pushfw(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
-    let value = (x86.regs.flags.bits() & 0x0000_FFFF) as u16;
+    let value = (flags_image(x86) & 0x0000_FFFF) as u16;
     push16(x86, value);
     Ok(())
 }
 
 pub fn popfd(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
-    x86.regs.flags = Flags::from_bits(pop(x86)).unwrap();
+    x86.regs.flags = Flags::from_bits_truncate(pop(x86));
     Ok(())
 }
 
 pub fn popfw(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
-    let prev = Flags::from_bits(x86.regs.flags.bits() & 0xFFFF_0000).unwrap();
-    let new = Flags::from_bits(pop16(x86) as u32).unwrap();
+    let prev = Flags::from_bits_truncate(x86.regs.flags.bits() & 0xFFFF_0000);
+    let new = Flags::from_bits_truncate(pop16(x86) as u32);
     x86.regs.flags = prev.union(new);
     Ok(())
 }
 
 pub fn sahf(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     let ah = (x86.regs.eax >> 8) as u8;
-    x86.regs.flags = Flags::from_bits((x86.regs.flags.bits() & 0xFFFF_FF00) | ah as u32).unwrap();
+    x86.regs.flags = Flags::from_bits_truncate((x86.regs.flags.bits() & 0xFFFF_FF00) | ah as u32);
     Ok(())
 }
 
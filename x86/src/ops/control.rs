@@ -132,6 +132,20 @@ pub fn js(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn jp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    if x86.regs.flags.contains(Flags::PF) {
+        x86_jmp(x86, instr.near_branch32())?;
+    }
+    Ok(())
+}
+
+pub fn jnp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    if !x86.regs.flags.contains(Flags::PF) {
+        x86_jmp(x86, instr.near_branch32())?;
+    }
+    Ok(())
+}
+
 pub fn loop_(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     x86.regs.ecx -= 1;
     if x86.regs.ecx != 0 {
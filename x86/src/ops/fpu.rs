@@ -1,9 +1,9 @@
-use iced_x86::Instruction;
+use iced_x86::{Instruction, MemorySize, OpKind, Register};
 
 use crate::{
-    registers::FPUStatus,
-    x86::{NULL_POINTER_REGION_SIZE, X86},
-    StepResult,
+    registers::{FPUStatus, Flags},
+    x86::X86,
+    StepError, StepResult,
 };
 
 use super::helpers::*;
@@ -12,25 +12,16 @@ fn read_f32(x86: &mut X86, addr: u32) -> f32 {
     f32::from_bits(x86.read_u32(addr))
 }
 
-pub fn read_f64(x86: &X86, addr: u32) -> f64 {
-    if addr < NULL_POINTER_REGION_SIZE {
-        panic!("null pointer read at {addr:#x}");
-    }
-    let addr = addr as usize;
-    let n = u64::from_le_bytes(x86.mem[addr..addr + 8].try_into().unwrap());
-    f64::from_bits(n)
+pub fn read_f64(x86: &mut X86, addr: u32) -> f64 {
+    f64::from_bits(x86.read_u64(addr))
 }
 
 pub fn write_f64(x86: &mut X86, addr: u32, value: f64) {
-    if addr < NULL_POINTER_REGION_SIZE {
-        panic!("null pointer read at {addr:#x}");
-    }
-    let addr = addr as usize;
-    x86.mem[addr..addr + 8].copy_from_slice(&f64::to_le_bytes(value));
+    x86.write_u64(addr, value.to_bits());
 }
 
 /// Compare two values and set floating-point comparison flags.
-fn fcom<T: std::cmp::PartialOrd>(x86: &mut X86, x: T, y: T) {
+fn compare<T: std::cmp::PartialOrd>(x86: &mut X86, x: T, y: T) {
     if x > y {
         x86.regs.fpu_status.set(FPUStatus::C3, false);
         x86.regs.fpu_status.set(FPUStatus::C2, false);
@@ -50,6 +41,24 @@ fn fcom<T: std::cmp::PartialOrd>(x86: &mut X86, x: T, y: T) {
     };
 }
 
+/// Compare two values and set EFLAGS like fcomi: ZF, PF and CF take the places
+/// of C3, C2 and C0.
+fn compare_eflags(x86: &mut X86, x: f64, y: f64) {
+    let (zf, pf, cf) = if x > y {
+        (false, false, false)
+    } else if x < y {
+        (false, false, true)
+    } else if x == y {
+        (true, false, false)
+    } else {
+        (true, true, true)
+    };
+    x86.regs.flags.set(Flags::ZF, zf);
+    x86.regs.flags.set(Flags::PF, pf);
+    x86.regs.flags.set(Flags::CF, cf);
+    x86.regs.flags.remove(Flags::OF | Flags::SF);
+}
+
 pub fn fld1(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     x86.regs.st_top -= 1;
     *x86.regs.st_top() = 1.0;
@@ -62,6 +71,36 @@ pub fn fldz(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn fldpi(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = std::f64::consts::PI;
+    Ok(())
+}
+
+pub fn fldl2e(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = std::f64::consts::LOG2_E;
+    Ok(())
+}
+
+pub fn fldl2t(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = std::f64::consts::LOG2_10;
+    Ok(())
+}
+
+pub fn fldlg2(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = std::f64::consts::LOG10_2;
+    Ok(())
+}
+
+pub fn fldln2(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = std::f64::consts::LN_2;
+    Ok(())
+}
+
 pub fn fld_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     x86.regs.st_top -= 1;
     *x86.regs.st_top() = read_f64(x86, x86_addr(x86, instr));
@@ -86,6 +125,12 @@ pub fn fild_m16int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn fild_m64int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = x86.read_u64(x86_addr(x86, instr)) as i64 as f64;
+    Ok(())
+}
+
 pub fn fst_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let f = *x86.regs.st_top();
     write_f64(x86, x86_addr(x86, instr), f);
@@ -106,17 +151,126 @@ pub fn fstp_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
-pub fn fistp_m64int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+/// Convert to an integer for the fist family, producing the "integer indefinite"
+/// value (the minimum integer) for NaN and out of range values.
+fn to_int<I: num_traits::Bounded + num_traits::NumCast>(f: f64) -> I {
+    num_traits::cast(f).unwrap_or_else(I::min_value)
+}
+
+/// Round to an integral value according to the rounding control field of the
+/// control word.
+fn round(x86: &X86, f: f64) -> f64 {
+    match (x86.regs.fpu_control >> 10) & 3 {
+        0 => f.round_ties_even(),
+        1 => f.floor(),
+        2 => f.ceil(),
+        _ => f.trunc(),
+    }
+}
+
+fn fist<I: num_traits::Bounded + num_traits::NumCast>(x86: &mut X86) -> I {
     let f = *x86.regs.st_top();
-    let addr = x86_addr(x86, instr) as usize;
-    x86.mem[addr..addr + 8].copy_from_slice(&(f as i64).to_le_bytes());
+    to_int(round(x86, f))
+}
+
+pub fn fist_m32int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = fist::<i32>(x86);
+    x86.write_u32(x86_addr(x86, instr), value as u32);
+    Ok(())
+}
+
+pub fn fist_m16int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = fist::<i16>(x86);
+    x86.write_u16(x86_addr(x86, instr), value as u16);
+    Ok(())
+}
+
+pub fn fistp_m64int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = fist::<i64>(x86);
+    x86.write_u64(x86_addr(x86, instr), value as u64);
     x86.regs.st_top += 1;
     Ok(())
 }
 
 pub fn fistp_m32int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    fist_m32int(x86, instr)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fistp_m16int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    fist_m16int(x86, instr)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+// fisttp always truncates, regardless of the control word.
+
+pub fn fisttp_m64int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = to_int::<i64>(x86.regs.st_top().trunc());
+    x86.write_u64(x86_addr(x86, instr), value as u64);
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fisttp_m32int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = to_int::<i32>(x86.regs.st_top().trunc());
+    x86.write_u32(x86_addr(x86, instr), value as u32);
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fisttp_m16int(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let value = to_int::<i16>(x86.regs.st_top().trunc());
+    x86.write_u16(x86_addr(x86, instr), value as u16);
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+/// Load an 80-bit packed BCD: 18 digits, two per byte with the low digits first,
+/// followed by a sign byte.
+pub fn fbld_m80bcd(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let addr = x86_addr(x86, instr);
+    let mut value = 0u64;
+    for i in (0..9).rev() {
+        let byte = x86.read_u8(addr.wrapping_add(i)) as u64;
+        value = value * 100 + (byte >> 4) * 10 + (byte & 0xF);
+    }
+    let negative = x86.read_u8(addr.wrapping_add(9)) & 0x80 != 0;
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = if negative {
+        -(value as f64)
+    } else {
+        value as f64
+    };
+    Ok(())
+}
+
+pub fn fbstp_m80bcd(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let addr = x86_addr(x86, instr);
     let f = *x86.regs.st_top();
-    x86.write_u32(x86_addr(x86, instr), f as i32 as u32);
+    let f = round(x86, f);
+    let mut bytes = [0u8; 10];
+    if f.abs() < 1e18 {
+        let mut value = f.abs() as u64;
+        for byte in &mut bytes[..9] {
+            let lo = (value % 10) as u8;
+            let hi = ((value / 10) % 10) as u8;
+            *byte = (hi << 4) | lo;
+            value /= 100;
+        }
+        if f.is_sign_negative() {
+            bytes[9] = 0x80;
+        }
+    } else {
+        // Out of range or NaN: the packed BCD indefinite value.
+        bytes[7] = 0xC0;
+        bytes[8] = 0xFF;
+        bytes[9] = 0xFF;
+    }
+    for (i, byte) in bytes.into_iter().enumerate() {
+        x86.write_u8(addr.wrapping_add(i as u32), byte);
+    }
     x86.regs.st_top += 1;
     Ok(())
 }
@@ -150,84 +304,248 @@ pub fn fsqrt(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
-pub fn fadd_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f64(x86, x86_addr(x86, instr));
-    *x86.regs.st_top() += y;
+pub fn fabs(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let reg = x86.regs.st_top();
+    *reg = reg.abs();
     Ok(())
 }
 
-pub fn fadd_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f32(x86, x86_addr(x86, instr)) as f64;
-    *x86.regs.st_top() += y;
+pub fn frndint(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let f = *x86.regs.st_top();
+    *x86.regs.st_top() = round(x86, f);
     Ok(())
 }
 
-pub fn faddp_sti_st0(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = *x86.regs.getst(instr.op1_register());
-    let x = x86.regs.getst(instr.op0_register());
-    *x += y;
-    x86.regs.st_top += 1;
+pub fn fscale(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.getst(Register::ST1).trunc();
+    let reg = x86.regs.st_top();
+    *reg *= y.exp2();
     Ok(())
 }
 
-pub fn fsub_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f32(x86, x86_addr(x86, instr)) as f64;
-    let x = x86.regs.st_top();
-    *x = *x - y;
+/// The binary exponent of a finite nonzero x, such that 2^e <= |x| < 2^(e+1).
+fn exponent(x: f64) -> i32 {
+    let bits = x.to_bits();
+    match ((bits >> 52) & 0x7ff) as i32 {
+        // Subnormal: the exponent comes from the top set bit of the fraction.
+        0 => 63 - (bits & ((1 << 52) - 1)).leading_zeros() as i32 - 1074,
+        e => e - 1023,
+    }
+}
+
+/// x * 2^exp, which is exact as long as the result is in range.
+fn ldexp(mut x: f64, mut exp: i32) -> f64 {
+    while exp > 1000 {
+        x *= 2f64.powi(1000);
+        exp -= 1000;
+    }
+    x * 2f64.powi(exp)
+}
+
+/// Shared implementation of fprem/fprem1, where `nearest` picks fprem1's
+/// round-to-nearest quotient over fprem's truncated one.
+/// Like the hardware, when x's exponent is 64 or more above y's we only partially
+/// reduce x, by a multiple of y, and set C2 ("incomplete") so the program loops.
+/// Otherwise C2 is clear and C0/C3/C1 get the low three bits of the quotient.
+fn fprem_common(x86: &mut X86, nearest: bool) {
+    let x = *x86.regs.st_top();
+    let y = *x86.regs.getst(Register::ST1);
+    let (r, q, incomplete) = if !x.is_finite() || !y.is_finite() || x == 0.0 || y == 0.0 {
+        (x % y, 0, false)
+    } else if exponent(x) - exponent(y) >= 64 {
+        // Leave a quotient of around 32 bits for the next round.
+        (x % ldexp(y, exponent(x) - exponent(y) - 32), 0, true)
+    } else {
+        // fmod is exact, and so are the differences below, so the quotient may
+        // exceed f64 precision without losing the remainder or its low bits.
+        let mut r = x % y;
+        let mut q = ((x % (8.0 * y) - r) / y).abs().round() as u64;
+        if nearest {
+            let half = y.abs() / 2.0;
+            if r.abs() > half || (r.abs() == half && q & 1 != 0) {
+                r -= y.abs().copysign(r);
+                q += 1;
+            }
+        }
+        (r, q, false)
+    };
+    *x86.regs.st_top() = r;
+    x86.regs.fpu_status.set(FPUStatus::C2, incomplete);
+    if !incomplete {
+        x86.regs.fpu_status.set(FPUStatus::C0, q & 0b100 != 0);
+        x86.regs.fpu_status.set(FPUStatus::C3, q & 0b010 != 0);
+        x86.regs.fpu_status.set(FPUStatus::C1, q & 0b001 != 0);
+    }
+}
+
+pub fn fprem(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    fprem_common(x86, false);
     Ok(())
 }
 
-pub fn fsubr_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f64(x86, x86_addr(x86, instr));
-    let x = x86.regs.st_top();
-    *x = y - *x;
+pub fn fprem1(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    fprem_common(x86, true);
     Ok(())
 }
 
-pub fn fsubr_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f32(x86, x86_addr(x86, instr)) as f64;
-    let x = x86.regs.st_top();
-    *x = y - *x;
+pub fn fptan(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let reg = x86.regs.st_top();
+    *reg = reg.tan();
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = 1.0;
     Ok(())
 }
 
-pub fn fmul_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f64(x86, x86_addr(x86, instr));
-    *x86.regs.st_top() *= y;
+pub fn fsincos(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let (sin, cos) = x86.regs.st_top().sin_cos();
+    *x86.regs.st_top() = sin;
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = cos;
     Ok(())
 }
 
-pub fn fmul_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f32(x86, x86_addr(x86, instr)) as f64;
-    *x86.regs.st_top() *= y;
+pub fn f2xm1(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let reg = x86.regs.st_top();
+    // 2^x - 1 computed via exp_m1 to keep precision for x near zero.
+    *reg = (*reg * std::f64::consts::LN_2).exp_m1();
     Ok(())
 }
 
-pub fn fmul_sti_sti(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = *x86.regs.getst(instr.op1_register());
-    let x = x86.regs.getst(instr.op0_register());
-    *x *= y;
+pub fn fyl2x(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let x = *x86.regs.st_top();
+    x86.regs.st_top += 1;
+    *x86.regs.st_top() *= x.log2();
     Ok(())
 }
 
-pub fn fmulp_sti_st0(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = *x86.regs.st_top();
-    let x = x86.regs.getst(instr.op0_register());
-    *x *= y;
+pub fn fyl2xp1(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let x = *x86.regs.st_top();
     x86.regs.st_top += 1;
+    *x86.regs.st_top() *= x.ln_1p() / std::f64::consts::LN_2;
     Ok(())
 }
 
-pub fn fdivrp_sti_st0(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+pub fn fxtract(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     let x = *x86.regs.st_top();
+    let (exp, sig) = if x == 0.0 {
+        (f64::NEG_INFINITY, x)
+    } else if x.is_infinite() {
+        (f64::INFINITY, x)
+    } else if x.is_nan() {
+        (x, x)
+    } else {
+        // Scale subnormals up into the normal range so the exponent bits are meaningful.
+        let (x, bias) = if x.is_subnormal() {
+            (x * 2f64.powi(54), 1023 + 54)
+        } else {
+            (x, 1023)
+        };
+        let bits = x.to_bits();
+        let exp = ((bits >> 52) & 0x7FF) as i64 - bias;
+        let sig = f64::from_bits((bits & !(0x7FF << 52)) | (1023 << 52));
+        (exp as f64, sig)
+    };
+    *x86.regs.st_top() = exp;
+    x86.regs.st_top -= 1;
+    *x86.regs.st_top() = sig;
+    Ok(())
+}
+
+/// Read the source operand of an arithmetic op: the memory operand in the
+/// one-operand forms, otherwise the second register.
+fn read_src(x86: &mut X86, instr: &Instruction) -> StepResult<f64> {
+    if instr.op0_kind() != OpKind::Memory {
+        return Ok(*x86.regs.getst(instr.op1_register()));
+    }
+    let addr = x86_addr(x86, instr);
+    Ok(match instr.memory_size() {
+        MemorySize::Float32 => read_f32(x86, addr) as f64,
+        MemorySize::Float64 => read_f64(x86, addr),
+        MemorySize::Int32 => x86.read_u32(addr) as i32 as f64,
+        MemorySize::Int16 => x86.read_u16(addr) as i16 as f64,
+        size => return Err(StepError::Error(format!("fpu operand {size:?}"))),
+    })
+}
+
+/// Shared implementation of the arithmetic ops, which come in the forms
+///   op st(0), mem       (written as just "op mem")
+///   op st(0), st(i)
+///   op st(i), st(0)
+/// and compute dst = op(dst, src).  The "r" variants (fsubr etc.) reverse the operands.
+fn arith(x86: &mut X86, instr: &Instruction, op: impl FnOnce(f64, f64) -> f64) -> StepResult<()> {
+    let y = read_src(x86, instr)?;
+    let dst = match instr.op0_kind() {
+        OpKind::Memory => Register::ST0,
+        _ => instr.op0_register(),
+    };
+    let x = x86.regs.getst(dst);
+    *x = op(*x, y);
+    Ok(())
+}
+
+pub fn fadd(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x + y)?;
+    Ok(())
+}
+
+pub fn faddp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x + y)?;
     x86.regs.st_top += 1;
-    *x86.regs.st_top() /= x;
     Ok(())
 }
 
-pub fn fdiv_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = read_f64(x86, x86_addr(x86, instr));
-    *x86.regs.st_top() /= y;
+pub fn fsub(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x - y)?;
+    Ok(())
+}
+
+pub fn fsubp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x - y)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fsubr(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| y - x)?;
+    Ok(())
+}
+
+pub fn fsubrp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| y - x)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fmul(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x * y)?;
+    Ok(())
+}
+
+pub fn fmulp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x * y)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fdiv(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x / y)?;
+    Ok(())
+}
+
+pub fn fdivp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| x / y)?;
+    x86.regs.st_top += 1;
+    Ok(())
+}
+
+pub fn fdivr(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| y / x)?;
+    Ok(())
+}
+
+pub fn fdivrp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    arith(x86, instr, |x, y| y / x)?;
+    x86.regs.st_top += 1;
     Ok(())
 }
 
@@ -236,37 +554,84 @@ pub fn fxch_st0_sti(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
-pub fn fcomp_m32fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+// The fucom variants differ from fcom only in which NaNs raise an invalid operation
+// exception, which we don't model, so the table maps them to the fcom ops.
+
+pub fn fcom(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let x = *x86.regs.st_top();
-    let y = read_f32(x86, x86_addr(x86, instr)) as f64;
-    fcom(x86, x, y);
+    let y = read_src(x86, instr)?;
+    compare(x86, x, y);
+    Ok(())
+}
+
+pub fn fcomp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    fcom(x86, instr)?;
     x86.regs.st_top += 1;
     Ok(())
 }
 
-pub fn fcomp_m64fp(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+pub fn fcompp(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
     let x = *x86.regs.st_top();
-    let y = read_f64(x86, x86_addr(x86, instr));
-    fcom(x86, x, y);
+    let y = *x86.regs.getst(Register::ST1);
+    compare(x86, x, y);
+    x86.regs.st_top += 2;
+    Ok(())
+}
+
+pub fn fcomi(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = *x86.regs.st_top();
+    let y = *x86.regs.getst(instr.op1_register());
+    compare_eflags(x86, x, y);
+    Ok(())
+}
+
+pub fn fcomip(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    fcomi(x86, instr)?;
     x86.regs.st_top += 1;
     Ok(())
 }
 
+pub fn ftst(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    let x = *x86.regs.st_top();
+    compare(x86, x, 0.0);
+    Ok(())
+}
+
+pub fn fxam(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
+    // C3, C2, C0 encode the class of st(0).
+    let (c3, c2, c0) = if x86.regs.st_top == 8 {
+        (true, false, true) // empty
+    } else {
+        let x = *x86.regs.st_top();
+        x86.regs.fpu_status.set(FPUStatus::C1, x.is_sign_negative());
+        match x.classify() {
+            std::num::FpCategory::Nan => (false, false, true),
+            std::num::FpCategory::Infinite => (false, true, true),
+            std::num::FpCategory::Zero => (true, false, false),
+            std::num::FpCategory::Subnormal => (true, true, false),
+            std::num::FpCategory::Normal => (false, true, false),
+        }
+    };
+    x86.regs.fpu_status.set(FPUStatus::C3, c3);
+    x86.regs.fpu_status.set(FPUStatus::C2, c2);
+    x86.regs.fpu_status.set(FPUStatus::C0, c0);
+    Ok(())
+}
+
 pub fn fnstsw_ax(x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
-    // TODO: does this need stack top in it?
-    x86.regs.eax = x86.regs.fpu_status.bits() as u32;
+    // The status word holds the stack top in bits 11-13.
+    let top = (x86.regs.st_top & 7) as u16;
+    let status = x86.regs.fpu_status.bits() | (top << 11);
+    x86.regs.set16(Register::AX, status);
     Ok(())
 }
 
 pub fn fnstcw_m2byte(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    // TODO: control word
-    let cw = 0x37u16; // default value
-    x86.write_u16(x86_addr(x86, instr), cw);
+    x86.write_u16(x86_addr(x86, instr), x86.regs.fpu_control);
     Ok(())
 }
 
 pub fn fldcw_m2byte(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    // TODO: control word
-    x86.read_u16(x86_addr(x86, instr));
+    x86.regs.fpu_control = x86.read_u16(x86_addr(x86, instr));
     Ok(())
 }
@@ -1,20 +1,6 @@
 //! Functions for common behaviors across all operations.
 
-use crate::{x86::X86, Memory, StepError, StepResult, NULL_POINTER_REGION_SIZE};
-
-pub fn read_u64(x86: &X86, addr: u32) -> u64 {
-    if addr < NULL_POINTER_REGION_SIZE {
-        panic!("null pointer read at {addr:#x}");
-    }
-    *x86.mem.view::<u64>(addr)
-}
-
-pub fn write_u64(x86: &mut X86, addr: u32, value: u64) {
-    if addr < NULL_POINTER_REGION_SIZE {
-        panic!("null pointer read at {addr:#x}");
-    }
-    *x86.mem.view_mut::<u64>(addr) = value;
-}
+use crate::{x86::X86, StepError, StepResult};
 
 // TODO: maybe there are no 64-bit memory reads needed (?)
 pub fn rm64_x(x86: &mut X86, instr: &iced_x86::Instruction, op: impl FnOnce(&mut X86, u64) -> u64) {
@@ -27,9 +13,9 @@ pub fn rm64_x(x86: &mut X86, instr: &iced_x86::Instruction, op: impl FnOnce(&mut
         }
         iced_x86::OpKind::Memory => {
             let addr = x86_addr(x86, instr);
-            let x = read_u64(x86, addr);
+            let x = x86.read_u64(addr);
             let value = op(x86, x);
-            write_u64(x86, addr, value);
+            x86.write_u64(addr, value);
         }
         _ => unimplemented!(),
     }
@@ -89,6 +75,54 @@ pub fn rm8_x(x86: &mut X86, instr: &iced_x86::Instruction, op: impl FnOnce(&mut
     }
 }
 
+/// Store to a rm64 operand without reading it first, unlike rm64_x.
+pub fn rm64_set(x86: &mut X86, instr: &iced_x86::Instruction, value: u64) {
+    match instr.op0_kind() {
+        iced_x86::OpKind::Register => x86.regs.set64(instr.op0_register(), value),
+        iced_x86::OpKind::Memory => {
+            let addr = x86_addr(x86, instr);
+            x86.write_u64(addr, value);
+        }
+        _ => unimplemented!(),
+    }
+}
+
+/// Store to a rm32 operand without reading it first, unlike rm32_x.
+pub fn rm32_set(x86: &mut X86, instr: &iced_x86::Instruction, value: u32) {
+    match instr.op0_kind() {
+        iced_x86::OpKind::Register => x86.regs.set32(instr.op0_register(), value),
+        iced_x86::OpKind::Memory => {
+            let addr = x86_addr(x86, instr);
+            x86.write_u32(addr, value);
+        }
+        _ => unimplemented!(),
+    }
+}
+
+/// Store to a rm16 operand without reading it first, unlike rm16_x.
+pub fn rm16_set(x86: &mut X86, instr: &iced_x86::Instruction, value: u16) {
+    match instr.op0_kind() {
+        iced_x86::OpKind::Register => x86.regs.set16(instr.op0_register(), value),
+        iced_x86::OpKind::Memory => {
+            let addr = x86_addr(x86, instr);
+            x86.write_u16(addr, value);
+        }
+        _ => unimplemented!(),
+    }
+}
+
+/// Store to a rm8 operand without reading it first, unlike rm8_x.
+pub fn rm8_set(x86: &mut X86, instr: &iced_x86::Instruction, value: u8) {
+    match instr.op0_kind() {
+        iced_x86::OpKind::Register => x86.regs.set8(instr.op0_register(), value),
+        iced_x86::OpKind::Memory => {
+            let addr = x86_addr(x86, instr);
+            x86.write_u8(addr, value);
+        }
+        _ => unimplemented!(),
+    }
+}
+
 pub fn op0_rm32(x86: &mut X86, instr: &iced_x86::Instruction) -> u32 {
     match instr.op0_kind() {
         iced_x86::OpKind::Register => x86.regs.get32(instr.op0_register()),
@@ -172,14 +206,34 @@ pub fn x86_addr(x86: &X86, instr: &iced_x86::Instruction) -> u32 {
         _ => 0,
     };
 
-    let base = if instr.memory_base() != iced_x86::Register::None {
-        x86.regs.get32(instr.memory_base())
+    let base = instr.memory_base();
+    let index = instr.memory_index();
+    if base.is_gpr16()
+        || index.is_gpr16()
+        || (base == iced_x86::Register::None
+            && index == iced_x86::Register::None
+            && instr.memory_displ_size() == 2)
+    {
+        // 16-bit addressing, via the 0x67 address-size prefix: the components are
+        // all 16-bit and the offset wraps around at 64k.
+        let mut ofs = instr.memory_displacement32() as u16;
+        if base != iced_x86::Register::None {
+            ofs = ofs.wrapping_add(x86.regs.get16(base));
+        }
+        if index != iced_x86::Register::None {
+            ofs = ofs.wrapping_add(x86.regs.get16(index));
+        }
+        return seg.wrapping_add(ofs as u32);
+    }
+
+    let base = if base != iced_x86::Register::None {
+        x86.regs.get32(base)
     } else {
         0
     };
-    let index = if instr.memory_index() != iced_x86::Register::None {
+    let index = if index != iced_x86::Register::None {
         x86.regs
-            .get32(instr.memory_index())
+            .get32(index)
             .wrapping_mul(instr.memory_index_scale())
     } else {
         0
@@ -1,6 +1,6 @@
 use iced_x86::Instruction;
 
-use crate::{registers::Flags, x86::X86, StepResult};
+use crate::{registers::Flags, x86::X86, StepError, StepResult};
 
 use super::helpers::*;
 
@@ -39,15 +39,29 @@ impl Int for u8 {
     }
 }
 
-// pub(crate) for use in the test opcode impl.
-pub(crate) fn and<I: Int>(x86: &mut X86, x: I, y: I) -> I {
-    let result = x & y;
-    // XXX More flags.
+/// Set ZF, SF and PF, which most ops derive from their result in the same way.
+fn set_result_flags<I: Int>(x86: &mut X86, result: I) {
     x86.regs.flags.set(Flags::ZF, result.is_zero());
     x86.regs
         .flags
         .set(Flags::SF, (result >> (I::bits() - 1)).is_one());
-    x86.regs.flags.set(Flags::OF, false);
+    // PF reflects only the low byte of the result.
+    let low = result.as_usize() as u8;
+    x86.regs.flags.set(Flags::PF, low.count_ones() % 2 == 0);
+}
+
+/// Set AF, the carry or borrow out of bit 3, for an add or subtract of x and y.
+fn set_af<I: Int>(x86: &mut X86, x: I, y: I, result: I) {
+    x86.regs
+        .flags
+        .set(Flags::AF, (x ^ y ^ result).as_usize() & 0x10 != 0);
+}
+
+// pub(crate) for use in the test opcode impl.
+pub(crate) fn and<I: Int>(x86: &mut X86, x: I, y: I) -> I {
+    let result = x & y;
+    set_result_flags(x86, result);
+    x86.regs.flags.remove(Flags::OF | Flags::CF);
     result
 }
 
@@ -72,7 +86,7 @@ pub fn and_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 pub fn and_r32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let reg = instr.op0_register();
     let y = op1_rm32(x86, instr);
-    let value = x86.regs.get32(reg) & y;
+    let value = and(x86, x86.regs.get32(reg), y);
     x86.regs.set32(reg, value);
     Ok(())
 }
@@ -91,8 +105,8 @@ pub fn and_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 
 fn or<I: Int>(x86: &mut X86, x: I, y: I) -> I {
     let result = x | y;
-    // XXX More flags.
-    x86.regs.flags.set(Flags::ZF, result.is_zero());
+    set_result_flags(x86, result);
+    x86.regs.flags.remove(Flags::OF | Flags::CF);
     result
 }
 
@@ -102,6 +116,18 @@ pub fn or_rm32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn or_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = op1_rm16(x86, instr);
+    rm16_x(x86, instr, |x86, x| or(x86, x, y));
+    Ok(())
+}
+
+pub fn or_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = op1_rm8(x86, instr);
+    rm8_x(x86, instr, |x86, x| or(x86, x, y));
+    Ok(())
+}
+
 pub fn or_rm32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate32();
     rm32_x(x86, instr, |x86, x| or(x86, x, y));
@@ -120,22 +146,34 @@ pub fn or_rm16_imm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+pub fn or_rm16_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate8to16() as u16;
+    rm16_x(x86, instr, |x86, x| or(x86, x, y));
+    Ok(())
+}
+
 pub fn or_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate8();
     rm8_x(x86, instr, |x86, x| or(x86, x, y));
     Ok(())
 }
 
-fn shl<I: Int + num_traits::WrappingShl>(x86: &mut X86, x: I, y: u8) -> I {
+/// Mask a shift or rotate count to 5 bits, as the processor does for all operand sizes
+/// other than 64-bit.
+fn shift_count(y: u8) -> usize {
+    (y & 0x1F) as usize
+}
+
+fn shl<I: Int>(x86: &mut X86, x: I, y: u8) -> I {
+    let y = shift_count(y);
     if y == 0 {
         return x;
     }
-    // Carry is the highest bit that will be shifted out.
-    let cf = (x.shr(I::bits() - y as usize) & I::one()).is_one();
-    let val = x.wrapping_shl(y.as_usize() as u32);
+    // Carry is the last bit shifted out; undefined (here, clear) past the operand width.
+    let cf = y <= I::bits() && (x.shr(I::bits() - y) & I::one()).is_one();
+    let val = if y < I::bits() { x.shl(y) } else { I::zero() };
     x86.regs.flags.set(Flags::CF, cf);
-    let msb = val.shr(I::bits() - 1).is_one();
-    x86.regs.flags.set(Flags::SF, msb);
+    set_result_flags(x86, val);
     // OF undefined for shifts != 1, but this matches what Windows machine does, and also docs:
     // "For left shifts, the OF flag is set to 0 if the mostsignificant bit of the result is the
     // same as the CF flag (that is, the top two bits of the original operand were the same) [...]"
@@ -143,7 +181,6 @@ fn shl<I: Int + num_traits::WrappingShl>(x86: &mut X86, x: I, y: u8) -> I {
         Flags::OF,
         x.shr(I::bits() - 1).is_one() ^ (x.shr(I::bits() - 2) & I::one()).is_one(),
     );
-    x86.regs.flags.set(Flags::ZF, val.is_zero());
 
     val
 }
@@ -173,15 +210,16 @@ pub fn shl_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 }
 
 fn shr<I: Int>(x86: &mut X86, x: I, y: u8) -> I {
+    let y = shift_count(y);
     if y == 0 {
         return x; // Don't affect flags.
     }
-    x86.regs
-        .flags
-        .set(Flags::CF, ((x >> (y - 1) as usize) & I::one()).is_one());
-    let val = x >> y as usize;
-    x86.regs.flags.set(Flags::SF, false); // ?
-    x86.regs.flags.set(Flags::ZF, val.is_zero());
+    x86.regs.flags.set(
+        Flags::CF,
+        y <= I::bits() && ((x >> (y - 1)) & I::one()).is_one(),
+    );
+    let val = if y < I::bits() { x >> y } else { I::zero() };
+    set_result_flags(x86, val);
 
     // Note: OF state undefined for shifts > 1 bit, but the following behavior
     // matches what my Windows box does in practice.
@@ -208,106 +246,108 @@ pub fn shr_rm32_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
-fn sar<I: Int>(x86: &mut X86, x: I, y: I) -> I {
-    if y.is_zero() {
+fn sar<I: Int>(x86: &mut X86, x: I, y: u8) -> I {
+    let y = shift_count(y);
+    if y == 0 {
         return x;
     }
-    x86.regs
-        .flags
-        .set(Flags::CF, x.shr(y.as_usize() - 1).bitand(I::one()).is_one());
-    x86.regs.flags.set(Flags::OF, false);
+    // Shifting by the operand width or more leaves only copies of the sign bit.
     // There's a random "u32" type in the num-traits signed_shr signature, so cast here.
-    let result = x.signed_shr(y.as_usize() as u32);
-
-    x86.regs
-        .flags
-        .set(Flags::SF, result.shr(I::bits() - 1).is_one());
-    x86.regs.flags.set(Flags::ZF, result.is_zero());
+    let max = I::bits() - 1;
+    x86.regs.flags.set(
+        Flags::CF,
+        (x.signed_shr((y - 1).min(max) as u32) & I::one()).is_one(),
+    );
+    x86.regs.flags.set(Flags::OF, false);
+    let result = x.signed_shr(y.min(max) as u32);
+    set_result_flags(x86, result);
     result
 }
 
 pub fn sar_rm32_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = instr.immediate8() as u32;
+    let y = instr.immediate8();
     rm32_x(x86, instr, |x86, x| sar(x86, x, y));
     Ok(())
 }
 
 pub fn sar_rm32_cl(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = x86.regs.ecx as u8 as u32;
+    let y = x86.regs.ecx as u8;
     rm32_x(x86, instr, |x86, x| sar(x86, x, y));
     Ok(())
 }
 
 pub fn sar_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = instr.immediate8() as u8;
+    let y = instr.immediate8();
     rm8_x(x86, instr, |x86, x| sar(x86, x, y));
     Ok(())
 }
 
 pub fn ror_rm32_cl(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let y = x86.regs.ecx as u8;
+    let y = shift_count(x86.regs.ecx as u8);
     rm32_x(x86, instr, |x86, x| {
+        if y == 0 {
+            return x;
+        }
         let out = x.rotate_right(y as u32);
         let msb = (out & 0x8000_0000) != 0;
         x86.regs.flags.set(Flags::CF, msb);
         x86.regs
             .flags
-            .set(Flags::OF, msb ^ ((out & 04000_0000) != 0));
+            .set(Flags::OF, msb ^ ((out & 0x4000_0000) != 0));
         out
     });
     Ok(())
 }
 
-fn xor32(x86: &mut X86, x: u32, y: u32) -> u32 {
+fn xor<I: Int>(x86: &mut X86, x: I, y: I) -> I {
     let result = x ^ y;
     // The OF and CF flags are cleared; the SF, ZF, and PF flags are set according to the result. The state of the AF flag is undefined.
     x86.regs.flags.remove(Flags::OF);
     x86.regs.flags.remove(Flags::CF);
-    x86.regs.flags.set(Flags::ZF, result == 0);
-    x86.regs.flags.set(Flags::SF, result & 0x8000_0000 != 0);
+    set_result_flags(x86, result);
     result
 }
 
 pub fn xor_rm32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm32(x86, instr);
-    rm32_x(x86, instr, |x86, x| xor32(x86, x, y));
+    rm32_x(x86, instr, |x86, x| xor(x86, x, y));
     Ok(())
 }
 
 pub fn xor_rm32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate32();
-    rm32_x(x86, instr, |x86, x| xor32(x86, x, y));
+    rm32_x(x86, instr, |x86, x| xor(x86, x, y));
     Ok(())
 }
 
 pub fn xor_rm32_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate8to32() as u32;
-    rm32_x(x86, instr, |x86, x| xor32(x86, x, y));
+    rm32_x(x86, instr, |x86, x| xor(x86, x, y));
     Ok(())
 }
 
 pub fn xor_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = instr.immediate8();
-    rm8_x(x86, instr, |_x86, x| x ^ y);
-    // TODO: flags
+    rm8_x(x86, instr, |x86, x| xor(x86, x, y));
     Ok(())
 }
 
 pub fn xor_r8_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm8(x86, instr);
-    rm8_x(x86, instr, |_x86, x| x ^ y);
-    // TODO: flags
+    rm8_x(x86, instr, |x86, x| xor(x86, x, y));
     Ok(())
 }
 
-fn add<I: Int + num_traits::ops::overflowing::OverflowingAdd>(x86: &mut X86, x: I, y: I) -> I {
-    // TODO "The CF, OF, SF, ZF, AF, and PF flags are set according to the result."
+// pub(crate) for use in the xadd opcode impl.
+pub(crate) fn add<I: Int + num_traits::ops::overflowing::OverflowingAdd>(
+    x86: &mut X86,
+    x: I,
+    y: I,
+) -> I {
     let (result, carry) = x.overflowing_add(&y);
     x86.regs.flags.set(Flags::CF, carry);
-    x86.regs.flags.set(Flags::ZF, result.is_zero());
-    x86.regs
-        .flags
-        .set(Flags::SF, (result >> (I::bits() - 1)).is_one());
+    set_result_flags(x86, result);
+    set_af(x86, x, y, result);
     // Overflow is true exactly when the high (sign) bits are like:
     //   x  y  result
     //   0  0  1
@@ -380,12 +420,9 @@ pub(crate) fn sub<I: Int + num_traits::ops::overflowing::OverflowingSub>(
     y: I,
 ) -> I {
     let (result, carry) = x.overflowing_sub(&y);
-    // TODO "The CF, OF, SF, ZF, AF, and PF flags are set according to the result."
     x86.regs.flags.set(Flags::CF, carry);
-    x86.regs.flags.set(Flags::ZF, result.is_zero());
-    x86.regs
-        .flags
-        .set(Flags::SF, (result >> (I::bits() - 1)).is_one());
+    set_result_flags(x86, result);
+    set_af(x86, x, y, result);
     // Overflow is true exactly when the high (sign) bits are like:
     //   x  y  result
     //   0  1  1
@@ -435,110 +472,364 @@ pub fn sub_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     Ok(())
 }
 
+/// add with carry in; like add, but CF and OF account for the carry too.
+fn adc<I: Int + num_traits::ops::overflowing::OverflowingAdd>(x86: &mut X86, x: I, y: I) -> I {
+    let carry = x86.regs.flags.contains(Flags::CF);
+    let (sum, carry1) = x.overflowing_add(&y);
+    let (result, carry2) = sum.overflowing_add(&if carry { I::one() } else { I::zero() });
+    x86.regs.flags.set(Flags::CF, carry1 || carry2);
+    set_result_flags(x86, result);
+    set_af(x86, x, y, result);
+    // Same as add: the carry in can't change which sign combinations overflow.
+    let of = !(((x ^ !y) & (x ^ result)) >> (I::bits() - 1)).is_zero();
+    x86.regs.flags.set(Flags::OF, of);
+    result
+}
+
+pub fn adc_r32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let reg = instr.op0_register();
+    let y = op1_rm32(x86, instr);
+    let value = adc(x86, x86.regs.get32(reg), y);
+    x86.regs.set32(reg, value);
+    Ok(())
+}
+
+pub fn adc_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.get32(instr.op1_register());
+    rm32_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_rm32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate32();
+    rm32_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_rm32_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate8to32() as u32;
+    rm32_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_r16_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let reg = instr.op0_register();
+    let y = op1_rm16(x86, instr);
+    let value = adc(x86, x86.regs.get16(reg), y);
+    x86.regs.set16(reg, value);
+    Ok(())
+}
+
+pub fn adc_rm16_r16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.get16(instr.op1_register());
+    rm16_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_rm16_imm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate16();
+    rm16_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_rm16_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate8to16() as u16;
+    rm16_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_r8_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let reg = instr.op0_register();
+    let y = op1_rm8(x86, instr);
+    let value = adc(x86, x86.regs.get8(reg), y);
+    x86.regs.set8(reg, value);
+    Ok(())
+}
+
+pub fn adc_rm8_r8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = x86.regs.get8(instr.op1_register());
+    rm8_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+pub fn adc_rm8_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let y = instr.immediate8();
+    rm8_x(x86, instr, |x86, x| adc(x86, x, y));
+    Ok(())
+}
+
+/// subtract with borrow in; like sub, but CF and OF account for the borrow too.
+fn sbb<I: Int + num_traits::ops::overflowing::OverflowingSub>(x86: &mut X86, x: I, y: I) -> I {
+    let borrow = x86.regs.flags.contains(Flags::CF);
+    let (diff, borrow1) = x.overflowing_sub(&y);
+    let (result, borrow2) = diff.overflowing_sub(&if borrow { I::one() } else { I::zero() });
+    x86.regs.flags.set(Flags::CF, borrow1 || borrow2);
+    set_result_flags(x86, result);
+    set_af(x86, x, y, result);
+    let of = !(((x ^ y) & (x ^ result)) >> (I::bits() - 1)).is_zero();
+    x86.regs.flags.set(Flags::OF, of);
+    result
+}
+
 pub fn sbb_r32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let reg = instr.op0_register();
-    let carry = x86.regs.flags.contains(Flags::CF) as u32;
-    let y = op1_rm32(x86, instr).wrapping_add(carry);
-    let value = sub(x86, x86.regs.get32(reg), y);
+    let y = op1_rm32(x86, instr);
+    let value = sbb(x86, x86.regs.get32(reg), y);
     x86.regs.set32(reg, value);
     Ok(())
 }
 
 pub fn sbb_rm32_r32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let carry = x86.regs.flags.contains(Flags::CF) as u32;
-    let y = x86.regs.get32(instr.op1_register()) + carry;
-    rm32_x(x86, instr, |x86, x| sub(x86, x, y));
+    let y = x86.regs.get32(instr.op1_register());
+    rm32_x(x86, instr, |x86, x| sbb(x86, x, y));
     Ok(())
 }
 
 pub fn sbb_r8_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let reg = instr.op0_register();
-    let carry = x86.regs.flags.contains(Flags::CF) as u8;
-    let y = op1_rm8(x86, instr).wrapping_add(carry);
-    let value = sub(x86, x86.regs.get8(reg), y);
+    let y = op1_rm8(x86, instr);
+    let value = sbb(x86, x86.regs.get8(reg), y);
     x86.regs.set8(reg, value);
     Ok(())
 }
 
+/// Set the flags after a multiply: CF and OF are set when the result didn't fit
+/// in the low half of the destination.
+fn mul_flags(x86: &mut X86, overflow: bool) {
+    x86.regs.flags.set(Flags::CF, overflow);
+    x86.regs.flags.set(Flags::OF, overflow);
+}
+
+pub fn mul_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as u64;
+    let y = op0_rm32(x86, instr) as u64;
+    let value = x * y;
+    x86.regs.eax = value as u32;
+    x86.regs.edx = (value >> 32) as u32;
+    mul_flags(x86, x86.regs.edx != 0);
+    Ok(())
+}
+
+pub fn mul_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as u16 as u32;
+    let y = op0_rm16(x86, instr) as u32;
+    let value = x * y;
+    x86.regs.set16(iced_x86::Register::AX, value as u16);
+    x86.regs.set16(iced_x86::Register::DX, (value >> 16) as u16);
+    mul_flags(x86, (value >> 16) != 0);
+    Ok(())
+}
+
+pub fn mul_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as u8 as u16;
+    let y = op0_rm8(x86, instr) as u16;
+    let value = x * y;
+    x86.regs.set16(iced_x86::Register::AX, value);
+    mul_flags(x86, (value >> 8) != 0);
+    Ok(())
+}
+
+pub fn imul_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as i32 as i64;
+    let y = op0_rm32(x86, instr) as i32 as i64;
+    let value = x * y;
+    x86.regs.eax = value as u32;
+    x86.regs.edx = (value >> 32) as u32;
+    mul_flags(x86, value != value as i32 as i64);
+    Ok(())
+}
+
+pub fn imul_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as i16 as i32;
+    let y = op0_rm16(x86, instr) as i16 as i32;
+    let value = x * y;
+    x86.regs.set16(iced_x86::Register::AX, value as u16);
+    x86.regs.set16(iced_x86::Register::DX, (value >> 16) as u16);
+    mul_flags(x86, value != value as i16 as i32);
+    Ok(())
+}
+
+pub fn imul_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as i8 as i16;
+    let y = op0_rm8(x86, instr) as i8 as i16;
+    let value = x * y;
+    x86.regs.set16(iced_x86::Register::AX, value as u16);
+    mul_flags(x86, value != value as i8 as i16);
+    Ok(())
+}
+
 pub fn imul_r32_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let x = x86.regs.get32(instr.op0_register());
-    let y = op1_rm32(x86, instr);
-    let value = x.wrapping_mul(y);
-    x86.regs.set32(instr.op0_register(), value);
+    let x = x86.regs.get32(instr.op0_register()) as i32;
+    let y = op1_rm32(x86, instr) as i32;
+    let (value, overflow) = x.overflowing_mul(y);
+    x86.regs.set32(instr.op0_register(), value as u32);
+    mul_flags(x86, overflow);
     Ok(())
 }
 
 pub fn imul_r32_rm32_imm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let x = op1_rm32(x86, instr) as i32;
     let y = instr.immediate32() as i32;
-    let value = x.wrapping_mul(y);
+    let (value, overflow) = x.overflowing_mul(y);
     x86.regs.set32(instr.op0_register(), value as u32);
+    mul_flags(x86, overflow);
     Ok(())
 }
 
 pub fn imul_r32_rm32_imm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let x = op1_rm32(x86, instr) as i32;
     let y = instr.immediate8to32();
-    let value = x.wrapping_mul(y);
+    let (value, overflow) = x.overflowing_mul(y);
     x86.regs.set32(instr.op0_register(), value as u32);
+    mul_flags(x86, overflow);
     Ok(())
 }
 
-pub fn idiv_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let x = (((x86.regs.edx as u64) << 32) | (x86.regs.eax as u64)) as i64;
-    let y = op0_rm32(x86, instr) as i32 as i64;
-    x86.regs.eax = (x / y) as i32 as u32;
-    x86.regs.edx = (x % y) as i32 as u32;
-    // TODO: flags.
-    Ok(())
+/// The #DE exception, raised by div/idiv when the divisor is zero or the quotient
+/// doesn't fit in the destination.
+fn divide_error() -> StepError {
+    StepError::Error("#DE: divide error".into())
 }
 
 pub fn div_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let x = ((x86.regs.edx as u64) << 32) | (x86.regs.eax as u64);
     let y = op0_rm32(x86, instr) as u64;
-    x86.regs.eax = (x / y) as u32;
-    x86.regs.edx = (x % y) as u32;
-    // TODO: flags.
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q <= u32::MAX as u64 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.eax = quotient as u32;
+    x86.regs.edx = remainder as u32;
+    Ok(())
+}
+
+pub fn div_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = ((x86.regs.edx as u16 as u32) << 16) | (x86.regs.eax as u16 as u32);
+    let y = op0_rm16(x86, instr) as u32;
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q <= u16::MAX as u32 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.set16(iced_x86::Register::AX, quotient as u16);
+    x86.regs.set16(iced_x86::Register::DX, remainder as u16);
     Ok(())
 }
 
+pub fn div_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as u16;
+    let y = op0_rm8(x86, instr) as u16;
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q <= u8::MAX as u16 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.set8(iced_x86::Register::AL, quotient as u8);
+    x86.regs.set8(iced_x86::Register::AH, remainder as u8);
+    Ok(())
+}
+
+// For the signed divisions, the dividend and divisor are widened so that the only
+// possible Rust overflow (MIN / -1) can't occur; an out of range quotient is
+// caught by the range check instead.
+
+pub fn idiv_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = (((x86.regs.edx as u64) << 32) | (x86.regs.eax as u64)) as i64 as i128;
+    let y = op0_rm32(x86, instr) as i32 as i128;
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q == q as i32 as i128 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.eax = quotient as u32;
+    x86.regs.edx = remainder as u32;
+    Ok(())
+}
+
+pub fn idiv_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = (((x86.regs.edx as u16 as u32) << 16) | (x86.regs.eax as u16 as u32)) as i32 as i64;
+    let y = op0_rm16(x86, instr) as i16 as i64;
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q == q as i16 as i64 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.set16(iced_x86::Register::AX, quotient as u16);
+    x86.regs.set16(iced_x86::Register::DX, remainder as u16);
+    Ok(())
+}
+
+pub fn idiv_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let x = x86.regs.eax as u16 as i16 as i32;
+    let y = op0_rm8(x86, instr) as i8 as i32;
+    let (quotient, remainder) = match (x.checked_div(y), x.checked_rem(y)) {
+        (Some(q), Some(r)) if q == q as i8 as i32 => (q, r),
+        _ => return Err(divide_error()),
+    };
+    x86.regs.set8(iced_x86::Register::AL, quotient as u8);
+    x86.regs.set8(iced_x86::Register::AH, remainder as u8);
+    Ok(())
+}
+
+/// inc is add(1) except that it leaves CF alone.
+fn inc<I: Int + num_traits::ops::overflowing::OverflowingAdd>(x86: &mut X86, x: I) -> I {
+    let cf = x86.regs.flags.contains(Flags::CF);
+    let result = add(x86, x, I::one());
+    x86.regs.flags.set(Flags::CF, cf);
+    result
+}
+
+/// dec is sub(1) except that it leaves CF alone.
+fn dec<I: Int + num_traits::ops::overflowing::OverflowingSub>(x86: &mut X86, x: I) -> I {
+    let cf = x86.regs.flags.contains(Flags::CF);
+    let result = sub(x86, x, I::one());
+    x86.regs.flags.set(Flags::CF, cf);
+    result
+}
+
 pub fn dec_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    rm32_x(x86, instr, |x86, x| sub(x86, x, 1));
+    rm32_x(x86, instr, dec);
+    Ok(())
+}
+
+pub fn dec_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    rm16_x(x86, instr, dec);
     Ok(())
 }
 
 pub fn dec_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    rm8_x(x86, instr, |x86, x| sub(x86, x, 1));
+    rm8_x(x86, instr, dec);
     Ok(())
 }
 
 pub fn inc_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    // TODO: flags.  Note that it's not add(1) because CF should be preserved.
-    rm32_x(x86, instr, |_x86, x| x + 1);
+    rm32_x(x86, instr, inc);
+    Ok(())
+}
+
+pub fn inc_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    rm16_x(x86, instr, inc);
     Ok(())
 }
 
 pub fn inc_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    // TODO: flags.  Note that it's not add(1) because CF should be preserved.
-    rm8_x(x86, instr, |_x86, x| x.wrapping_add(1));
+    rm8_x(x86, instr, inc);
     Ok(())
 }
 
+/// neg is a subtraction from zero, which also gets CF (set unless x is zero) right.
+fn neg<I: Int + num_traits::ops::overflowing::OverflowingSub>(x86: &mut X86, x: I) -> I {
+    sub(x86, I::zero(), x)
+}
+
 pub fn neg_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    rm32_x(x86, instr, |x86, x| {
-        x86.regs.flags.set(Flags::CF, x != 0);
-        // TODO: other flags registers.
-        -(x as i32) as u32
-    });
+    rm32_x(x86, instr, neg);
+    Ok(())
+}
+
+pub fn neg_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    rm16_x(x86, instr, neg);
     Ok(())
 }
 
 pub fn neg_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    rm8_x(x86, instr, |x86, x| {
-        x86.regs.flags.set(Flags::CF, x != 0);
-        // TODO: other flags registers.
-        -(x as i8) as u8
-    });
+    rm8_x(x86, instr, neg);
     Ok(())
 }
 
@@ -546,3 +837,13 @@ pub fn not_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     rm32_x(x86, instr, |_x86, x| !x);
     Ok(())
 }
+
+pub fn not_rm16(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    rm16_x(x86, instr, |_x86, x| !x);
+    Ok(())
+}
+
+pub fn not_rm8(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    rm8_x(x86, instr, |_x86, x| !x);
+    Ok(())
+}
@@ -7,7 +7,7 @@ use super::helpers::*;
 fn op1_mmm64(x86: &mut X86, instr: &iced_x86::Instruction) -> u64 {
     match instr.op1_kind() {
         iced_x86::OpKind::Register => x86.regs.get64(instr.op1_register()),
-        iced_x86::OpKind::Memory => read_u64(x86, x86_addr(x86, instr)),
+        iced_x86::OpKind::Memory => x86.read_u64(x86_addr(x86, instr)),
         _ => unreachable!(),
     }
 }
@@ -28,13 +28,13 @@ pub fn pxor_mm_mmm64(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 
 pub fn movd_mm_rm32(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = op1_rm32(x86, instr) as u64;
-    rm64_x(x86, instr, |_x86, _x| y);
+    rm64_set(x86, instr, y);
     Ok(())
 }
 
 pub fn movd_rm32_mm(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     let y = x86.regs.get64(instr.op1_register()) as u32;
-    rm32_x(x86, instr, |_x86, _x| y);
+    rm32_set(x86, instr, y);
     Ok(())
 }
 
@@ -55,13 +55,17 @@ pub fn punpcklbw_mm_mmm32(x86: &mut X86, instr: &Instruction) -> StepResult<()>
 }
 
 pub fn pmullw_mm_mmm64(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    fn op(x: u16, y: u16) -> u16 {
+        // Low 16 bits of the signed product.
+        (x as i16).wrapping_mul(y as i16) as u16
+    }
+
     let y = op1_mmm64(x86, instr);
     rm64_x(x86, instr, |_x86, x| {
-        let t0 = (((x >> 0) & 0xFFFF) as i16 as u32) * (((y >> 0) & 0xFFFF) as i16 as u32);
-        let t1 = (((x >> 16) & 0xFFFF) as i16 as u32) * (((y >> 16) & 0xFFFF) as i16 as u32);
-        let t2 = (((x >> 32) & 0xFFFF) as i16 as u32) * (((y >> 32) & 0xFFFF) as i16 as u32);
-        let t3 = (((x >> 48) & 0xFFFF) as i16 as u32) * (((y >> 48) & 0xFFFF) as i16 as u32);
-        (t3 as u64) << 48 | (t2 as u64) << 32 | (t1 as u64) << 16 | (t0 as u64)
+        ((op((x >> 0) as u16, (y >> 0) as u16) as u64) << 0)
+            | ((op((x >> 16) as u16, (y >> 16) as u16) as u64) << 16)
+            | ((op((x >> 32) as u16, (y >> 32) as u16) as u64) << 32)
+            | ((op((x >> 48) as u16, (y >> 48) as u16) as u64) << 48)
     });
     Ok(())
 }
@@ -109,7 +113,7 @@ pub fn emms(_x86: &mut X86, _instr: &Instruction) -> StepResult<()> {
 
 pub fn psubusb_mm_mmm64(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     fn op(x: u8, y: u8) -> u8 {
-        y.saturating_sub(x)
+        x.saturating_sub(y)
     }
 
     let y = op1_mmm64(x86, instr);
@@ -14,5 +14,7 @@ pub use helpers::{pop, push, x86_jmp};
 pub use math::*;
 pub use mmx::*;
 pub use string::*;
+#[cfg(test)]
+pub(crate) use table::has_op;
 pub use table::{execute, init_op_tab};
 pub use test::*;
@@ -1,59 +1,138 @@
 use super::math::sub;
 use iced_x86::Instruction;
 
-use crate::{memory::Memory, registers::Flags, x86::X86, StepError, StepResult};
+use crate::{registers::Flags, x86::X86, StepError, StepResult};
 
-pub fn cmps(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    assert!(x86.regs.flags.contains(Flags::DF)); // TODO
-    let p1 = x86.regs.esi as usize;
-    let p2 = x86.regs.edi as usize;
-    let count = x86.regs.ecx as usize;
-    if instr.has_repe_prefix() {
-        let pos = x86.mem[p1..p1 + count]
-            .iter()
-            .zip(x86.mem[p2..p2 + count].iter())
-            .position(|(&x, &y)| x == y)
-            .unwrap_or(count);
-        x86.regs.esi += pos as u32;
-        x86.regs.edi += pos as u32;
-        x86.regs.ecx -= pos as u32;
-        let x = x86.read_u8(x86.regs.esi);
-        let y = x86.read_u8(x86.regs.edi);
-        sub(x86, x, y);
+/// Run a comparing string op (cmps/scas) under its prefix, where repe/repne
+/// repeat while ZF is set/clear.  op performs one step and returns false if the
+/// access crashed.
+fn rep_compare(x86: &mut X86, instr: &Instruction, mut op: impl FnMut(&mut X86) -> bool) {
+    // Check repne first, as iced reports an 0xF3 prefix as both rep and repe.
+    let stop_on_zf = if instr.has_repne_prefix() {
+        true
+    } else if instr.has_repe_prefix() {
+        false
     } else {
-        return Err(StepError::Error("unimpl".into()));
+        op(x86);
+        return;
+    };
+    while x86.regs.ecx > 0 {
+        if !op(x86) {
+            break;
+        }
+        x86.regs.ecx -= 1;
+        if x86.regs.flags.contains(Flags::ZF) == stop_on_zf {
+            break;
+        }
     }
-    Ok(())
 }
 
-fn movs(x86: &mut X86, instr: &Instruction, size: usize) -> StepResult<()> {
-    let reverse = x86.regs.flags.contains(Flags::DF);
-    let step = if reverse {
-        -(size as isize) as usize
+pub fn cmps(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    let step = if x86.regs.flags.contains(Flags::DF) {
+        -1i32 as u32
     } else {
-        size
+        1
     };
-    let mut dst = x86.regs.edi as usize;
-    let mut src = x86.regs.esi as usize;
-
-    let mut movs_single = || {
-        x86.mem.copy_within(src..src + size, dst);
-        src = src.wrapping_add(step);
-        dst = dst.wrapping_add(step);
-    };
-    if instr.has_rep_prefix() {
-        let count = x86.regs.ecx;
-        for _ in 0..count {
-            movs_single();
+    rep_compare(x86, instr, |x86| {
+        let (src, dst) = (x86.regs.esi, x86.regs.edi);
+        if x86.check_oob_len(src, 1) || x86.check_oob_len(dst, 1) {
+            return false;
         }
-        x86.regs.ecx = 0;
-    } else if instr.has_repe_prefix() || instr.has_repne_prefix() {
-        return Err(StepError::Error("movs: unimplemented prefix".into()));
+        x86.watchpoints.check(src, 1, false);
+        x86.watchpoints.check(dst, 1, false);
+        let (x, y) = (x86.mem[src as usize], x86.mem[dst as usize]);
+        sub(x86, x, y);
+        x86.regs.esi = src.wrapping_add(step);
+        x86.regs.edi = dst.wrapping_add(step);
+        true
+    });
+    Ok(())
+}
+
+/// Compute the span of memory touched by a rep-prefixed string op that steps through
+/// count elements of size bytes, starting at addr.
+/// Returns None if the span wraps around the address space.
+fn rep_span(addr: u32, count: u32, size: u32, reverse: bool) -> Option<std::ops::Range<u32>> {
+    let len = count.checked_mul(size)?;
+    let start = if reverse {
+        addr.checked_add(size)?.checked_sub(len)?
     } else {
-        movs_single();
+        addr
     };
-    x86.regs.edi = dst as u32;
-    x86.regs.esi = src as u32;
+    Some(start..start.checked_add(len)?)
+}
+
+/// Copy a single element for movs.  Returns false if the access crashed.
+fn movs_single(x86: &mut X86, size: u32, step: u32) -> bool {
+    let src = x86.regs.esi;
+    let dst = x86.regs.edi;
+    if x86.check_oob_len(src, size) || x86.check_oob_len(dst, size) {
+        return false;
+    }
+    x86.watchpoints.check(src, size, false);
+    x86.watchpoints.check(dst, size, true);
+    x86.mem
+        .copy_within(src as usize..(src + size) as usize, dst as usize);
+    x86.regs.esi = src.wrapping_add(step);
+    x86.regs.edi = dst.wrapping_add(step);
+    true
+}
+
+fn movs(x86: &mut X86, instr: &Instruction, size: u32) -> StepResult<()> {
+    let reverse = x86.regs.flags.contains(Flags::DF);
+    let step = if reverse { size.wrapping_neg() } else { size };
+
+    // Note: iced reports an 0xF3 prefix as both rep and repe.
+    if instr.has_repne_prefix() {
+        return Err(StepError::Error("movs: unimplemented prefix".into()));
+    }
+    if !instr.has_rep_prefix() {
+        movs_single(x86, size, step);
+        return Ok(());
+    }
+
+    let count = x86.regs.ecx;
+    if count == 0 {
+        return Ok(());
+    }
+    let src = x86.regs.esi;
+    let dst = x86.regs.edi;
+    if let (Some(src_span), Some(dst_span)) = (
+        rep_span(src, count, size, reverse),
+        rep_span(dst, count, size, reverse),
+    ) {
+        // Element-by-element copying is equivalent to a memmove, unless the destination
+        // overlaps the part of the source that is yet to be read, in which case the
+        // copy smears the already-copied data forward (which some code relies on).
+        let overlap = src_span.start < dst_span.end && dst_span.start < src_span.end;
+        let hazard = overlap && if reverse { dst < src } else { dst > src };
+        if !hazard
+            && x86.in_bounds(src_span.start, src_span.end - src_span.start)
+            && x86.in_bounds(dst_span.start, dst_span.end - dst_span.start)
+        {
+            x86.watchpoints
+                .check(src_span.start, src_span.end - src_span.start, false);
+            x86.watchpoints
+                .check(dst_span.start, dst_span.end - dst_span.start, true);
+            x86.mem.copy_within(
+                src_span.start as usize..src_span.end as usize,
+                dst_span.start as usize,
+            );
+            x86.regs.esi = src.wrapping_add(step.wrapping_mul(count));
+            x86.regs.edi = dst.wrapping_add(step.wrapping_mul(count));
+            x86.regs.ecx = 0;
+            return Ok(());
+        }
+    }
+
+    // Slow path: step one element at a time, leaving the registers pointing at the
+    // faulting element if we crash partway through.
+    while x86.regs.ecx > 0 {
+        if !movs_single(x86, size, step) {
+            break;
+        }
+        x86.regs.ecx -= 1;
+    }
     Ok(())
 }
 
@@ -66,83 +145,95 @@ pub fn movsb(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
 }
 
 pub fn scas(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    assert!(x86.regs.flags.contains(Flags::DF)); // TODO
-    let src = x86.regs.edi as usize;
-    let value = x86.regs.eax as u8;
-    let count = x86.regs.ecx as usize;
-    if instr.has_repne_prefix() {
-        let pos = x86.mem[src..src + count]
-            .iter()
-            .position(|&c| c == value)
-            .unwrap_or(count);
-        x86.regs.edi += pos as u32;
-        x86.regs.ecx -= pos as u32;
-        sub(
-            x86,
-            x86.regs.get8(iced_x86::Register::AL),
-            x86.regs.get8(iced_x86::Register::DL),
-        );
+    let step = if x86.regs.flags.contains(Flags::DF) {
+        -1i32 as u32
     } else {
-        return Err(StepError::Error("unimpl".into()));
-    }
+        1
+    };
+    let value = x86.regs.eax as u8;
+    rep_compare(x86, instr, |x86| {
+        let dst = x86.regs.edi;
+        if x86.check_oob_len(dst, 1) {
+            return false;
+        }
+        x86.watchpoints.check(dst, 1, false);
+        let y = x86.mem[dst as usize];
+        sub(x86, value, y);
+        x86.regs.edi = dst.wrapping_add(step);
+        true
+    });
     Ok(())
 }
 
-pub fn stosd(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    let mut dst = x86.regs.edi as usize;
-    let value = x86.regs.eax;
-
-    if instr.has_rep_prefix() {
-        let count = x86.regs.ecx as usize;
-        let reverse = x86.regs.flags.contains(Flags::DF);
-        if reverse && count > 1 {
-            dst -= (count - 1) * 4;
+/// Store a single element for stos.  Returns false if the access crashed.
+fn stos_single(x86: &mut X86, value: &[u8], step: u32) -> bool {
+    let dst = x86.regs.edi;
+    if x86.check_oob_len(dst, value.len() as u32) {
+        return false;
+    }
+    x86.watchpoints.check(dst, value.len() as u32, true);
+    let dst = dst as usize;
+    x86.mem[dst..dst + value.len()].copy_from_slice(value);
+    x86.regs.edi = x86.regs.edi.wrapping_add(step);
+    true
+}
+
+fn stos(x86: &mut X86, instr: &Instruction, size: u32) -> StepResult<()> {
+    let reverse = x86.regs.flags.contains(Flags::DF);
+    let step = if reverse { size.wrapping_neg() } else { size };
+    let bytes = x86.regs.eax.to_le_bytes();
+    let value = &bytes[..size as usize];
+
+    if instr.has_repne_prefix() {
+        return Err(StepError::Error("stos: unimplemented prefix".into()));
+    }
+    if !instr.has_rep_prefix() {
+        stos_single(x86, value, step);
+        return Ok(());
+    }
+
+    let count = x86.regs.ecx;
+    if count == 0 {
+        return Ok(());
+    }
+    let dst = x86.regs.edi;
+    if let Some(span) = rep_span(dst, count, size, reverse) {
+        if x86.in_bounds(span.start, span.end - span.start) {
+            x86.watchpoints
+                .check(span.start, span.end - span.start, true);
+            let mem = &mut x86.mem[span.start as usize..span.end as usize];
+            if size == 1 {
+                mem.fill(value[0]);
+            } else {
+                for chunk in mem.chunks_exact_mut(size as usize) {
+                    chunk.copy_from_slice(value);
+                }
+            }
+            x86.regs.edi = dst.wrapping_add(step.wrapping_mul(count));
+            x86.regs.ecx = 0;
+            return Ok(());
         }
-        let mem: &mut [u32] = unsafe {
-            let mem = &mut x86.mem[dst..];
-            std::slice::from_raw_parts_mut(mem.as_ptr() as *mut u32, count)
-        };
-        mem.fill(value);
-        if reverse {
-            x86.regs.edi -= count as u32 * 4;
-        } else {
-            x86.regs.edi += count as u32 * 4;
+    }
+
+    // Slow path: step one element at a time so a crash happens at the right element.
+    while x86.regs.ecx > 0 {
+        if !stos_single(x86, value, step) {
+            break;
         }
-        x86.regs.ecx = 0;
-    } else if instr.has_repe_prefix() || instr.has_repne_prefix() {
-        return Err(StepError::Error("unimpl".into()));
-    } else {
-        *x86.mem.view_mut::<u32>(dst as u32) = value;
-        x86.regs.edi += 4;
+        x86.regs.ecx -= 1;
     }
-    // TODO: does this modify esi?  Sources disagree (!?)
     Ok(())
 }
 
-pub fn stosb(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
-    assert!(!x86.regs.flags.contains(Flags::DF)); // TODO
+pub fn stosd(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    stos(x86, instr, 4)
+}
 
-    let dst = x86.regs.edi as usize;
-    let value = x86.regs.eax as u8;
-    if instr.has_rep_prefix() {
-        let count = x86.regs.ecx as usize;
-        x86.mem[dst..dst + count].fill(value);
-        x86.regs.edi += count as u32;
-        x86.regs.ecx = 0;
-    } else if instr.has_repe_prefix() || instr.has_repne_prefix() {
-        return Err(StepError::Error("unimpl".into()));
-    } else {
-        x86.mem[dst] = value;
-        x86.regs.edi += 1;
-    }
-    Ok(())
+pub fn stosb(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
+    stos(x86, instr, 1)
 }
 
 pub fn lods(x86: &mut X86, instr: &Instruction, size: usize) -> StepResult<()> {
-    if x86.regs.flags.contains(Flags::DF) {
-        return Err(StepError::Error("TODO DF".into()));
-    }
-
     assert!(!instr.has_rep_prefix() && !instr.has_repe_prefix() && !instr.has_repne_prefix());
     match size {
         4 => {
@@ -154,7 +245,12 @@ pub fn lods(x86: &mut X86, instr: &Instruction, size: usize) -> StepResult<()> {
         }
         _ => unimplemented!("lods size {}", size),
     }
-    x86.regs.esi += size as u32;
+    let step = if x86.regs.flags.contains(Flags::DF) {
+        (size as u32).wrapping_neg()
+    } else {
+        size as u32
+    };
+    x86.regs.esi = x86.regs.esi.wrapping_add(step);
     Ok(())
 }
 
@@ -46,14 +46,21 @@ pub unsafe fn init_op_tab() {
     OP_TAB[iced_x86::Code::Jl_rel8_32 as usize] = Some(ops::jl);
     OP_TAB[iced_x86::Code::Js_rel32_32 as usize] = Some(ops::js);
     OP_TAB[iced_x86::Code::Js_rel8_32 as usize] = Some(ops::js);
+    OP_TAB[iced_x86::Code::Jp_rel32_32 as usize] = Some(ops::jp);
+    OP_TAB[iced_x86::Code::Jp_rel8_32 as usize] = Some(ops::jp);
+    OP_TAB[iced_x86::Code::Jnp_rel32_32 as usize] = Some(ops::jnp);
+    OP_TAB[iced_x86::Code::Jnp_rel8_32 as usize] = Some(ops::jnp);
 
     OP_TAB[iced_x86::Code::Loop_rel8_32_ECX as usize] = Some(ops::loop_);
 
     OP_TAB[iced_x86::Code::Pushd_imm8 as usize] = Some(ops::pushd_imm8);
     OP_TAB[iced_x86::Code::Pushd_imm32 as usize] = Some(ops::pushd_imm32);
+    OP_TAB[iced_x86::Code::Pushw_imm8 as usize] = Some(ops::pushw_imm8);
+    OP_TAB[iced_x86::Code::Push_imm16 as usize] = Some(ops::push_imm16);
     OP_TAB[iced_x86::Code::Push_r32 as usize] = Some(ops::push_r32);
     OP_TAB[iced_x86::Code::Push_rm32 as usize] = Some(ops::push_rm32);
     OP_TAB[iced_x86::Code::Push_rm16 as usize] = Some(ops::push_rm16);
+    OP_TAB[iced_x86::Code::Push_r16 as usize] = Some(ops::push_rm16);
 
     OP_TAB[iced_x86::Code::Pop_r32 as usize] = Some(ops::pop_rm32);
     OP_TAB[iced_x86::Code::Pop_rm32 as usize] = Some(ops::pop_rm32);
@@ -62,8 +69,14 @@ pub unsafe fn init_op_tab() {
 
     OP_TAB[iced_x86::Code::Mov_rm32_imm32 as usize] = Some(ops::mov_rm32_imm32);
     OP_TAB[iced_x86::Code::Mov_r32_imm32 as usize] = Some(ops::mov_r32_imm32);
+    OP_TAB[iced_x86::Code::Mov_rm16_imm16 as usize] = Some(ops::mov_rm16_imm16);
+    OP_TAB[iced_x86::Code::Mov_r16_imm16 as usize] = Some(ops::mov_rm16_imm16);
     OP_TAB[iced_x86::Code::Mov_moffs32_EAX as usize] = Some(ops::mov_moffs32_eax);
     OP_TAB[iced_x86::Code::Mov_EAX_moffs32 as usize] = Some(ops::mov_eax_moffs32);
+    OP_TAB[iced_x86::Code::Mov_moffs16_AX as usize] = Some(ops::mov_moffs16_ax);
+    OP_TAB[iced_x86::Code::Mov_AX_moffs16 as usize] = Some(ops::mov_ax_moffs16);
+    OP_TAB[iced_x86::Code::Mov_moffs8_AL as usize] = Some(ops::mov_moffs8_al);
+    OP_TAB[iced_x86::Code::Mov_AL_moffs8 as usize] = Some(ops::mov_al_moffs8);
     OP_TAB[iced_x86::Code::Mov_rm32_r32 as usize] = Some(ops::mov_rm32_r32);
     OP_TAB[iced_x86::Code::Mov_r32_rm32 as usize] = Some(ops::mov_r32_rm32);
     OP_TAB[iced_x86::Code::Mov_r16_rm16 as usize] = Some(ops::mov_r16_rm16);
@@ -76,15 +89,26 @@ pub unsafe fn init_op_tab() {
     OP_TAB[iced_x86::Code::Movsx_r32_rm16 as usize] = Some(ops::movsx_r32_rm16);
     OP_TAB[iced_x86::Code::Movsx_r32_rm8 as usize] = Some(ops::movsx_r32_rm8);
     OP_TAB[iced_x86::Code::Movsx_r16_rm8 as usize] = Some(ops::movsx_r16_rm8);
+    OP_TAB[iced_x86::Code::Movsx_r16_rm16 as usize] = Some(ops::mov_r16_rm16);
 
     OP_TAB[iced_x86::Code::Movzx_r32_rm16 as usize] = Some(ops::movzx_r32_rm16);
     OP_TAB[iced_x86::Code::Movzx_r32_rm8 as usize] = Some(ops::movzx_r32_rm8);
     OP_TAB[iced_x86::Code::Movzx_r16_rm8 as usize] = Some(ops::movzx_r16_rm8);
+    OP_TAB[iced_x86::Code::Movzx_r16_rm16 as usize] = Some(ops::mov_r16_rm16);
 
     OP_TAB[iced_x86::Code::Xchg_rm32_r32 as usize] = Some(ops::xchg_rm32_r32);
     OP_TAB[iced_x86::Code::Xchg_r32_EAX as usize] = Some(ops::xchg_rm32_r32);
+    OP_TAB[iced_x86::Code::Xchg_rm16_r16 as usize] = Some(ops::xchg_rm16_r16);
+    OP_TAB[iced_x86::Code::Xchg_r16_AX as usize] = Some(ops::xchg_rm16_r16);
+    OP_TAB[iced_x86::Code::Xchg_rm8_r8 as usize] = Some(ops::xchg_rm8_r8);
 
     OP_TAB[iced_x86::Code::Cmpxchg_rm32_r32 as usize] = Some(ops::cmpxchg_rm32_r32);
+    OP_TAB[iced_x86::Code::Cmpxchg_rm16_r16 as usize] = Some(ops::cmpxchg_rm16_r16);
+    OP_TAB[iced_x86::Code::Cmpxchg_rm8_r8 as usize] = Some(ops::cmpxchg_rm8_r8);
+    OP_TAB[iced_x86::Code::Cmpxchg8b_m64 as usize] = Some(ops::cmpxchg8b_m64);
+    OP_TAB[iced_x86::Code::Xadd_rm32_r32 as usize] = Some(ops::xadd_rm32_r32);
+    OP_TAB[iced_x86::Code::Xadd_rm16_r16 as usize] = Some(ops::xadd_rm16_r16);
+    OP_TAB[iced_x86::Code::Xadd_rm8_r8 as usize] = Some(ops::xadd_rm8_r8);
 
     OP_TAB[iced_x86::Code::Cmpsb_m8_m8 as usize] = Some(ops::cmps);
     OP_TAB[iced_x86::Code::Movsd_m32_m32 as usize] = Some(ops::movsd);
@@ -111,7 +135,14 @@ pub unsafe fn init_op_tab() {
 
     OP_TAB[iced_x86::Code::Or_rm32_imm8 as usize] = Some(ops::or_rm32_imm8);
     OP_TAB[iced_x86::Code::Or_rm16_imm16 as usize] = Some(ops::or_rm16_imm16);
+    OP_TAB[iced_x86::Code::Or_rm16_r16 as usize] = Some(ops::or_rm16_r16);
+    OP_TAB[iced_x86::Code::Or_r16_rm16 as usize] = Some(ops::or_rm16_r16);
+    OP_TAB[iced_x86::Code::Or_AX_imm16 as usize] = Some(ops::or_rm16_imm16);
+    OP_TAB[iced_x86::Code::Or_rm16_imm8 as usize] = Some(ops::or_rm16_imm8);
     OP_TAB[iced_x86::Code::Or_rm8_imm8 as usize] = Some(ops::or_rm8_imm8);
+    OP_TAB[iced_x86::Code::Or_AL_imm8 as usize] = Some(ops::or_rm8_imm8);
+    OP_TAB[iced_x86::Code::Or_rm8_r8 as usize] = Some(ops::or_rm8_r8);
+    OP_TAB[iced_x86::Code::Or_r8_rm8 as usize] = Some(ops::or_rm8_r8);
     OP_TAB[iced_x86::Code::Shl_rm32_imm8 as usize] = Some(ops::shl_rm32_imm8);
     OP_TAB[iced_x86::Code::Shl_rm32_1 as usize] = Some(ops::shl_rm32_imm8);
     OP_TAB[iced_x86::Code::Shl_rm32_CL as usize] = Some(ops::shl_rm32_cl);
@@ -151,6 +182,20 @@ pub unsafe fn init_op_tab() {
     OP_TAB[iced_x86::Code::Sub_r8_rm8 as usize] = Some(ops::sub_r8_rm8);
     OP_TAB[iced_x86::Code::Sub_rm8_imm8 as usize] = Some(ops::sub_rm8_imm8);
     OP_TAB[iced_x86::Code::Sub_AL_imm8 as usize] = Some(ops::sub_rm8_imm8);
+    OP_TAB[iced_x86::Code::Adc_r32_rm32 as usize] = Some(ops::adc_r32_rm32);
+    OP_TAB[iced_x86::Code::Adc_rm32_r32 as usize] = Some(ops::adc_rm32_r32);
+    OP_TAB[iced_x86::Code::Adc_EAX_imm32 as usize] = Some(ops::adc_rm32_imm32);
+    OP_TAB[iced_x86::Code::Adc_rm32_imm32 as usize] = Some(ops::adc_rm32_imm32);
+    OP_TAB[iced_x86::Code::Adc_rm32_imm8 as usize] = Some(ops::adc_rm32_imm8);
+    OP_TAB[iced_x86::Code::Adc_r16_rm16 as usize] = Some(ops::adc_r16_rm16);
+    OP_TAB[iced_x86::Code::Adc_rm16_r16 as usize] = Some(ops::adc_rm16_r16);
+    OP_TAB[iced_x86::Code::Adc_AX_imm16 as usize] = Some(ops::adc_rm16_imm16);
+    OP_TAB[iced_x86::Code::Adc_rm16_imm16 as usize] = Some(ops::adc_rm16_imm16);
+    OP_TAB[iced_x86::Code::Adc_rm16_imm8 as usize] = Some(ops::adc_rm16_imm8);
+    OP_TAB[iced_x86::Code::Adc_r8_rm8 as usize] = Some(ops::adc_r8_rm8);
+    OP_TAB[iced_x86::Code::Adc_rm8_r8 as usize] = Some(ops::adc_rm8_r8);
+    OP_TAB[iced_x86::Code::Adc_AL_imm8 as usize] = Some(ops::adc_rm8_imm8);
+    OP_TAB[iced_x86::Code::Adc_rm8_imm8 as usize] = Some(ops::adc_rm8_imm8);
     OP_TAB[iced_x86::Code::Sbb_r32_rm32 as usize] = Some(ops::sbb_r32_rm32);
     OP_TAB[iced_x86::Code::Sbb_rm32_r32 as usize] = Some(ops::sbb_rm32_r32);
     OP_TAB[iced_x86::Code::Sbb_r8_rm8 as usize] = Some(ops::sbb_r8_rm8);
@@ -159,17 +204,35 @@ pub unsafe fn init_op_tab() {
     OP_TAB[iced_x86::Code::Imul_r32_rm32_imm8 as usize] = Some(ops::imul_r32_rm32_imm8);
     OP_TAB[iced_x86::Code::Idiv_rm32 as usize] = Some(ops::idiv_rm32);
     OP_TAB[iced_x86::Code::Div_rm32 as usize] = Some(ops::div_rm32);
+    OP_TAB[iced_x86::Code::Imul_rm32 as usize] = Some(ops::imul_rm32);
+    OP_TAB[iced_x86::Code::Imul_rm16 as usize] = Some(ops::imul_rm16);
+    OP_TAB[iced_x86::Code::Imul_rm8 as usize] = Some(ops::imul_rm8);
+    OP_TAB[iced_x86::Code::Idiv_rm16 as usize] = Some(ops::idiv_rm16);
+    OP_TAB[iced_x86::Code::Idiv_rm8 as usize] = Some(ops::idiv_rm8);
+    OP_TAB[iced_x86::Code::Mul_rm32 as usize] = Some(ops::mul_rm32);
+    OP_TAB[iced_x86::Code::Mul_rm16 as usize] = Some(ops::mul_rm16);
+    OP_TAB[iced_x86::Code::Mul_rm8 as usize] = Some(ops::mul_rm8);
+    OP_TAB[iced_x86::Code::Div_rm16 as usize] = Some(ops::div_rm16);
+    OP_TAB[iced_x86::Code::Div_rm8 as usize] = Some(ops::div_rm8);
     OP_TAB[iced_x86::Code::Dec_r32 as usize] = Some(ops::dec_rm32);
     OP_TAB[iced_x86::Code::Dec_rm32 as usize] = Some(ops::dec_rm32);
+    OP_TAB[iced_x86::Code::Dec_r16 as usize] = Some(ops::dec_rm16);
+    OP_TAB[iced_x86::Code::Dec_rm16 as usize] = Some(ops::dec_rm16);
     OP_TAB[iced_x86::Code::Dec_rm8 as usize] = Some(ops::dec_rm8);
     OP_TAB[iced_x86::Code::Inc_r32 as usize] = Some(ops::inc_rm32);
     OP_TAB[iced_x86::Code::Inc_rm32 as usize] = Some(ops::inc_rm32);
+    OP_TAB[iced_x86::Code::Inc_r16 as usize] = Some(ops::inc_rm16);
+    OP_TAB[iced_x86::Code::Inc_rm16 as usize] = Some(ops::inc_rm16);
     OP_TAB[iced_x86::Code::Inc_rm8 as usize] = Some(ops::inc_rm8);
     OP_TAB[iced_x86::Code::Neg_rm32 as usize] = Some(ops::neg_rm32);
+    OP_TAB[iced_x86::Code::Neg_rm16 as usize] = Some(ops::neg_rm16);
     OP_TAB[iced_x86::Code::Neg_rm8 as usize] = Some(ops::neg_rm8);
     OP_TAB[iced_x86::Code::Not_rm32 as usize] = Some(ops::not_rm32);
+    OP_TAB[iced_x86::Code::Not_rm16 as usize] = Some(ops::not_rm16);
+    OP_TAB[iced_x86::Code::Not_rm8 as usize] = Some(ops::not_rm8);
 
     OP_TAB[iced_x86::Code::Lea_r32_m as usize] = Some(ops::lea_r32_m);
+    OP_TAB[iced_x86::Code::Lea_r16_m as usize] = Some(ops::lea_r16_m);
 
     OP_TAB[iced_x86::Code::Cmp_rm32_r32 as usize] = Some(ops::cmp_rm32_r32);
     OP_TAB[iced_x86::Code::Cmp_r32_rm32 as usize] = Some(ops::cmp_r32_rm32);
@@ -204,36 +267,108 @@ pub unsafe fn init_op_tab() {
 
     OP_TAB[iced_x86::Code::Fld1 as usize] = Some(ops::fld1);
     OP_TAB[iced_x86::Code::Fldz as usize] = Some(ops::fldz);
+    OP_TAB[iced_x86::Code::Fldpi as usize] = Some(ops::fldpi);
+    OP_TAB[iced_x86::Code::Fldl2e as usize] = Some(ops::fldl2e);
+    OP_TAB[iced_x86::Code::Fldl2t as usize] = Some(ops::fldl2t);
+    OP_TAB[iced_x86::Code::Fldlg2 as usize] = Some(ops::fldlg2);
+    OP_TAB[iced_x86::Code::Fldln2 as usize] = Some(ops::fldln2);
     OP_TAB[iced_x86::Code::Fld_m64fp as usize] = Some(ops::fld_m64fp);
     OP_TAB[iced_x86::Code::Fld_m32fp as usize] = Some(ops::fld_m32fp);
     OP_TAB[iced_x86::Code::Fild_m32int as usize] = Some(ops::fild_m32int);
     OP_TAB[iced_x86::Code::Fild_m16int as usize] = Some(ops::fild_m16int);
+    OP_TAB[iced_x86::Code::Fild_m64int as usize] = Some(ops::fild_m64int);
     OP_TAB[iced_x86::Code::Fst_m64fp as usize] = Some(ops::fst_m64fp);
     OP_TAB[iced_x86::Code::Fstp_m64fp as usize] = Some(ops::fstp_m64fp);
     OP_TAB[iced_x86::Code::Fstp_m32fp as usize] = Some(ops::fstp_m32fp);
     OP_TAB[iced_x86::Code::Fistp_m64int as usize] = Some(ops::fistp_m64int);
     OP_TAB[iced_x86::Code::Fistp_m32int as usize] = Some(ops::fistp_m32int);
+    OP_TAB[iced_x86::Code::Fistp_m16int as usize] = Some(ops::fistp_m16int);
+    OP_TAB[iced_x86::Code::Fist_m32int as usize] = Some(ops::fist_m32int);
+    OP_TAB[iced_x86::Code::Fist_m16int as usize] = Some(ops::fist_m16int);
+    OP_TAB[iced_x86::Code::Fisttp_m64int as usize] = Some(ops::fisttp_m64int);
+    OP_TAB[iced_x86::Code::Fisttp_m32int as usize] = Some(ops::fisttp_m32int);
+    OP_TAB[iced_x86::Code::Fisttp_m16int as usize] = Some(ops::fisttp_m16int);
+    OP_TAB[iced_x86::Code::Fbld_m80bcd as usize] = Some(ops::fbld_m80bcd);
+    OP_TAB[iced_x86::Code::Fbstp_m80bcd as usize] = Some(ops::fbstp_m80bcd);
     OP_TAB[iced_x86::Code::Fchs as usize] = Some(ops::fchs);
     OP_TAB[iced_x86::Code::Fcos as usize] = Some(ops::fcos);
     OP_TAB[iced_x86::Code::Fsin as usize] = Some(ops::fsin);
     OP_TAB[iced_x86::Code::Fpatan as usize] = Some(ops::fpatan);
     OP_TAB[iced_x86::Code::Fsqrt as usize] = Some(ops::fsqrt);
-    OP_TAB[iced_x86::Code::Fadd_m64fp as usize] = Some(ops::fadd_m64fp);
-    OP_TAB[iced_x86::Code::Fadd_m32fp as usize] = Some(ops::fadd_m32fp);
-    OP_TAB[iced_x86::Code::Faddp_sti_st0 as usize] = Some(ops::faddp_sti_st0);
-    OP_TAB[iced_x86::Code::Fsub_m32fp as usize] = Some(ops::fsub_m32fp);
-    OP_TAB[iced_x86::Code::Fsubr_m64fp as usize] = Some(ops::fsubr_m64fp);
-    OP_TAB[iced_x86::Code::Fsubr_m32fp as usize] = Some(ops::fsubr_m32fp);
-    OP_TAB[iced_x86::Code::Fmul_m64fp as usize] = Some(ops::fmul_m64fp);
-    OP_TAB[iced_x86::Code::Fmul_m32fp as usize] = Some(ops::fmul_m32fp);
-    OP_TAB[iced_x86::Code::Fmul_st0_sti as usize] = Some(ops::fmul_sti_sti);
-    OP_TAB[iced_x86::Code::Fmul_sti_st0 as usize] = Some(ops::fmul_sti_sti);
-    OP_TAB[iced_x86::Code::Fmulp_sti_st0 as usize] = Some(ops::fmulp_sti_st0);
-    OP_TAB[iced_x86::Code::Fdivrp_sti_st0 as usize] = Some(ops::fdivrp_sti_st0);
-    OP_TAB[iced_x86::Code::Fdiv_m64fp as usize] = Some(ops::fdiv_m64fp);
+    OP_TAB[iced_x86::Code::Fadd_m32fp as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Fadd_m64fp as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Fadd_st0_sti as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Fadd_sti_st0 as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Fiadd_m32int as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Fiadd_m16int as usize] = Some(ops::fadd);
+    OP_TAB[iced_x86::Code::Faddp_sti_st0 as usize] = Some(ops::faddp);
+    OP_TAB[iced_x86::Code::Fsub_m32fp as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fsub_m64fp as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fsub_st0_sti as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fsub_sti_st0 as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fisub_m32int as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fisub_m16int as usize] = Some(ops::fsub);
+    OP_TAB[iced_x86::Code::Fsubp_sti_st0 as usize] = Some(ops::fsubp);
+    OP_TAB[iced_x86::Code::Fsubr_m32fp as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fsubr_m64fp as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fsubr_st0_sti as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fsubr_sti_st0 as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fisubr_m32int as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fisubr_m16int as usize] = Some(ops::fsubr);
+    OP_TAB[iced_x86::Code::Fsubrp_sti_st0 as usize] = Some(ops::fsubrp);
+    OP_TAB[iced_x86::Code::Fmul_m32fp as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fmul_m64fp as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fmul_st0_sti as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fmul_sti_st0 as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fimul_m32int as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fimul_m16int as usize] = Some(ops::fmul);
+    OP_TAB[iced_x86::Code::Fmulp_sti_st0 as usize] = Some(ops::fmulp);
+    OP_TAB[iced_x86::Code::Fdiv_m32fp as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fdiv_m64fp as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fdiv_st0_sti as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fdiv_sti_st0 as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fidiv_m32int as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fidiv_m16int as usize] = Some(ops::fdiv);
+    OP_TAB[iced_x86::Code::Fdivp_sti_st0 as usize] = Some(ops::fdivp);
+    OP_TAB[iced_x86::Code::Fdivr_m32fp as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fdivr_m64fp as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fdivr_st0_sti as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fdivr_sti_st0 as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fidivr_m32int as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fidivr_m16int as usize] = Some(ops::fdivr);
+    OP_TAB[iced_x86::Code::Fdivrp_sti_st0 as usize] = Some(ops::fdivrp);
+    OP_TAB[iced_x86::Code::Fabs as usize] = Some(ops::fabs);
+    OP_TAB[iced_x86::Code::Frndint as usize] = Some(ops::frndint);
+    OP_TAB[iced_x86::Code::Fscale as usize] = Some(ops::fscale);
+    OP_TAB[iced_x86::Code::Fprem as usize] = Some(ops::fprem);
+    OP_TAB[iced_x86::Code::Fprem1 as usize] = Some(ops::fprem1);
+    OP_TAB[iced_x86::Code::Fptan as usize] = Some(ops::fptan);
+    OP_TAB[iced_x86::Code::Fsincos as usize] = Some(ops::fsincos);
+    OP_TAB[iced_x86::Code::F2xm1 as usize] = Some(ops::f2xm1);
+    OP_TAB[iced_x86::Code::Fyl2x as usize] = Some(ops::fyl2x);
+    OP_TAB[iced_x86::Code::Fyl2xp1 as usize] = Some(ops::fyl2xp1);
+    OP_TAB[iced_x86::Code::Fxtract as usize] = Some(ops::fxtract);
     OP_TAB[iced_x86::Code::Fxch_st0_sti as usize] = Some(ops::fxch_st0_sti);
-    OP_TAB[iced_x86::Code::Fcomp_m32fp as usize] = Some(ops::fcomp_m32fp);
-    OP_TAB[iced_x86::Code::Fcomp_m64fp as usize] = Some(ops::fcomp_m64fp);
+    OP_TAB[iced_x86::Code::Fcom_m32fp as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Fcom_m64fp as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Fcom_st0_sti as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Ficom_m32int as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Ficom_m16int as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Fucom_st0_sti as usize] = Some(ops::fcom);
+    OP_TAB[iced_x86::Code::Fcomp_m32fp as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Fcomp_m64fp as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Fcomp_st0_sti as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Ficomp_m32int as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Ficomp_m16int as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Fucomp_st0_sti as usize] = Some(ops::fcomp);
+    OP_TAB[iced_x86::Code::Fcompp as usize] = Some(ops::fcompp);
+    OP_TAB[iced_x86::Code::Fucompp as usize] = Some(ops::fcompp);
+    OP_TAB[iced_x86::Code::Fcomi_st0_sti as usize] = Some(ops::fcomi);
+    OP_TAB[iced_x86::Code::Fucomi_st0_sti as usize] = Some(ops::fcomi);
+    OP_TAB[iced_x86::Code::Fcomip_st0_sti as usize] = Some(ops::fcomip);
+    OP_TAB[iced_x86::Code::Fucomip_st0_sti as usize] = Some(ops::fcomip);
+    OP_TAB[iced_x86::Code::Ftst as usize] = Some(ops::ftst);
+    OP_TAB[iced_x86::Code::Fxam as usize] = Some(ops::fxam);
     OP_TAB[iced_x86::Code::Fnstsw_AX as usize] = Some(ops::fnstsw_ax);
     OP_TAB[iced_x86::Code::Fnstcw_m2byte as usize] = Some(ops::fnstcw_m2byte);
     OP_TAB[iced_x86::Code::Fldcw_m2byte as usize] = Some(ops::fldcw_m2byte);
@@ -267,6 +402,9 @@ pub unsafe fn init_op_tab() {
     OP_TAB[iced_x86::Code::Paddusb_mm_mmm64 as usize] = Some(ops::paddusb_mm_mmm64);
 
     OP_TAB[iced_x86::Code::Nopd as usize] = Some(ops::nop);
+    OP_TAB[iced_x86::Code::Nopw as usize] = Some(ops::nop);
+    OP_TAB[iced_x86::Code::Nop_rm32 as usize] = Some(ops::nop);
+    OP_TAB[iced_x86::Code::Nop_rm16 as usize] = Some(ops::nop);
 
     OP_TAB[iced_x86::Code::Int3 as usize] = Some(ops::int3);
 
@@ -275,6 +413,19 @@ pub unsafe fn init_op_tab() {
     // log::info!("highest op at {}", last.unwrap());
 }
 
+/// Whether there is an implementation for the given instruction code.
+#[cfg(test)]
+pub(crate) fn has_op(code: iced_x86::Code) -> bool {
+    let tab = unsafe { OP_TAB };
+    tab.get(code as usize).is_some_and(Option::is_some)
+}
+
+/// Executes a single instruction.
+///
+/// A lock prefix needs no handling here: instructions run one at a time to completion,
+/// so each read-modify-write is already atomic with respect to all guest threads.
+/// iced decodes lock on an instruction that doesn't permit it as Code::INVALID, which
+/// then fails dispatch just like the #UD real hardware would raise.
 pub fn execute(x86: &mut X86, instr: &Instruction) -> StepResult<()> {
     match unsafe { OP_TAB[instr.code() as usize] } {
         Some(f) => f(x86, instr),
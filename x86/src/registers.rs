@@ -6,6 +6,10 @@ bitflags! {
     pub struct Flags: u32 {
         /// carry
         const CF = 1 << 0;
+        /// parity
+        const PF = 1 << 2;
+        /// auxiliary carry
+        const AF = 1 << 4;
         /// zero
         const ZF = 1 << 6;
         /// sign
@@ -27,8 +31,16 @@ bitflags! {
     }
 }
 
+/// Windows' initial FPU control word: all exceptions masked, 53-bit precision,
+/// round to nearest.
+pub const FPU_CONTROL_DEFAULT: u16 = 0x027F;
+
+fn fpu_control_default() -> u16 {
+    FPU_CONTROL_DEFAULT
+}
+
 #[repr(C)]
-#[derive(serde::Serialize, serde::Deserialize, Tsify)]
+#[derive(Clone, serde::Serialize, serde::Deserialize, Tsify)]
 pub struct Registers {
     // Warning: get32 assumes the registers start with eax and are in a particular order.
     pub eax: u32,
@@ -63,12 +75,22 @@ pub struct Registers {
     pub st_top: usize,
     /// FPU status word (TODO fold st_top in here?)
     pub fpu_status: FPUStatus,
+    /// FPU control word, as loaded by fldcw.  Only the rounding control field is
+    /// modeled.
+    #[serde(default = "fpu_control_default")]
+    pub fpu_control: u16,
 
     /// MMX registers.
     // TODO: officially these should alias the FPU registers(!).
     pub mm: [u64; 8],
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Self {
         Registers {
@@ -93,6 +115,7 @@ impl Registers {
             st: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
             st_top: 8,
             fpu_status: FPUStatus::empty(),
+            fpu_control: FPU_CONTROL_DEFAULT,
 
             mm: [0, 0, 0, 0, 0, 0, 0, 0],
         }
@@ -163,6 +186,8 @@ impl Registers {
             iced_x86::Register::CX => self.ecx = (self.ecx & 0xFFFF_0000) | value as u32,
             iced_x86::Register::DX => self.edx = (self.edx & 0xFFFF_0000) | value as u32,
             iced_x86::Register::BX => self.ebx = (self.ebx & 0xFFFF_0000) | value as u32,
+            iced_x86::Register::SP => self.esp = (self.esp & 0xFFFF_0000) | value as u32,
+            iced_x86::Register::BP => self.ebp = (self.ebp & 0xFFFF_0000) | value as u32,
             iced_x86::Register::SI => self.esi = (self.esi & 0xFFFF_0000) | value as u32,
             iced_x86::Register::DI => self.edi = (self.edi & 0xFFFF_0000) | value as u32,
             iced_x86::Register::ES => self.es = value,
@@ -200,6 +225,12 @@ impl Registers {
             + match reg {
                 iced_x86::Register::ST0 => 0,
                 iced_x86::Register::ST1 => 1,
+                iced_x86::Register::ST2 => 2,
+                iced_x86::Register::ST3 => 3,
+                iced_x86::Register::ST4 => 4,
+                iced_x86::Register::ST5 => 5,
+                iced_x86::Register::ST6 => 6,
+                iced_x86::Register::ST7 => 7,
                 _ => unreachable!("{reg:?}"),
             }
     }
@@ -0,0 +1,617 @@
+//! Property tests of the ALU ops against a reference model.
+//!
+//! Every arithmetic, logic and shift instruction that has an op is run on random
+//! operands, and its result and flags are compared against a model written from the
+//! pseudocode in the Intel SDM.  Flags the manual leaves undefined for an input are not
+//! compared.  Operands come from a fixed-seed generator, so failures reproduce.
+
+use crate::{registers::Flags, StepError, X86};
+use iced_x86::{Code, Instruction, Mnemonic, OpCodeOperandKind, Register};
+
+/// Random cases run per instruction code.
+const CASES: usize = 2000;
+
+/// The flags the ALU ops compute.
+const ALU_FLAGS: [Flags; 6] = [
+    Flags::CF,
+    Flags::PF,
+    Flags::AF,
+    Flags::ZF,
+    Flags::SF,
+    Flags::OF,
+];
+
+/// xorshift64*, which is plenty for picking operands.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A 32-bit register value whose low `bits` are often a boundary case.
+    fn value(&mut self, bits: u32) -> u32 {
+        let m = mask(bits) as u32;
+        let sign = 1 << (bits - 1);
+        let low = match self.next() % 4 {
+            0 => [0, 1, 0xf, 0x10, sign - 1, sign, sign + 1, m][self.next() as usize % 8],
+            _ => self.next() as u32,
+        };
+        (self.next() as u32 & !m) | (low & m)
+    }
+
+    /// A shift count, mostly within the range that matters after masking.
+    fn count(&mut self) -> u8 {
+        match self.next() % 4 {
+            0 => self.next() as u8,
+            _ => (self.next() % 40) as u8,
+        }
+    }
+
+    fn flags(&mut self) -> Flags {
+        let bits = self.next() as u32;
+        ALU_FLAGS
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| bits & (1 << i) != 0)
+            .fold(Flags::empty(), |acc, (_, &f)| acc | f)
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    u64::MAX >> (64 - bits)
+}
+
+fn msb(x: u64, bits: u32) -> bool {
+    (x >> (bits - 1)) & 1 != 0
+}
+
+/// Interpret the low `bits` of x as a signed value.
+fn signed(x: u64, bits: u32) -> i64 {
+    ((x << (64 - bits)) as i64) >> (64 - bits)
+}
+
+fn fits_signed(x: i64, bits: u32) -> bool {
+    let max = (1i64 << (bits - 1)) - 1;
+    x >= -max - 1 && x <= max
+}
+
+/// What an instruction should do, per the SDM.
+#[derive(Default)]
+struct Expected {
+    /// New value of the destination register.
+    dst: Option<u64>,
+    /// New value of the high half (AH, DX or EDX) for one-operand mul and div.
+    high: Option<u64>,
+    /// Flags with a defined value afterwards, including ones that must be unchanged.
+    flags: Vec<(Flags, bool)>,
+    divide_error: bool,
+}
+
+/// The ZF, SF and PF values derived from a result.
+fn result_flags(r: u64, bits: u32) -> [(Flags, bool); 3] {
+    [
+        (Flags::ZF, r == 0),
+        (Flags::SF, msb(r, bits)),
+        (Flags::PF, (r as u8).count_ones() % 2 == 0),
+    ]
+}
+
+/// The given flags, unchanged from their value before the instruction.
+fn unchanged(before: Flags, flags: &[Flags]) -> Vec<(Flags, bool)> {
+    flags.iter().map(|&f| (f, before.contains(f))).collect()
+}
+
+/// DEST ← x + y + carry.
+fn model_add(x: u64, y: u64, carry: bool, bits: u32) -> Expected {
+    let c = carry as u64;
+    let sum = x + y + c;
+    let r = sum & mask(bits);
+    let mut flags = vec![
+        (Flags::CF, sum > mask(bits)),
+        (
+            Flags::OF,
+            !fits_signed(signed(x, bits) + signed(y, bits) + c as i64, bits),
+        ),
+        (Flags::AF, (x & 0xf) + (y & 0xf) + c > 0xf),
+    ];
+    flags.extend(result_flags(r, bits));
+    Expected {
+        dst: Some(r),
+        flags,
+        ..Default::default()
+    }
+}
+
+/// DEST ← x - (y + borrow).
+fn model_sub(x: u64, y: u64, borrow: bool, bits: u32) -> Expected {
+    let b = borrow as u64;
+    let r = x.wrapping_sub(y).wrapping_sub(b) & mask(bits);
+    let mut flags = vec![
+        (Flags::CF, x < y + b),
+        (
+            Flags::OF,
+            !fits_signed(signed(x, bits) - signed(y, bits) - b as i64, bits),
+        ),
+        (Flags::AF, (x & 0xf) < (y & 0xf) + b),
+    ];
+    flags.extend(result_flags(r, bits));
+    Expected {
+        dst: Some(r),
+        flags,
+        ..Default::default()
+    }
+}
+
+/// AND/OR/XOR: OF and CF are cleared, AF is undefined.
+fn model_logic(r: u64, bits: u32) -> Expected {
+    let mut flags = vec![(Flags::CF, false), (Flags::OF, false)];
+    flags.extend(result_flags(r, bits));
+    Expected {
+        dst: Some(r),
+        flags,
+        ..Default::default()
+    }
+}
+
+/// SAL/SHL/SHR/SAR.
+fn model_shift(m: Mnemonic, x: u64, count: u64, before: Flags, bits: u32) -> Expected {
+    let count = count & 0x1f;
+    if count == 0 {
+        return Expected {
+            dst: Some(x),
+            flags: unchanged(before, &ALU_FLAGS),
+            ..Default::default()
+        };
+    }
+    let (r, cf) = match m {
+        Mnemonic::Shl | Mnemonic::Sal => (
+            (x << count) & mask(bits),
+            // CF is undefined for SHL and SHR when the count is at least the width.
+            (count < bits as u64).then(|| (x >> (bits as u64 - count)) & 1 != 0),
+        ),
+        Mnemonic::Shr => (
+            x >> count,
+            (count < bits as u64).then(|| (x >> (count - 1)) & 1 != 0),
+        ),
+        Mnemonic::Sar => (
+            (signed(x, bits) >> count) as u64 & mask(bits),
+            Some((signed(x, bits) >> (count - 1)) & 1 != 0),
+        ),
+        _ => unreachable!(),
+    };
+    let mut flags = result_flags(r, bits).to_vec();
+    if let Some(cf) = cf {
+        flags.push((Flags::CF, cf));
+    }
+    // OF is only defined for 1-bit shifts.
+    if count == 1 {
+        let of = match m {
+            Mnemonic::Shl | Mnemonic::Sal => msb(r, bits) != cf.unwrap(),
+            Mnemonic::Shr => msb(x, bits),
+            _ => false,
+        };
+        flags.push((Flags::OF, of));
+    }
+    Expected {
+        dst: Some(r),
+        flags,
+        ..Default::default()
+    }
+}
+
+/// ROL/ROR.
+fn model_rotate(m: Mnemonic, x: u64, count: u64, before: Flags, bits: u32) -> Expected {
+    let masked = count & 0x1f;
+    // SF, ZF, AF and PF are never affected by rotates.
+    let mut flags = unchanged(before, &[Flags::SF, Flags::ZF, Flags::AF, Flags::PF]);
+    if masked == 0 {
+        flags.extend(unchanged(before, &[Flags::CF, Flags::OF]));
+        return Expected {
+            dst: Some(x),
+            flags,
+            ..Default::default()
+        };
+    }
+    let n = masked % bits as u64;
+    let rotl = |x: u64, n: u64| ((x << n) | (x >> ((bits as u64 - n) % bits as u64))) & mask(bits);
+    let r = match m {
+        Mnemonic::Rol => rotl(x, n),
+        _ => rotl(x, (bits as u64 - n) % bits as u64),
+    };
+    let cf = match m {
+        Mnemonic::Rol => r & 1 != 0,
+        _ => msb(r, bits),
+    };
+    flags.push((Flags::CF, cf));
+    if masked == 1 {
+        let of = match m {
+            Mnemonic::Rol => msb(r, bits) != cf,
+            _ => msb(r, bits) != msb(r << 1, bits),
+        };
+        flags.push((Flags::OF, of));
+    }
+    Expected {
+        dst: Some(r),
+        flags,
+        ..Default::default()
+    }
+}
+
+/// Signed or unsigned multiply of x by y, with the full product split into halves.
+/// CF and OF are set when the high half is significant; the other flags are undefined.
+fn model_mul(signed_mul: bool, x: u64, y: u64, bits: u32) -> Expected {
+    let (low, high, overflow) = if signed_mul {
+        let p = signed(x, bits) * signed(y, bits);
+        let low = p as u64 & mask(bits);
+        (low, (p >> bits) as u64 & mask(bits), p != signed(low, bits))
+    } else {
+        let p = x * y;
+        (p & mask(bits), p >> bits, p >> bits != 0)
+    };
+    Expected {
+        dst: Some(low),
+        high: Some(high),
+        flags: vec![(Flags::CF, overflow), (Flags::OF, overflow)],
+        ..Default::default()
+    }
+}
+
+/// Divide high:low by y, raising #DE on a zero divisor or a quotient that doesn't fit.
+/// All flags are undefined.
+fn model_div(signed_div: bool, low: u64, high: u64, y: u64, bits: u32) -> Expected {
+    let dividend = (high << bits) | low;
+    let result = if signed_div {
+        signed(dividend, bits * 2)
+            .checked_div(signed(y, bits))
+            .filter(|&q| fits_signed(q, bits))
+            .map(|q| {
+                let r = signed(dividend, bits * 2) % signed(y, bits);
+                (q as u64 & mask(bits), r as u64 & mask(bits))
+            })
+    } else {
+        dividend
+            .checked_div(y)
+            .filter(|&q| q <= mask(bits))
+            .map(|q| (q, dividend % y))
+    };
+    match result {
+        Some((q, r)) => Expected {
+            dst: Some(q),
+            high: Some(r),
+            ..Default::default()
+        },
+        None => Expected {
+            divide_error: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// Inputs to the model for one case.
+struct Inputs {
+    mnemonic: Mnemonic,
+    bits: u32,
+    /// The destination (or, for mul/div, the accumulator) before the instruction.
+    x: u64,
+    /// The source operand or shift count.
+    y: u64,
+    /// The high half of the dividend for one-operand div.
+    high: u64,
+    /// Whether this is the one-operand form of imul.
+    one_operand: bool,
+    flags: Flags,
+}
+
+fn model(i: &Inputs) -> Expected {
+    let (x, y, bits) = (i.x, i.y, i.bits);
+    let cf = i.flags.contains(Flags::CF);
+    match i.mnemonic {
+        Mnemonic::Add => model_add(x, y, false, bits),
+        Mnemonic::Adc => model_add(x, y, cf, bits),
+        Mnemonic::Sub => model_sub(x, y, false, bits),
+        Mnemonic::Sbb => model_sub(x, y, cf, bits),
+        Mnemonic::Cmp => Expected {
+            dst: None,
+            ..model_sub(x, y, false, bits)
+        },
+        Mnemonic::And => model_logic(x & y, bits),
+        Mnemonic::Or => model_logic(x | y, bits),
+        Mnemonic::Xor => model_logic(x ^ y, bits),
+        Mnemonic::Test => Expected {
+            dst: None,
+            ..model_logic(x & y, bits)
+        },
+        Mnemonic::Inc | Mnemonic::Dec => {
+            let mut e = if i.mnemonic == Mnemonic::Inc {
+                model_add(x, 1, false, bits)
+            } else {
+                model_sub(x, 1, false, bits)
+            };
+            // CF is not affected.
+            e.flags.retain(|&(f, _)| f != Flags::CF);
+            e.flags.extend(unchanged(i.flags, &[Flags::CF]));
+            e
+        }
+        Mnemonic::Neg => model_sub(0, x, false, bits),
+        Mnemonic::Not => Expected {
+            dst: Some(!x & mask(bits)),
+            flags: unchanged(i.flags, &ALU_FLAGS),
+            ..Default::default()
+        },
+        Mnemonic::Shl | Mnemonic::Sal | Mnemonic::Shr | Mnemonic::Sar => {
+            model_shift(i.mnemonic, x, y, i.flags, bits)
+        }
+        Mnemonic::Rol | Mnemonic::Ror => model_rotate(i.mnemonic, x, y, i.flags, bits),
+        Mnemonic::Mul => model_mul(false, x, y, bits),
+        Mnemonic::Imul => {
+            let mut e = model_mul(true, x, y, bits);
+            if !i.one_operand {
+                e.high = None;
+            }
+            e
+        }
+        Mnemonic::Div => model_div(false, x, i.high, y, bits),
+        Mnemonic::Idiv => model_div(true, x, i.high, y, bits),
+        _ => unreachable!(),
+    }
+}
+
+/// The registers used at each width: destination, source, and the high half of
+/// one-operand mul/div.  The shift count register CL is not among them.
+fn registers(bits: u32) -> (Register, Register, Register) {
+    match bits {
+        8 => (Register::AL, Register::BL, Register::AH),
+        16 => (Register::AX, Register::BX, Register::DX),
+        32 => (Register::EAX, Register::EBX, Register::EDX),
+        _ => unreachable!(),
+    }
+}
+
+fn get(x86: &X86, reg: Register) -> u64 {
+    match reg.size() {
+        1 => x86.regs.get8(reg) as u64,
+        2 => x86.regs.get16(reg) as u64,
+        _ => x86.regs.get32(reg) as u64,
+    }
+}
+
+fn set(x86: &mut X86, reg: Register, value: u64) {
+    match reg.size() {
+        1 => x86.regs.set8(reg, value as u8),
+        2 => x86.regs.set16(reg, value as u16),
+        _ => x86.regs.set32(reg, value as u32),
+    }
+}
+
+/// An operand of an instruction under test.
+#[derive(Clone, Copy)]
+enum Operand {
+    Dst,
+    Src,
+    Cl,
+    Imm(OpCodeOperandKind),
+}
+
+/// The operand width and operands of code, or None if it has operands this test
+/// doesn't model (memory, other register classes, 64-bit forms).
+fn shape(code: Code) -> Option<(u32, Vec<Operand>)> {
+    use OpCodeOperandKind as K;
+    let op_code = code.op_code();
+    if !op_code.mode32() {
+        return None;
+    }
+    let mut bits = None;
+    let mut operands = Vec::new();
+    for (i, &kind) in op_code.op_kinds().iter().enumerate() {
+        let (width, operand) = match kind {
+            K::r8_or_mem | K::r8_reg | K::r8_opcode | K::al => (Some(8), Operand::Dst),
+            K::r16_or_mem | K::r16_reg | K::r16_opcode | K::ax => (Some(16), Operand::Dst),
+            K::r32_or_mem | K::r32_reg | K::r32_opcode | K::eax => (Some(32), Operand::Dst),
+            K::cl => (None, Operand::Cl),
+            K::imm8 | K::imm8_const_1 | K::imm8sex16 | K::imm8sex32 | K::imm16 | K::imm32 => {
+                (None, Operand::Imm(kind))
+            }
+            _ => return None,
+        };
+        let operand = match operand {
+            Operand::Dst if i > 0 => Operand::Src,
+            _ => operand,
+        };
+        bits = bits.or(width);
+        operands.push(operand);
+    }
+    // One-operand mul and div name their source; the accumulator is implicit.
+    if matches!(
+        code.mnemonic(),
+        Mnemonic::Mul | Mnemonic::Imul | Mnemonic::Div | Mnemonic::Idiv
+    ) && operands.len() == 1
+    {
+        operands[0] = Operand::Src;
+    }
+    Some((bits?, operands))
+}
+
+/// Build one instruction of the given shape, returning it with its immediate, if any,
+/// extended to the operand width.
+fn build(code: Code, bits: u32, operands: &[Operand], rng: &mut Rng) -> (Instruction, u64) {
+    use OpCodeOperandKind as K;
+    let (dst, src, _) = registers(bits);
+    let reg = |op: Operand| match op {
+        Operand::Dst => dst,
+        Operand::Src => src,
+        Operand::Cl => Register::CL,
+        Operand::Imm(_) => unreachable!(),
+    };
+    let mut imm = 0;
+    let mut imm_arg = 0i32;
+    if let Some(&Operand::Imm(kind)) = operands.last() {
+        let (raw, value) = match kind {
+            K::imm8_const_1 => (1, 1),
+            K::imm8 => {
+                let v = rng.count() as i32;
+                (v, v as u64)
+            }
+            K::imm8sex16 | K::imm8sex32 => {
+                let v = rng.value(8) as i8 as i32;
+                (v, v as u64)
+            }
+            K::imm16 => {
+                let v = rng.value(16) as u16 as i32;
+                (v, v as u64)
+            }
+            _ => {
+                let v = rng.value(32) as i32;
+                (v, v as u32 as u64)
+            }
+        };
+        imm_arg = raw;
+        imm = value & mask(bits);
+    }
+    let instr = match *operands {
+        [a] => Instruction::with1(code, reg(a)),
+        [a, Operand::Imm(K::imm8 | K::imm8_const_1 | K::imm16 | K::imm32)] => {
+            Instruction::with2(code, reg(a), imm_arg as u32)
+        }
+        [a, Operand::Imm(_)] => Instruction::with2(code, reg(a), imm_arg),
+        [a, b] => Instruction::with2(code, reg(a), reg(b)),
+        [a, b, Operand::Imm(K::imm32)] => Instruction::with3(code, reg(a), reg(b), imm_arg as u32),
+        [a, b, Operand::Imm(_)] => Instruction::with3(code, reg(a), reg(b), imm_arg),
+        _ => panic!("{code:?}: unexpected operands"),
+    }
+    .unwrap_or_else(|err| panic!("{code:?}: {err}"));
+    (instr, imm)
+}
+
+/// Run CASES random cases of one code, returning a description of the first failure.
+fn check_code(code: Code, bits: u32, operands: &[Operand], rng: &mut Rng) -> Option<String> {
+    let (dst, src, high_reg) = registers(bits);
+    let mut x86 = X86::new();
+    let mut want = X86::new();
+    for _ in 0..CASES {
+        let (instr, imm) = build(code, bits, operands, rng);
+        x86.regs.eax = rng.value(bits);
+        x86.regs.ebx = rng.value(bits);
+        x86.regs.ecx = (rng.next() as u32 & !0xff) | rng.count() as u32;
+        x86.regs.edx = rng.value(bits);
+        let flags = rng.flags();
+        x86.regs.flags = flags;
+        let before = [x86.regs.eax, x86.regs.ebx, x86.regs.ecx, x86.regs.edx];
+
+        let has = |op: fn(&Operand) -> bool| operands.iter().any(op);
+        let one_operand = operands.len() == 1 && matches!(operands[0], Operand::Src);
+        let three_operand = operands.len() == 3;
+        let inputs = Inputs {
+            mnemonic: code.mnemonic(),
+            bits,
+            // Three-operand imul multiplies its source by the immediate.
+            x: if three_operand {
+                get(&x86, src)
+            } else {
+                get(&x86, dst)
+            },
+            y: if has(|op| matches!(op, Operand::Imm(_))) {
+                imm
+            } else if has(|op| matches!(op, Operand::Cl)) {
+                x86.regs.ecx as u8 as u64
+            } else if has(|op| matches!(op, Operand::Src)) {
+                get(&x86, src)
+            } else {
+                0
+            },
+            high: get(&x86, high_reg),
+            one_operand,
+            flags,
+        };
+        let expected = model(&inputs);
+
+        let outcome = x86.run(&instr);
+        let describe = || {
+            format!(
+                "{code:?} with eax={:#x} ebx={:#x} ecx={:#x} edx={:#x} flags={flags:?}",
+                before[0], before[1], before[2], before[3]
+            )
+        };
+        if expected.divide_error {
+            match outcome {
+                Err(StepError::Error(msg)) if msg.starts_with("#DE") => continue,
+                _ => return Some(format!("{}: expected #DE", describe())),
+            }
+        }
+        if let Err(err) = outcome {
+            return Some(format!("{}: failed with {err:?}", describe()));
+        }
+
+        [want.regs.eax, want.regs.ebx, want.regs.ecx, want.regs.edx] = before;
+        if let Some(value) = expected.dst {
+            set(&mut want, dst, value);
+        }
+        if let Some(value) = expected.high {
+            set(&mut want, high_reg, value);
+        }
+        let got = [x86.regs.eax, x86.regs.ebx, x86.regs.ecx, x86.regs.edx];
+        let wanted = [want.regs.eax, want.regs.ebx, want.regs.ecx, want.regs.edx];
+        if got != wanted {
+            return Some(format!(
+                "{}: registers are {got:#x?}, want {wanted:#x?}",
+                describe()
+            ));
+        }
+        for (flag, value) in expected.flags {
+            if x86.regs.flags.contains(flag) != value {
+                return Some(format!("{}: {flag:?} should be {value}", describe()));
+            }
+        }
+    }
+    None
+}
+
+/// The mnemonics whose register and immediate forms the model covers.
+const MNEMONICS: [Mnemonic; 23] = [
+    Mnemonic::Add,
+    Mnemonic::Adc,
+    Mnemonic::Sub,
+    Mnemonic::Sbb,
+    Mnemonic::Cmp,
+    Mnemonic::And,
+    Mnemonic::Or,
+    Mnemonic::Xor,
+    Mnemonic::Test,
+    Mnemonic::Inc,
+    Mnemonic::Dec,
+    Mnemonic::Neg,
+    Mnemonic::Not,
+    Mnemonic::Shl,
+    Mnemonic::Sal,
+    Mnemonic::Shr,
+    Mnemonic::Sar,
+    Mnemonic::Rol,
+    Mnemonic::Ror,
+    Mnemonic::Mul,
+    Mnemonic::Imul,
+    Mnemonic::Div,
+    Mnemonic::Idiv,
+];
+
+#[test]
+fn alu_matches_reference() {
+    X86::new(); // Fills in the op table that has_op() consults.
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut tested = 0;
+    let mut failures = Vec::new();
+    for code in iced_x86::Code::values() {
+        if !MNEMONICS.contains(&code.mnemonic()) || !crate::ops::has_op(code) {
+            continue;
+        }
+        let Some((bits, operands)) = shape(code) else {
+            continue;
+        };
+        tested += 1;
+        failures.extend(check_code(code, bits, &operands, &mut rng));
+    }
+    assert!(tested > 100, "only {tested} codes tested");
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
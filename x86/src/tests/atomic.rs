@@ -0,0 +1,103 @@
+//! lock-prefixed read-modify-write instructions, as used by Interlocked* functions.
+
+use super::{new_x86, run, try_run};
+use crate::registers::Flags;
+
+#[test]
+fn lock_xadd() {
+    let mut x86 = new_x86();
+    x86.regs.ecx = 0x2000;
+    x86.write_u32(0x2000, 5);
+    x86.regs.eax = 0xFFFF_FFFF;
+    // lock xadd [ecx],eax
+    run(&mut x86, &[0xf0, 0x0f, 0xc1, 0x01]);
+    assert_eq!(x86.regs.eax, 5);
+    assert_eq!(x86.read_u32(0x2000), 4);
+    assert!(x86.regs.flags.contains(Flags::CF));
+
+    x86.regs.eax = 3;
+    // xadd eax,eax
+    run(&mut x86, &[0x0f, 0xc1, 0xc0]);
+    assert_eq!(x86.regs.eax, 6);
+
+    x86.regs.ebx = 0xAAAA_AA01;
+    x86.write_u8(0x2000, 0xFF);
+    // lock xadd [ecx],bl
+    run(&mut x86, &[0xf0, 0x0f, 0xc0, 0x19]);
+    assert_eq!(x86.regs.ebx, 0xAAAA_AAFF);
+    assert_eq!(x86.read_u8(0x2000), 0);
+    assert!(x86.regs.flags.contains(Flags::ZF));
+}
+
+#[test]
+fn lock_inc_dec_or() {
+    let mut x86 = new_x86();
+    x86.regs.ecx = 0x2000;
+    x86.write_u32(0x2000, 1);
+    // lock dec dword ptr [ecx]
+    run(&mut x86, &[0xf0, 0xff, 0x09]);
+    assert_eq!(x86.read_u32(0x2000), 0);
+    assert!(x86.regs.flags.contains(Flags::ZF));
+    // lock inc dword ptr [ecx]
+    run(&mut x86, &[0xf0, 0xff, 0x01]);
+    assert_eq!(x86.read_u32(0x2000), 1);
+    assert!(!x86.regs.flags.contains(Flags::ZF));
+
+    x86.regs.flags.insert(Flags::CF | Flags::OF);
+    // lock or dword ptr [ecx],80000000h
+    run(&mut x86, &[0xf0, 0x81, 0x09, 0x00, 0x00, 0x00, 0x80]);
+    assert_eq!(x86.read_u32(0x2000), 0x8000_0001);
+    assert!(x86.regs.flags.contains(Flags::SF));
+    assert!(!x86.regs.flags.intersects(Flags::CF | Flags::OF));
+}
+
+#[test]
+fn cmpxchg8b() {
+    let mut x86 = new_x86();
+    x86.regs.esi = 0x2000;
+    x86.write_u32(0x2000, 0x1111_1111);
+    x86.write_u32(0x2004, 0x2222_2222);
+    x86.regs.eax = 0x1111_1111;
+    x86.regs.edx = 0x2222_2222;
+    x86.regs.ebx = 0x3333_3333;
+    x86.regs.ecx = 0x4444_4444;
+    // lock cmpxchg8b [esi]
+    run(&mut x86, &[0xf0, 0x0f, 0xc7, 0x0e]);
+    assert!(x86.regs.flags.contains(Flags::ZF));
+    assert_eq!(x86.read_u32(0x2000), 0x3333_3333);
+    assert_eq!(x86.read_u32(0x2004), 0x4444_4444);
+
+    // Now a mismatch, which loads edx:eax.
+    run(&mut x86, &[0xf0, 0x0f, 0xc7, 0x0e]);
+    assert!(!x86.regs.flags.contains(Flags::ZF));
+    assert_eq!(x86.regs.eax, 0x3333_3333);
+    assert_eq!(x86.regs.edx, 0x4444_4444);
+}
+
+#[test]
+fn cmpxchg8b_wraps() {
+    let mut x86 = new_x86();
+    // A qword straddling the top of the address space crashes rather than overflowing.
+    x86.regs.esi = 0xFFFF_FFFC;
+    // cmpxchg8b [esi]
+    assert!(try_run(&mut x86, &[0x0f, 0xc7, 0x0e]).is_err());
+}
+
+#[test]
+fn xchg_mem() {
+    let mut x86 = new_x86();
+    x86.regs.ecx = 0x2000;
+    x86.write_u32(0x2000, 7);
+    x86.regs.eax = 9;
+    // xchg [ecx],eax (implicitly locked)
+    run(&mut x86, &[0x87, 0x01]);
+    assert_eq!(x86.regs.eax, 7);
+    assert_eq!(x86.read_u32(0x2000), 9);
+}
+
+#[test]
+fn invalid_lock() {
+    let mut x86 = new_x86();
+    // lock mov eax,ecx
+    assert!(try_run(&mut x86, &[0xf0, 0x89, 0xc8]).is_err());
+}
@@ -0,0 +1,125 @@
+use super::new_x86;
+use crate::{
+    debug::Symbols,
+    expr::{Expr, Template},
+    Breakpoint, InstrCache, StepError, X86,
+};
+
+fn eval(x86: &X86, text: &str) -> Result<u32, String> {
+    Expr::parse(text)?.eval(x86)
+}
+
+#[test]
+fn expr() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0x1234;
+    x86.regs.esp = 0x2000;
+    x86.write_u32(0x2004, 11);
+    x86.regs.flags = crate::registers::Flags::ZF;
+
+    assert_eq!(eval(&x86, "eax == 0x1234 && [esp+4] > 10"), Ok(1));
+    assert_eq!(eval(&x86, "eax == 0x1234 && [esp+4] > 11"), Ok(0));
+    assert_eq!(eval(&x86, "ah + al * 2"), Ok(0x12 + 0x34 * 2));
+    assert_eq!(eval(&x86, "(1 + 2) * 3 - 4 / 2"), Ok(7));
+    assert_eq!(eval(&x86, "1 << 4 | 1 == 17"), Ok(1)); // comparison binds looser than |
+    assert_eq!(eval(&x86, "byte [esp + 4] + word [0x2004]"), Ok(22));
+    assert_eq!(eval(&x86, "zf && !cf"), Ok(1));
+    assert_eq!(eval(&x86, "-1"), Ok(0xffff_ffff));
+    assert_eq!(eval(&x86, "0 - 1 > 0"), Ok(1)); // unsigned
+
+    assert!(eval(&x86, "1 / 0").is_err());
+    assert!(eval(&x86, "[0]").is_err());
+    assert!(Expr::parse("eax ==").is_err());
+    assert!(Expr::parse("foo").is_err());
+    assert!(Expr::parse("[eax").is_err());
+    assert!(Expr::parse("1 2").is_err());
+}
+
+#[test]
+fn template() {
+    let mut x86 = new_x86();
+    x86.regs.ecx = 0x40;
+    let t = Template::parse("read {ecx} bytes, {ecx / 0}").unwrap();
+    assert_eq!(t.render(&x86), "read 40 bytes, <division by zero>");
+    assert!(Template::parse("{ecx").is_err());
+}
+
+const CODE_ADDR: u32 = 0x1000;
+// loop: inc eax; jmp loop
+const CODE: &[u8] = &[0x40, 0xeb, 0xfd];
+
+/// Runs the loop until it stops at a breakpoint, returning eax.
+fn run_to_break(x86: &mut X86, icache: &mut InstrCache) -> u32 {
+    for _ in 0..1000 {
+        match icache.step(x86, &Symbols::default()) {
+            Ok(true) => {}
+            Ok(false) => icache.jmp(&x86.mem, x86.regs.eip).unwrap(),
+            Err(StepError::Interrupt) => return x86.regs.eax,
+            Err(err) => panic!("{err:?}"),
+        }
+    }
+    panic!("no break");
+}
+
+fn looping(bp: Breakpoint) -> (X86, InstrCache) {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0;
+    x86.mem[CODE_ADDR as usize..][..CODE.len()].copy_from_slice(CODE);
+    let mut icache = InstrCache::new();
+    icache.disassemble(CODE, CODE_ADDR);
+    icache.add_breakpoint(CODE_ADDR, bp);
+    (x86, icache)
+}
+
+#[test]
+fn conditional() {
+    let (mut x86, mut icache) = looping(Breakpoint {
+        condition: Some(Expr::parse("eax % 5 == 3").unwrap()),
+        ..Default::default()
+    });
+    assert_eq!(run_to_break(&mut x86, &mut icache), 3);
+    assert_eq!(x86.regs.eip, CODE_ADDR);
+    // Stepping again stops at the same place until resumed.
+    assert_eq!(run_to_break(&mut x86, &mut icache), 3);
+    icache.resume();
+    assert_eq!(run_to_break(&mut x86, &mut icache), 8);
+    assert_eq!(icache.breakpoint(CODE_ADDR).unwrap().hits, 3);
+}
+
+#[test]
+fn counted() {
+    let (mut x86, mut icache) = looping(Breakpoint {
+        ignore_count: 4,
+        ..Default::default()
+    });
+    assert_eq!(run_to_break(&mut x86, &mut icache), 4);
+    icache.resume();
+    assert_eq!(run_to_break(&mut x86, &mut icache), 5);
+}
+
+#[test]
+fn tracepoint() {
+    let (mut x86, mut icache) = looping(Breakpoint {
+        log: Some(Template::parse("eax={eax}").unwrap()),
+        ..Default::default()
+    });
+    icache.add_breakpoint(CODE_ADDR + 1, Breakpoint::default());
+    assert_eq!(run_to_break(&mut x86, &mut icache), 1);
+    icache.resume();
+    assert_eq!(run_to_break(&mut x86, &mut icache), 2);
+    assert_eq!(icache.breakpoint(CODE_ADDR).unwrap().hits, 2);
+}
+
+#[test]
+fn not_an_instruction() {
+    let (mut x86, mut icache) = looping(Breakpoint::default());
+    // Mid-instruction (the jmp's displacement), and outside the code.
+    assert!(!icache.add_breakpoint(CODE_ADDR + 2, Breakpoint::default()));
+    assert!(!icache.add_breakpoint(0x5000, Breakpoint::default()));
+    assert!(!icache.clear_breakpoint(CODE_ADDR + 1));
+    // The real breakpoint still works, and clears once.
+    assert_eq!(run_to_break(&mut x86, &mut icache), 0);
+    assert!(icache.clear_breakpoint(CODE_ADDR));
+    assert!(!icache.clear_breakpoint(CODE_ADDR));
+    assert!(icache.breakpoint(CODE_ADDR).is_none());
+}
@@ -0,0 +1,60 @@
+use crate::debug::{call_before, disassemble, Symbols};
+
+#[test]
+fn symbolized() {
+    let mut mem = vec![0u8; 0x2000];
+    let code: &[u8] = &[
+        0xff, 0x15, 0x00, 0x18, 0x00, 0x00, // call dword ptr [0x1800]
+        0xe8, 0xf5, 0xff, 0xff, 0xff, // call 0x1000
+        0xa1, 0x04, 0x18, 0x00, 0x00, // mov eax, [0x1804]
+        0x68, 0x00, 0x18, 0x00, 0x00, // push 0x1800
+    ];
+    mem[0x1000..][..code.len()].copy_from_slice(code);
+    let mut symbols = Symbols::default();
+    symbols.insert(0x1000, "start".into());
+    symbols.insert(0x1800, "GetProcAddress@IAT".into());
+
+    let text: Vec<String> = disassemble(&mem, 0x1000, &symbols)
+        .iter()
+        .take(4)
+        .map(|instr| instr.code.iter().map(|part| part.text.as_str()).collect())
+        .collect();
+    assert_eq!(
+        text,
+        [
+            "call dword ptr [GetProcAddress@IAT]",
+            "call start",
+            "mov eax,[1804h]",
+            // Immediates aren't assumed to be addresses.
+            "push 1800h",
+        ]
+    );
+}
+
+#[test]
+fn calls_before() {
+    let mut mem = vec![0u8; 0x2000];
+    let code: &[u8] = &[
+        0xff, 0xd0, // call eax
+        0xff, 0x50, 0x08, // call dword ptr [eax+8]
+        0xff, 0x54, 0x24, 0x08, // call dword ptr [esp+8]
+        0xe8, 0xf2, 0xff, 0xff, 0xff, // call 1000h
+        0xff, 0x15, 0x00, 0x18, 0x00, 0x00, // call dword ptr [1800h]
+        0xff, 0x94, 0x24, 0x00, 0x01, 0x00, 0x00, // call dword ptr [esp+100h]
+        0xb8, 0x05, 0x00, 0x00, 0x00, // mov eax,5
+    ];
+    mem[0x1000..][..code.len()].copy_from_slice(code);
+
+    let mut addr = 0x1000;
+    for len in [2, 3, 4, 5, 6, 7] {
+        addr += len;
+        let instr = call_before(&mem, addr).unwrap_or_else(|| panic!("no call before {addr:x}"));
+        assert_eq!((instr.ip() as u32, instr.len()), (addr - len, len as usize));
+    }
+    // Not after a call.
+    assert!(call_before(&mem, addr + 5).is_none());
+    assert!(call_before(&mem, 0x1001).is_none());
+    // Too close to either end of memory.
+    assert!(call_before(&mem, 1).is_none());
+    assert!(call_before(&mem, 0x3000).is_none());
+}
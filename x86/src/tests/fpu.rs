@@ -0,0 +1,331 @@
+//! x87 arithmetic and transcendental ops.
+
+use super::{new_x86, run, try_run};
+use crate::registers::FPUStatus;
+use iced_x86::Register;
+
+/// Push values onto the FPU stack, so the last one ends up in st(0).
+fn push(x86: &mut crate::X86, values: &[f64]) {
+    for &v in values {
+        x86.regs.st_top -= 1;
+        *x86.regs.st_top() = v;
+    }
+}
+
+fn st(x86: &mut crate::X86, i: usize) -> f64 {
+    *x86.regs
+        .getst([Register::ST0, Register::ST1, Register::ST2][i])
+}
+
+#[test]
+fn arith_forms() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[10.0, 4.0]);
+    // fsub st,st(1) => st0 = 4 - 10
+    run(&mut x86, &[0xd8, 0xe1]);
+    assert_eq!(st(&mut x86, 0), -6.0);
+    // fsubr st,st(1) => st0 = 10 - -6
+    run(&mut x86, &[0xd8, 0xe9]);
+    assert_eq!(st(&mut x86, 0), 16.0);
+    // fdiv st(1),st => st1 = 10 / 16
+    run(&mut x86, &[0xdc, 0xf9]);
+    assert_eq!(st(&mut x86, 1), 0.625);
+    // fdivrp st(1),st => st1 = 16 / 0.625, pop
+    run(&mut x86, &[0xde, 0xf1]);
+    assert_eq!(x86.regs.st_top, 7);
+    assert_eq!(st(&mut x86, 0), 25.6);
+}
+
+#[test]
+fn arith_mem() {
+    let mut x86 = new_x86();
+    x86.write_u32(0x2000, 3);
+    x86.write_u16(0x2004, -2i16 as u16);
+    x86.write_u32(0x2008, 1.5f32.to_bits());
+    push(&mut x86, &[1.0]);
+    // fiadd dword ptr ds:[2000h]
+    run(&mut x86, &[0xda, 0x05, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(st(&mut x86, 0), 4.0);
+    // fimul word ptr ds:[2004h]
+    run(&mut x86, &[0xde, 0x0d, 0x04, 0x20, 0x00, 0x00]);
+    assert_eq!(st(&mut x86, 0), -8.0);
+    // fdivr dword ptr ds:[2008h] => 1.5 / -8
+    run(&mut x86, &[0xd8, 0x3d, 0x08, 0x20, 0x00, 0x00]);
+    assert_eq!(st(&mut x86, 0), -0.1875);
+}
+
+#[test]
+fn constants() {
+    let mut x86 = new_x86();
+    // fldpi; fldl2e; fldln2
+    run(&mut x86, &[0xd9, 0xeb, 0xd9, 0xea, 0xd9, 0xed]);
+    assert_eq!(st(&mut x86, 0), std::f64::consts::LN_2);
+    assert_eq!(st(&mut x86, 1), std::f64::consts::LOG2_E);
+    assert_eq!(st(&mut x86, 2), std::f64::consts::PI);
+}
+
+#[test]
+fn rounding_and_scale() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[-2.7, 2.5]);
+    // frndint
+    run(&mut x86, &[0xd9, 0xfc]);
+    assert_eq!(st(&mut x86, 0), 2.0);
+    // fscale => 2 * 2^trunc(-2.7)
+    run(&mut x86, &[0xd9, 0xfd]);
+    assert_eq!(st(&mut x86, 0), 0.5);
+    // fabs on st(1) after fxch
+    run(&mut x86, &[0xd9, 0xc9, 0xd9, 0xe1]);
+    assert_eq!(st(&mut x86, 0), 2.7);
+}
+
+#[test]
+fn fprem() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[3.0, 17.0]);
+    // fprem => 17 % 3, quotient 5
+    run(&mut x86, &[0xd9, 0xf8]);
+    assert_eq!(st(&mut x86, 0), 2.0);
+    let status = x86.regs.fpu_status;
+    assert!(status.contains(FPUStatus::C0 | FPUStatus::C1));
+    assert!(!status.intersects(FPUStatus::C2 | FPUStatus::C3));
+
+    *x86.regs.st_top() = 17.0;
+    // fprem1 => 17 - 6*3
+    run(&mut x86, &[0xd9, 0xf5]);
+    assert_eq!(st(&mut x86, 0), -1.0);
+}
+
+#[test]
+fn fprem_large_quotient() {
+    // 1e18 / 7 doesn't fit in an f64's mantissa, but the remainder is still exact.
+    let mut x86 = new_x86();
+    push(&mut x86, &[7.0, 1e18]);
+    run(&mut x86, &[0xd9, 0xf8]);
+    assert_eq!(st(&mut x86, 0), 1.0);
+    let status = x86.regs.fpu_status;
+    assert!(status.contains(FPUStatus::C1));
+    assert!(!status.intersects(FPUStatus::C0 | FPUStatus::C2 | FPUStatus::C3));
+
+    *x86.regs.st_top() = 1e18;
+    run(&mut x86, &[0xd9, 0xf5]);
+    assert_eq!(st(&mut x86, 0), 1.0);
+
+    // Exponents 99 apart: fprem only partially reduces, setting C2 until done.
+    *x86.regs.st_top() = 2f64.powi(100);
+    *x86.regs.getst(Register::ST1) = 3.0;
+    run(&mut x86, &[0xd9, 0xf8]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C2));
+    let mut rounds = 1;
+    while x86.regs.fpu_status.contains(FPUStatus::C2) {
+        run(&mut x86, &[0xd9, 0xf8]);
+        rounds += 1;
+    }
+    assert_eq!(st(&mut x86, 0), 1.0);
+    assert!(rounds <= 4);
+}
+
+#[test]
+fn transcendental() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[0.0]);
+    // fsincos
+    run(&mut x86, &[0xd9, 0xfb]);
+    assert_eq!(st(&mut x86, 0), 1.0);
+    assert_eq!(st(&mut x86, 1), 0.0);
+
+    let mut x86 = new_x86();
+    push(&mut x86, &[0.0]);
+    // fptan
+    run(&mut x86, &[0xd9, 0xf2]);
+    assert_eq!(st(&mut x86, 0), 1.0);
+    assert_eq!(st(&mut x86, 1), 0.0);
+
+    let mut x86 = new_x86();
+    push(&mut x86, &[3.0, 8.0]);
+    // fyl2x => 3 * log2(8)
+    run(&mut x86, &[0xd9, 0xf1]);
+    assert_eq!(x86.regs.st_top, 7);
+    assert_eq!(st(&mut x86, 0), 9.0);
+    // fld1; fyl2xp1 => 9 * log2(1 + 1)
+    run(&mut x86, &[0xd9, 0xe8, 0xd9, 0xf9]);
+    assert_eq!(st(&mut x86, 0), 9.0);
+
+    *x86.regs.st_top() = 3.0;
+    // f2xm1
+    run(&mut x86, &[0xd9, 0xf0]);
+    assert!((st(&mut x86, 0) - 7.0).abs() < 1e-12);
+}
+
+#[test]
+fn fxtract() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[-24.0]);
+    // fxtract
+    run(&mut x86, &[0xd9, 0xf4]);
+    assert_eq!(st(&mut x86, 0), -1.5);
+    assert_eq!(st(&mut x86, 1), 4.0);
+
+    let mut x86 = new_x86();
+    push(&mut x86, &[f64::MIN_POSITIVE / 4.0]);
+    run(&mut x86, &[0xd9, 0xf4]);
+    assert_eq!(st(&mut x86, 0), 1.0);
+    assert_eq!(st(&mut x86, 1), -1024.0);
+}
+
+#[test]
+fn fcom_family() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[2.0, 1.0]);
+    // fcom st(1) => 1 < 2
+    run(&mut x86, &[0xd8, 0xd1]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C0));
+    assert!(!x86
+        .regs
+        .fpu_status
+        .intersects(FPUStatus::C2 | FPUStatus::C3));
+
+    x86.write_u16(0x2000, 1);
+    // ficom word ptr ds:[2000h] => equal
+    run(&mut x86, &[0xde, 0x15, 0x00, 0x20, 0x00, 0x00]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C3));
+    assert!(!x86
+        .regs
+        .fpu_status
+        .intersects(FPUStatus::C2 | FPUStatus::C0));
+
+    *x86.regs.st_top() = f64::NAN;
+    // fucompp => unordered, pops both
+    run(&mut x86, &[0xda, 0xe9]);
+    assert!(x86
+        .regs
+        .fpu_status
+        .contains(FPUStatus::C3 | FPUStatus::C2 | FPUStatus::C0));
+    assert_eq!(x86.regs.st_top, 8);
+}
+
+#[test]
+fn fcomi() {
+    use crate::registers::Flags;
+    let mut x86 = new_x86();
+    push(&mut x86, &[1.0, 2.0]);
+    // fcomi st,st(1) => 2 > 1
+    run(&mut x86, &[0xdb, 0xf1]);
+    assert!(!x86.regs.flags.intersects(Flags::ZF | Flags::PF | Flags::CF));
+
+    *x86.regs.st_top() = f64::NAN;
+    // fucomip st,st(1)
+    run(&mut x86, &[0xdf, 0xe9]);
+    assert!(x86.regs.flags.contains(Flags::ZF | Flags::PF | Flags::CF));
+    assert_eq!(x86.regs.st_top, 7);
+
+    // jp $+4
+    let instr = iced_x86::Decoder::with_ip(32, &[0x7a, 0x02], 0x1000, 0).decode();
+    x86.regs.eip = instr.next_ip() as u32;
+    x86.run(&instr).unwrap();
+    assert_eq!(x86.regs.eip, 0x1004);
+}
+
+#[test]
+fn ftst_fxam() {
+    let mut x86 = new_x86();
+    // fxam on an empty stack
+    run(&mut x86, &[0xd9, 0xe5]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C3 | FPUStatus::C0));
+
+    push(&mut x86, &[-0.0]);
+    // ftst; equal to zero
+    run(&mut x86, &[0xd9, 0xe4]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C3));
+    // fxam => negative zero
+    run(&mut x86, &[0xd9, 0xe5]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C3 | FPUStatus::C1));
+    assert!(!x86
+        .regs
+        .fpu_status
+        .intersects(FPUStatus::C2 | FPUStatus::C0));
+
+    *x86.regs.st_top() = f64::INFINITY;
+    run(&mut x86, &[0xd9, 0xe5]);
+    assert!(x86.regs.fpu_status.contains(FPUStatus::C2 | FPUStatus::C0));
+    assert!(!x86
+        .regs
+        .fpu_status
+        .intersects(FPUStatus::C3 | FPUStatus::C1));
+}
+
+#[test]
+fn integer_transfers() {
+    let mut x86 = new_x86();
+    x86.write_u32(0x2000, 0xFFFF_FFFF);
+    x86.write_u32(0x2004, 0xFFFF_FFFF);
+    // fild qword ptr ds:[2000h]
+    run(&mut x86, &[0xdf, 0x2d, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(st(&mut x86, 0), -1.0);
+
+    *x86.regs.st_top() = -2.9;
+    // fist word ptr ds:[2000h] => rounds to nearest
+    run(&mut x86, &[0xdf, 0x15, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(x86.read_u16(0x2000), -3i16 as u16);
+
+    *x86.regs.st_top() = 40000.0;
+    // fistp word ptr ds:[2000h] => out of range, integer indefinite
+    run(&mut x86, &[0xdf, 0x1d, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(x86.read_u16(0x2000), 0x8000);
+    assert_eq!(x86.regs.st_top, 8);
+
+    push(&mut x86, &[7.9]);
+    // fisttp dword ptr ds:[2000h]
+    run(&mut x86, &[0xdb, 0x0d, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(x86.read_u32(0x2000), 7);
+    assert_eq!(x86.regs.st_top, 8);
+}
+
+#[test]
+fn rounding_control() {
+    let mut x86 = new_x86();
+    let fistp = [0xdb, 0x1d, 0x00, 0x20, 0x00, 0x00]; // fistp dword ptr ds:[2000h]
+    let fldcw = [0xd9, 0x2d, 0x04, 0x20, 0x00, 0x00]; // fldcw word ptr ds:[2004h]
+
+    // Default round to nearest, ties to even.
+    for (value, expected) in [(2.5, 2), (3.5, 4), (-2.5, -2)] {
+        push(&mut x86, &[value]);
+        run(&mut x86, &fistp);
+        assert_eq!(x86.read_u32(0x2000) as i32, expected);
+    }
+
+    for (rc, expected) in [(1, -3), (2, -2), (3, -2)] {
+        x86.write_u16(0x2004, 0x027F | (rc << 10));
+        run(&mut x86, &fldcw);
+        push(&mut x86, &[-2.5]);
+        run(&mut x86, &fistp);
+        assert_eq!(x86.read_u32(0x2000) as i32, expected);
+    }
+
+    // fnstcw word ptr ds:[2006h] reports the loaded word.
+    run(&mut x86, &[0xd9, 0x3d, 0x06, 0x20, 0x00, 0x00]);
+    assert_eq!(x86.read_u16(0x2006), 0x0E7F);
+}
+
+#[test]
+fn bcd() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[-1234567.5]);
+    // fbstp tbyte ptr ds:[2000h] => rounds to even
+    run(&mut x86, &[0xdf, 0x35, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(x86.read_u32(0x2000), 0x0123_4568);
+    assert_eq!(x86.read_u8(0x2009), 0x80);
+    assert_eq!(x86.regs.st_top, 8);
+
+    // fbld tbyte ptr ds:[2000h]
+    run(&mut x86, &[0xdf, 0x25, 0x00, 0x20, 0x00, 0x00]);
+    assert_eq!(st(&mut x86, 0), -1234568.0);
+}
+
+#[test]
+fn bcd_wraps() {
+    let mut x86 = new_x86();
+    push(&mut x86, &[1.0]);
+    // fbstp tbyte ptr ds:[0FFFFFFFAh]
+    assert!(try_run(&mut x86, &[0xdf, 0x35, 0xfa, 0xff, 0xff, 0xff]).is_err());
+}
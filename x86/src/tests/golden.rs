@@ -0,0 +1,332 @@
+//! Table-driven instruction tests against golden vectors.
+//!
+//! Each line of golden.txt is one case, with four fields separated by '|':
+//!
+//!   bytes | disassembly | state before | expected state after
+//!
+//! The disassembly must match iced's formatting of the bytes, which guards against
+//! typos in the bytes.  States are space-separated tokens:
+//!
+//!   eax=1       a 32-bit register (or eip), in decimal, -decimal, or 0x hex
+//!   +CF -ZF     an EFLAGS bit (CF PF ZF SF DF OF) or FPU condition code (C0-C3)
+//!   m32[0x2000]=5   memory, one of m8/m16/m32/m64
+//!   mm0=0x1     an MMX register
+//!   st=1,2.5    the whole FPU stack, st(0) first; "st=" is an empty stack
+//!   !interrupt  (after only) the instruction stops with StepError::Interrupt
+//!   !error      (after only) the instruction fails with StepError::Error
+//!
+//! Flags and memory are only compared when listed.  Registers and the FPU stack
+//! must be unchanged unless listed, and eip defaults to the next instruction.
+
+use crate::{
+    registers::{FPUStatus, Flags},
+    StepError, X86,
+};
+use iced_x86::{Decoder, DecoderOptions, Formatter, IntelFormatter};
+use std::collections::HashSet;
+
+const CORPUS: &str = include_str!("golden.txt");
+
+/// Where the instruction under test is placed.
+const CODE_ADDR: u32 = 0x1000;
+
+fn parse_num(s: &str) -> u64 {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let n = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.parse(),
+    }
+    .unwrap_or_else(|_| panic!("bad number {s:?}"));
+    if neg {
+        n.wrapping_neg()
+    } else {
+        n
+    }
+}
+
+fn parse_float(s: &str) -> f64 {
+    match s {
+        "nan" => f64::NAN,
+        "inf" => f64::INFINITY,
+        "-inf" => f64::NEG_INFINITY,
+        _ => s.parse().unwrap_or_else(|_| panic!("bad float {s:?}")),
+    }
+}
+
+const GPRS: [&str; 9] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip",
+];
+
+/// Index of a register name in GPRS.
+fn gpr_index(name: &str) -> Option<usize> {
+    GPRS.iter().position(|&n| n == name)
+}
+
+fn gprs(x86: &X86) -> [u32; 9] {
+    let r = &x86.regs;
+    [
+        r.eax, r.ecx, r.edx, r.ebx, r.esp, r.ebp, r.esi, r.edi, r.eip,
+    ]
+}
+
+fn set_gpr(x86: &mut X86, index: usize, value: u32) {
+    let r = &mut x86.regs;
+    *[
+        &mut r.eax, &mut r.ecx, &mut r.edx, &mut r.ebx, &mut r.esp, &mut r.ebp, &mut r.esi,
+        &mut r.edi, &mut r.eip,
+    ][index] = value;
+}
+
+enum Flag {
+    Cpu(Flags),
+    Fpu(FPUStatus),
+}
+
+fn flag(name: &str) -> Flag {
+    match name {
+        "CF" => Flag::Cpu(Flags::CF),
+        "PF" => Flag::Cpu(Flags::PF),
+        "ZF" => Flag::Cpu(Flags::ZF),
+        "SF" => Flag::Cpu(Flags::SF),
+        "DF" => Flag::Cpu(Flags::DF),
+        "OF" => Flag::Cpu(Flags::OF),
+        "C0" => Flag::Fpu(FPUStatus::C0),
+        "C1" => Flag::Fpu(FPUStatus::C1),
+        "C2" => Flag::Fpu(FPUStatus::C2),
+        "C3" => Flag::Fpu(FPUStatus::C3),
+        _ => panic!("bad flag {name:?}"),
+    }
+}
+
+/// A memory token like m32[0x2000], returning (size in bytes, address).
+fn parse_mem(key: &str) -> Option<(u32, u32)> {
+    let (size, rest) = key.split_once('[')?;
+    let size = match size {
+        "m8" => 1,
+        "m16" => 2,
+        "m32" => 4,
+        "m64" => 8,
+        _ => return None,
+    };
+    Some((size, parse_num(rest.strip_suffix(']')?) as u32))
+}
+
+fn read_mem(x86: &X86, addr: u32, size: u32) -> u64 {
+    let addr = addr as usize;
+    let mut bytes = [0u8; 8];
+    bytes[..size as usize].copy_from_slice(&x86.mem[addr..addr + size as usize]);
+    u64::from_le_bytes(bytes)
+}
+
+fn parse_stack(value: &str) -> Vec<f64> {
+    if value.is_empty() {
+        return vec![];
+    }
+    value.split(',').map(parse_float).collect()
+}
+
+fn stack(x86: &X86) -> Vec<f64> {
+    x86.regs.st[x86.regs.st_top..].to_vec()
+}
+
+fn float_eq(x: f64, y: f64) -> bool {
+    (x.is_nan() && y.is_nan()) || x == y || (x - y).abs() <= 1e-12 * x.abs().max(y.abs())
+}
+
+/// Apply a "before" state to a fresh machine.
+fn setup(x86: &mut X86, state: &str) {
+    for token in state.split_whitespace() {
+        if let Some(name) = token.strip_prefix('+') {
+            match flag(name) {
+                Flag::Cpu(f) => x86.regs.flags.insert(f),
+                Flag::Fpu(f) => x86.regs.fpu_status.insert(f),
+            }
+            continue;
+        }
+        let (key, value) = token
+            .split_once('=')
+            .unwrap_or_else(|| panic!("bad token {token:?}"));
+        if let Some(i) = gpr_index(key) {
+            set_gpr(x86, i, parse_num(value) as u32);
+        } else if let Some((size, addr)) = parse_mem(key) {
+            let addr = addr as usize;
+            let bytes = parse_num(value).to_le_bytes();
+            x86.mem[addr..addr + size as usize].copy_from_slice(&bytes[..size as usize]);
+        } else if let Some(i) = key.strip_prefix("mm") {
+            x86.regs.mm[i.parse::<usize>().unwrap()] = parse_num(value);
+        } else if key == "st" {
+            let values = parse_stack(value);
+            x86.regs.st_top = 8 - values.len();
+            x86.regs.st[x86.regs.st_top..].copy_from_slice(&values);
+        } else {
+            panic!("bad token {token:?}");
+        }
+    }
+}
+
+/// The parts of the machine state that must be unchanged unless the case says otherwise.
+struct Initial {
+    gprs: [u32; 9],
+    stack: Vec<f64>,
+}
+
+/// Compare the machine against an "after" state, returning a description of each mismatch.
+fn check(x86: &X86, initial: Initial, state: &str, outcome: Result<(), StepError>) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut expected_gprs = initial.gprs;
+    let mut expected_stack = initial.stack;
+    let mut expected_outcome = "ok";
+
+    for token in state.split_whitespace() {
+        if let Some(outcome) = token.strip_prefix('!') {
+            expected_outcome = outcome;
+            continue;
+        }
+        if let Some((set, name)) = token
+            .strip_prefix('+')
+            .map(|n| (true, n))
+            .or_else(|| token.strip_prefix('-').map(|n| (false, n)))
+        {
+            let actual = match flag(name) {
+                Flag::Cpu(f) => x86.regs.flags.contains(f),
+                Flag::Fpu(f) => x86.regs.fpu_status.contains(f),
+            };
+            if actual != set {
+                errors.push(format!(
+                    "{name} is {}",
+                    if actual { "set" } else { "clear" }
+                ));
+            }
+            continue;
+        }
+        let (key, value) = token.split_once('=').unwrap();
+        if let Some(i) = gpr_index(key) {
+            expected_gprs[i] = parse_num(value) as u32;
+        } else if let Some((size, addr)) = parse_mem(key) {
+            let want = parse_num(value) & (u64::MAX >> (64 - size * 8));
+            let got = read_mem(x86, addr, size);
+            if got != want {
+                errors.push(format!("{key} is {got:#x}, want {want:#x}"));
+            }
+        } else if let Some(i) = key.strip_prefix("mm") {
+            let want = parse_num(value);
+            let got = x86.regs.mm[i.parse::<usize>().unwrap()];
+            if got != want {
+                errors.push(format!("{key} is {got:#x}, want {want:#x}"));
+            }
+        } else if key == "st" {
+            expected_stack = parse_stack(value);
+        } else {
+            panic!("bad token {token:?}");
+        }
+    }
+
+    for ((name, got), want) in GPRS.iter().zip(gprs(x86)).zip(expected_gprs) {
+        if got != want {
+            errors.push(format!("{name} is {got:#x}, want {want:#x}"));
+        }
+    }
+    let got_stack = stack(x86);
+    if got_stack.len() != expected_stack.len()
+        || !got_stack
+            .iter()
+            .zip(&expected_stack)
+            .all(|(&x, &y)| float_eq(x, y))
+    {
+        errors.push(format!("st is {got_stack:?}, want {expected_stack:?}"));
+    }
+    let got_outcome = match outcome {
+        Ok(()) => "ok".to_string(),
+        Err(StepError::Interrupt) => "interrupt".to_string(),
+        Err(StepError::Error(msg)) => format!("error ({msg})"),
+    };
+    if !got_outcome.starts_with(expected_outcome) {
+        errors.push(format!("outcome is {got_outcome}, want {expected_outcome}"));
+    }
+    errors
+}
+
+fn new_machine() -> X86 {
+    let mut x86 = X86::new();
+    x86.mem.resize(0x4000, 0);
+    x86.regs.esp = 0x4000;
+    x86
+}
+
+/// Run every case in the corpus, returning the set of instruction codes exercised.
+fn run_corpus() -> HashSet<iced_x86::Code> {
+    let mut codes = HashSet::new();
+    let mut failures = Vec::new();
+    let mut formatter = IntelFormatter::new();
+    for (lineno, line) in CORPUS.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [bytes, asm, before, after] = fields[..] else {
+            panic!("golden.txt:{}: expected 4 fields", lineno + 1);
+        };
+        let bytes: Vec<u8> = bytes
+            .split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).unwrap())
+            .collect();
+
+        let mut decoder = Decoder::with_ip(32, &bytes, CODE_ADDR as u64, DecoderOptions::NONE);
+        let instr = decoder.decode();
+        let mut text = String::new();
+        formatter.format(&instr, &mut text);
+        if text != asm || instr.len() != bytes.len() {
+            failures.push(format!(
+                "golden.txt:{}: bytes decode as {text:?} ({} bytes)",
+                lineno + 1,
+                instr.len()
+            ));
+            continue;
+        }
+        codes.insert(instr.code());
+
+        let mut x86 = new_machine();
+        x86.mem[CODE_ADDR as usize..][..bytes.len()].copy_from_slice(&bytes);
+        setup(&mut x86, before);
+        x86.regs.eip = instr.next_ip() as u32;
+        let initial = Initial {
+            gprs: gprs(&x86),
+            stack: stack(&x86),
+        };
+
+        let outcome = x86.run(&instr);
+        let errors = check(&x86, initial, after, outcome);
+        if !errors.is_empty() {
+            failures.push(format!(
+                "golden.txt:{}: {asm}: {}",
+                lineno + 1,
+                errors.join(", ")
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    codes
+}
+
+#[test]
+fn golden() {
+    run_corpus();
+}
+
+/// Codes with an op that the decoder never produces, so no case can reach them.
+/// iced decodes "fwait; fnclex" as two instructions rather than as Fclex.
+const UNDECODABLE: [iced_x86::Code; 1] = [iced_x86::Code::Fclex];
+
+#[test]
+fn coverage() {
+    let codes = run_corpus();
+    let missing: Vec<_> = iced_x86::Code::values()
+        .filter(|&code| crate::ops::has_op(code) && !codes.contains(&code))
+        .filter(|code| !UNDECODABLE.contains(code))
+        .collect();
+    assert!(missing.is_empty(), "no golden cases for {missing:?}");
+}
@@ -0,0 +1,38 @@
+//! Tests that decode and run instructions, then check the resulting machine state.
+
+mod alu;
+mod atomic;
+mod breakpoint;
+mod debug;
+mod fpu;
+mod golden;
+mod mul_div;
+mod operand_size;
+mod string;
+mod trace;
+mod watch;
+
+use crate::{StepResult, X86};
+
+/// Create an X86 with a bit of memory past the null page and a stack at the end of it.
+fn new_x86() -> X86 {
+    let mut x86 = X86::new();
+    x86.mem.resize(0x3000, 0);
+    x86.regs.esp = 0x3000;
+    x86
+}
+
+/// Decode and run the instructions in code, which must not jump.
+fn run(x86: &mut X86, code: &[u8]) {
+    try_run(x86, code).unwrap();
+}
+
+/// Like run(), but stops at the first instruction that fails.
+fn try_run(x86: &mut X86, code: &[u8]) -> StepResult<()> {
+    let decoder = iced_x86::Decoder::with_ip(32, code, 0, iced_x86::DecoderOptions::NONE);
+    for instr in decoder {
+        x86.regs.eip = instr.next_ip() as u32;
+        x86.run(&instr)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! One-operand multiply/divide and carrying arithmetic.
+
+use super::{new_x86, run, try_run};
+use crate::{registers::Flags, StepError};
+
+#[test]
+fn mul32() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0x8000_0001;
+    x86.regs.ebx = 4;
+    // mul ebx
+    run(&mut x86, &[0xf7, 0xe3]);
+    assert_eq!(x86.regs.eax, 4);
+    assert_eq!(x86.regs.edx, 2);
+    assert!(x86.regs.flags.contains(Flags::CF | Flags::OF));
+
+    x86.regs.eax = 3;
+    // mul ebx
+    run(&mut x86, &[0xf7, 0xe3]);
+    assert_eq!(x86.regs.eax, 12);
+    assert_eq!(x86.regs.edx, 0);
+    assert!(!x86.regs.flags.intersects(Flags::CF | Flags::OF));
+}
+
+#[test]
+fn mul8() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_AA80;
+    x86.regs.ebx = 0x03;
+    // mul bl
+    run(&mut x86, &[0xf6, 0xe3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_0180);
+    assert!(x86.regs.flags.contains(Flags::CF));
+}
+
+#[test]
+fn imul() {
+    let mut x86 = new_x86();
+    x86.regs.eax = -3i32 as u32;
+    x86.regs.ebx = 5;
+    // imul ebx
+    run(&mut x86, &[0xf7, 0xeb]);
+    assert_eq!(x86.regs.eax, -15i32 as u32);
+    assert_eq!(x86.regs.edx, 0xFFFF_FFFF);
+    assert!(!x86.regs.flags.intersects(Flags::CF | Flags::OF));
+
+    x86.regs.eax = 0xAAAA_FF80; // al = -128
+    x86.regs.ebx = 0xFF; // bl = -1
+                         // imul bl
+    run(&mut x86, &[0xf6, 0xeb]);
+    assert_eq!(x86.regs.eax, 0xAAAA_0080);
+    assert!(x86.regs.flags.contains(Flags::CF | Flags::OF));
+
+    x86.regs.eax = 0x4000_0000;
+    x86.regs.ecx = 2;
+    // imul eax,ecx
+    run(&mut x86, &[0x0f, 0xaf, 0xc1]);
+    assert_eq!(x86.regs.eax, 0x8000_0000);
+    assert!(x86.regs.flags.contains(Flags::CF | Flags::OF));
+}
+
+#[test]
+fn div8() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_0107; // 263
+    x86.regs.ebx = 0x10;
+    // div bl
+    run(&mut x86, &[0xf6, 0xf3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_0710);
+}
+
+#[test]
+fn idiv() {
+    let mut x86 = new_x86();
+    x86.regs.edx = 0xFFFF_FFFF;
+    x86.regs.eax = -7i32 as u32;
+    x86.regs.ebx = 2;
+    // idiv ebx
+    run(&mut x86, &[0xf7, 0xfb]);
+    assert_eq!(x86.regs.eax, -3i32 as u32);
+    assert_eq!(x86.regs.edx, -1i32 as u32);
+
+    x86.regs.eax = 0xAAAA_FFF9; // ax = -7
+    x86.regs.ebx = 2;
+    // idiv bl
+    run(&mut x86, &[0xf6, 0xfb]);
+    assert_eq!(x86.regs.eax, 0xAAAA_FFFD);
+}
+
+fn assert_divide_error(result: Result<(), StepError>) {
+    match result {
+        Err(StepError::Error(msg)) => assert!(msg.contains("#DE"), "{msg}"),
+        r => panic!("expected #DE, got {r:?}"),
+    }
+}
+
+#[test]
+fn divide_error() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 1;
+    x86.regs.edx = 0;
+    x86.regs.ebx = 0;
+    // div ebx
+    assert_divide_error(try_run(&mut x86, &[0xf7, 0xf3]));
+    // Registers are untouched.
+    assert_eq!(x86.regs.eax, 1);
+
+    // Quotient too large.
+    x86.regs.edx = 1;
+    x86.regs.ebx = 1;
+    // div ebx
+    assert_divide_error(try_run(&mut x86, &[0xf7, 0xf3]));
+
+    // INT_MIN / -1.
+    x86.regs.edx = 0xFFFF_FFFF;
+    x86.regs.eax = 0x8000_0000;
+    x86.regs.ebx = 0xFFFF_FFFF;
+    // idiv ebx
+    assert_divide_error(try_run(&mut x86, &[0xf7, 0xfb]));
+
+    x86.regs.eax = 0x8000;
+    x86.regs.ebx = 0xFF;
+    // idiv bl
+    assert_divide_error(try_run(&mut x86, &[0xf6, 0xfb]));
+}
+
+#[test]
+fn adc_sbb() {
+    let mut x86 = new_x86();
+    // 64-bit add of 0x1_FFFF_FFFF + 1 in edx:eax.
+    x86.regs.eax = 0xFFFF_FFFF;
+    x86.regs.edx = 1;
+    // add eax,1; adc edx,0
+    run(&mut x86, &[0x83, 0xc0, 0x01, 0x83, 0xd2, 0x00]);
+    assert_eq!(x86.regs.eax, 0);
+    assert_eq!(x86.regs.edx, 2);
+    assert!(!x86.regs.flags.contains(Flags::CF));
+
+    // Carry in with y = 0xFFFF_FFFF carries out.
+    x86.regs.flags.insert(Flags::CF);
+    x86.regs.ecx = 0xFFFF_FFFF;
+    x86.regs.ebx = 5;
+    // adc ebx,ecx
+    run(&mut x86, &[0x11, 0xcb]);
+    assert_eq!(x86.regs.ebx, 5);
+    assert!(x86.regs.flags.contains(Flags::CF));
+
+    // Borrow in with y = 0xFFFF_FFFF borrows out.
+    // sbb ebx,ecx
+    run(&mut x86, &[0x19, 0xcb]);
+    assert_eq!(x86.regs.ebx, 5);
+    assert!(x86.regs.flags.contains(Flags::CF));
+
+    x86.regs.eax = 0xAAAA_AA7F;
+    x86.regs.flags.insert(Flags::CF);
+    // adc al,0
+    run(&mut x86, &[0x14, 0x00]);
+    assert_eq!(x86.regs.eax, 0xAAAA_AA80);
+    assert!(x86.regs.flags.contains(Flags::OF | Flags::SF));
+}
@@ -0,0 +1,166 @@
+//! Instructions using the 0x66 operand-size and 0x67 address-size prefixes.
+
+use super::{new_x86, run};
+use crate::registers::Flags;
+
+#[test]
+fn push_pop16() {
+    let mut x86 = new_x86();
+    x86.regs.ebx = 0xAAAA_AAAA;
+    // pushw -1; pushw 1234h; pop bx
+    run(
+        &mut x86,
+        &[0x66, 0x6a, 0xff, 0x66, 0x68, 0x34, 0x12, 0x66, 0x5b],
+    );
+    assert_eq!(x86.regs.esp, 0x3000 - 2);
+    assert_eq!(x86.read_u16(x86.regs.esp), 0xFFFF);
+    assert_eq!(x86.regs.ebx, 0xAAAA_1234);
+}
+
+#[test]
+fn pop_bp16() {
+    let mut x86 = new_x86();
+    x86.regs.ebp = 0xAAAA_AAAA;
+    // pushw 1234h; pop bp
+    run(&mut x86, &[0x66, 0x68, 0x34, 0x12, 0x66, 0x5d]);
+    assert_eq!(x86.regs.ebp, 0xAAAA_1234);
+}
+
+#[test]
+fn mov16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_AAAA;
+    x86.regs.ebx = 0x2000;
+    // mov ax,1234h; mov word ptr [ebx],5678h; mov ds:[2002h],ax
+    run(
+        &mut x86,
+        &[
+            0x66, 0xb8, 0x34, 0x12, 0x66, 0xc7, 0x03, 0x78, 0x56, 0x66, 0xa3, 0x02, 0x20, 0x00,
+            0x00,
+        ],
+    );
+    assert_eq!(x86.regs.eax, 0xAAAA_1234);
+    assert_eq!(x86.read_u32(0x2000), 0x1234_5678);
+}
+
+#[test]
+fn movzx_movsx16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_AAAA;
+    x86.regs.ebx = 0x0000_80FF;
+    // movsx ax,bl
+    run(&mut x86, &[0x66, 0x0f, 0xbe, 0xc3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_FFFF);
+    // movzx ax,bx
+    run(&mut x86, &[0x66, 0x0f, 0xb7, 0xc3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_80FF);
+}
+
+#[test]
+fn inc_dec16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0x0001_7FFF;
+    x86.regs.flags.insert(Flags::CF);
+    // inc ax
+    run(&mut x86, &[0x66, 0x40]);
+    assert_eq!(x86.regs.eax, 0x0001_8000);
+    assert!(x86.regs.flags.contains(Flags::OF | Flags::SF | Flags::CF));
+
+    x86.regs.ebx = 0x0001_0000;
+    x86.regs.flags.remove(Flags::CF);
+    // dec bx
+    run(&mut x86, &[0x66, 0x4b]);
+    assert_eq!(x86.regs.ebx, 0x0001_FFFF);
+    assert!(!x86.regs.flags.contains(Flags::CF));
+    assert!(x86.regs.flags.contains(Flags::SF));
+}
+
+#[test]
+fn neg_not16() {
+    let mut x86 = new_x86();
+    x86.regs.ebx = 0x1234_0001;
+    // neg bx
+    run(&mut x86, &[0x66, 0xf7, 0xdb]);
+    assert_eq!(x86.regs.ebx, 0x1234_FFFF);
+    assert!(x86.regs.flags.contains(Flags::CF));
+    // not bx
+    run(&mut x86, &[0x66, 0xf7, 0xd3]);
+    assert_eq!(x86.regs.ebx, 0x1234_0000);
+}
+
+#[test]
+fn mul_div16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_1234;
+    x86.regs.ebx = 0x0100;
+    x86.regs.edx = 0xBBBB_BBBB;
+    // mul bx
+    run(&mut x86, &[0x66, 0xf7, 0xe3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_3400);
+    assert_eq!(x86.regs.edx, 0xBBBB_0012);
+    assert!(x86.regs.flags.contains(Flags::CF | Flags::OF));
+
+    // div bx
+    run(&mut x86, &[0x66, 0xf7, 0xf3]);
+    assert_eq!(x86.regs.eax, 0xAAAA_1234);
+    assert_eq!(x86.regs.edx, 0xBBBB_0000);
+}
+
+#[test]
+fn xchg16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_1111;
+    x86.regs.ebx = 0xBBBB_2222;
+    // xchg bx,ax
+    run(&mut x86, &[0x66, 0x93]);
+    assert_eq!(x86.regs.eax, 0xAAAA_2222);
+    assert_eq!(x86.regs.ebx, 0xBBBB_1111);
+}
+
+#[test]
+fn cmpxchg16() {
+    let mut x86 = new_x86();
+    x86.regs.ebx = 0x2000;
+    x86.write_u16(0x2000, 0x1111);
+    x86.regs.eax = 0xAAAA_1111;
+    x86.regs.ecx = 0x2222;
+    // cmpxchg [ebx],cx: matches, so memory is replaced.
+    run(&mut x86, &[0x66, 0x0f, 0xb1, 0x0b]);
+    assert_eq!(x86.read_u16(0x2000), 0x2222);
+    assert!(x86.regs.flags.contains(Flags::ZF));
+
+    // Again: doesn't match, so ax gets the memory value.
+    run(&mut x86, &[0x66, 0x0f, 0xb1, 0x0b]);
+    assert_eq!(x86.read_u16(0x2000), 0x2222);
+    assert_eq!(x86.regs.eax, 0xAAAA_2222);
+    assert!(!x86.regs.flags.contains(Flags::ZF));
+}
+
+#[test]
+fn lea16() {
+    let mut x86 = new_x86();
+    x86.regs.eax = 0xAAAA_AAAA;
+    x86.regs.esi = 0x0001_FFF8;
+    // lea ax,[esi+10h]
+    run(&mut x86, &[0x66, 0x8d, 0x46, 0x10]);
+    assert_eq!(x86.regs.eax, 0xAAAA_0008);
+}
+
+#[test]
+fn addr16() {
+    let mut x86 = new_x86();
+    x86.write_u32(0x2000, 0x1234_5678);
+    // High bits of the address registers are ignored.
+    x86.regs.ebx = 0xFFFF_1FF0;
+    x86.regs.esi = 0xFFFF_0012;
+    // mov eax,[bx+si-2]
+    run(&mut x86, &[0x67, 0x8b, 0x40, 0xfe]);
+    assert_eq!(x86.regs.eax, 0x1234_5678);
+
+    // The offset wraps around at 64k.
+    x86.regs.ebx = 0xFFFF;
+    x86.regs.esi = 0x2001;
+    // lea ax,[bx+si+2] => 0x2002
+    run(&mut x86, &[0x67, 0x66, 0x8d, 0x40, 0x02]);
+    assert_eq!(x86.regs.eax & 0xFFFF, 0x2002);
+}
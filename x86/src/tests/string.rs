@@ -0,0 +1,73 @@
+//! rep movs/stos, whose bulk copies must match stepping one element at a time.
+
+use super::{new_x86, run, try_run};
+
+#[test]
+fn reverse() {
+    let mut x86 = new_x86();
+    x86.mem[0x1000..0x1008].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    x86.regs.esi = 0x1004;
+    x86.regs.edi = 0x2004;
+    x86.regs.ecx = 2;
+    run(&mut x86, &[0xfd, 0xf3, 0xa5]); // std; rep movsd
+    assert_eq!(x86.mem[0x2000..0x2008], [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!((x86.regs.esi, x86.regs.edi), (0x0ffc, 0x1ffc));
+    assert_eq!(x86.regs.ecx, 0);
+
+    x86.regs.eax = 0xAB;
+    x86.regs.edi = 0x2003;
+    x86.regs.ecx = 2;
+    run(&mut x86, &[0xf3, 0xaa]); // rep stosb
+    assert_eq!(x86.mem[0x2000..0x2005], [1, 2, 0xAB, 0xAB, 5]);
+    assert_eq!(x86.regs.edi, 0x2001);
+}
+
+#[test]
+fn overlap() {
+    let mut x86 = new_x86();
+    // A destination just past the source smears the first byte forward.
+    x86.mem[0x1000..0x1005].copy_from_slice(&[7, 1, 2, 3, 4]);
+    x86.regs.esi = 0x1000;
+    x86.regs.edi = 0x1001;
+    x86.regs.ecx = 4;
+    run(&mut x86, &[0xf3, 0xa4]); // rep movsb
+    assert_eq!(x86.mem[0x1000..0x1005], [7; 5]);
+
+    // Backwards, the hazard is a destination just before the source.
+    x86.mem[0x1000..0x1005].copy_from_slice(&[1, 2, 3, 4, 7]);
+    x86.regs.esi = 0x1004;
+    x86.regs.edi = 0x1003;
+    x86.regs.ecx = 3;
+    run(&mut x86, &[0xfd, 0xf3, 0xa4]); // std; rep movsb
+    assert_eq!(x86.mem[0x1000..0x1005], [1, 7, 7, 7, 7]);
+
+    // Overlapping the other way, it's a plain memmove.
+    x86.mem[0x1000..0x1005].copy_from_slice(&[0, 1, 2, 3, 4]);
+    x86.regs.esi = 0x1001;
+    x86.regs.edi = 0x1000;
+    x86.regs.ecx = 4;
+    run(&mut x86, &[0xfc, 0xf3, 0xa4]); // cld; rep movsb
+    assert_eq!(x86.mem[0x1000..0x1005], [1, 2, 3, 4, 4]);
+    assert_eq!((x86.regs.esi, x86.regs.edi), (0x1005, 0x1004));
+}
+
+#[test]
+fn off_end_of_memory() {
+    let mut x86 = new_x86();
+    let end = x86.mem.len() as u32;
+    x86.regs.eax = 0x55;
+    x86.regs.edi = end - 2;
+    x86.regs.ecx = 4;
+    // rep stosb crashes at the first byte past the end, having filled those before.
+    assert!(try_run(&mut x86, &[0xf3, 0xaa]).is_err());
+    assert_eq!(x86.mem[end as usize - 2..], [0x55, 0x55]);
+    assert_eq!((x86.regs.edi, x86.regs.ecx), (end, 2));
+
+    x86.mem[0x1000..0x1004].copy_from_slice(&[1, 2, 3, 4]);
+    x86.regs.esi = 0x1000;
+    x86.regs.edi = end - 2;
+    x86.regs.ecx = 4;
+    assert!(try_run(&mut x86, &[0xf3, 0xa4]).is_err()); // rep movsb
+    assert_eq!(x86.mem[end as usize - 2..], [1, 2]);
+    assert_eq!((x86.regs.esi, x86.regs.edi, x86.regs.ecx), (0x1002, end, 2));
+}
@@ -0,0 +1,180 @@
+use super::new_x86;
+use crate::{debug::Symbols, InstrCache, TraceFilter, TraceFormat, Tracer, TRACE_MAGIC, X86};
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+/// A Write sink whose contents we can inspect after the tracer is done with it.
+#[derive(Clone, Default)]
+struct Sink(Rc<RefCell<Vec<u8>>>);
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+const CODE_ADDR: u32 = 0x1000;
+const CODE: &[u8] = &[
+    0xb8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+    0x50, // push eax
+    0x31, 0xc0, // xor eax, eax
+    0x40, // inc eax
+];
+
+/// Run all of CODE with a tracer attached, returning what it wrote.
+fn trace(format: TraceFormat, filter: TraceFilter) -> Vec<u8> {
+    let mut x86: X86 = new_x86();
+    x86.mem[CODE_ADDR as usize..][..CODE.len()].copy_from_slice(CODE);
+    let mut icache = InstrCache::new();
+    icache.disassemble(CODE, CODE_ADDR);
+    let sink = Sink::default();
+    icache.tracer = Some(Tracer::new(Box::new(sink.clone()), format, filter).unwrap());
+    for _ in 0..4 {
+        assert!(icache.step(&mut x86, &Symbols::default()).unwrap());
+    }
+    let out = sink.0.borrow().clone();
+    out
+}
+
+#[test]
+fn text() {
+    let out = String::from_utf8(trace(TraceFormat::Text, TraceFilter::default())).unwrap();
+    let lines: Vec<Vec<&str>> = out
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], ["0", "00001000", "mov", "eax,5", "eax=00000005"]);
+    assert_eq!(
+        lines[1],
+        [
+            "1",
+            "00001005",
+            "push",
+            "eax",
+            "esp=00002ffc",
+            "[00002ffc]=00000005"
+        ]
+    );
+    assert_eq!(
+        lines[2],
+        [
+            "2",
+            "00001006",
+            "xor",
+            "eax,eax",
+            "eax=00000000",
+            "+PF",
+            "+ZF"
+        ]
+    );
+    assert_eq!(
+        lines[3],
+        ["3", "00001008", "inc", "eax", "eax=00000001", "-PF", "-ZF"]
+    );
+}
+
+#[test]
+fn filters() {
+    let out = String::from_utf8(trace(
+        TraceFormat::Text,
+        TraceFilter {
+            addrs: Some(0x1005..0x1009),
+            skip: 0,
+            limit: Some(2),
+        },
+    ))
+    .unwrap();
+    let ips: Vec<&str> = out.lines().map(|line| &line[..10]).collect();
+    assert_eq!(ips, ["1 00001005", "2 00001006"]);
+
+    let out = String::from_utf8(trace(
+        TraceFormat::Text,
+        TraceFilter {
+            skip: 3,
+            ..Default::default()
+        },
+    ))
+    .unwrap();
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.starts_with("3 00001008"));
+}
+
+#[test]
+fn binary() {
+    let out = trace(
+        TraceFormat::Binary,
+        TraceFilter {
+            addrs: Some(0x1005..0x1006),
+            ..Default::default()
+        },
+    );
+    let (header, rec) = out.split_at(12);
+    assert_eq!(&header[..8], TRACE_MAGIC);
+    #[rustfmt::skip]
+    assert_eq!(rec, [
+        1, 0, 0, 0, 0, 0, 0, 0, // index
+        0x05, 0x10, 0, 0, // eip
+        1, 0x50, // instruction bytes
+        0, // status
+        0x10, 0x00, 0xfc, 0x2f, 0, 0, // changed esp
+        1, 0xfc, 0x2f, 0, 0, 4, 0, 5, 0, 0, 0, // write
+    ]);
+}
+
+#[test]
+fn symbols() {
+    // mov [2000h], eax
+    const STORE: &[u8] = &[0xa3, 0x00, 0x20, 0x00, 0x00];
+    let mut x86: X86 = new_x86();
+    x86.mem[CODE_ADDR as usize..][..STORE.len()].copy_from_slice(STORE);
+    let mut icache = InstrCache::new();
+    icache.disassemble(STORE, CODE_ADDR);
+    let sink = Sink::default();
+    icache.tracer = Some(
+        Tracer::new(
+            Box::new(sink.clone()),
+            TraceFormat::Text,
+            Default::default(),
+        )
+        .unwrap(),
+    );
+    // Names come from the symbols passed to each step, not from when the tracer was attached.
+    let mut symbols = Symbols::default();
+    symbols.insert(0x2000, "counter".into());
+    icache.step(&mut x86, &symbols).unwrap();
+    let out = String::from_utf8(sink.0.borrow().clone()).unwrap();
+    assert!(out.starts_with("0 00001000 mov [counter],eax"), "{out}");
+}
+
+/// A Write sink that always fails.
+struct Broken;
+impl Write for Broken {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("disk full"))
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_failure() {
+    let mut x86: X86 = new_x86();
+    x86.mem[CODE_ADDR as usize..][..CODE.len()].copy_from_slice(CODE);
+    let mut icache = InstrCache::new();
+    icache.disassemble(CODE, CODE_ADDR);
+    icache.tracer =
+        Some(Tracer::new(Box::new(Broken), TraceFormat::Text, Default::default()).unwrap());
+    // Each instruction runs exactly once, despite the trace failing.
+    for _ in 0..4 {
+        assert!(icache.step(&mut x86, &Symbols::default()).unwrap());
+    }
+    assert_eq!((x86.regs.eax, x86.regs.eip), (1, CODE_ADDR + 9));
+    assert_eq!(x86.read_u32(0x2ffc), 5);
+    // The failure is reported on flushing.
+    let mut tracer = icache.tracer.take().unwrap();
+    assert_eq!(tracer.flush().unwrap_err().to_string(), "disk full");
+}
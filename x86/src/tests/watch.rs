@@ -0,0 +1,87 @@
+use super::{new_x86, run};
+use crate::{WatchHit, WatchKind, Watchpoint, X86};
+
+fn watched(addrs: std::ops::Range<u32>, kind: WatchKind) -> X86 {
+    let mut x86 = new_x86();
+    x86.watchpoints.add(Watchpoint { addrs, kind });
+    x86
+}
+
+fn hit(x86: &mut X86) -> Option<(u32, u32, bool)> {
+    x86.watchpoints.take_hit().map(
+        |WatchHit {
+             addr, len, write, ..
+         }| (addr, len, write),
+    )
+}
+
+#[test]
+fn write() {
+    let mut x86 = watched(0x2002..0x2003, WatchKind::Write);
+    x86.regs.ebx = 0x2000;
+    run(&mut x86, &[0x8b, 0x03]); // mov eax, [ebx]
+    assert_eq!(hit(&mut x86), None);
+    run(&mut x86, &[0x89, 0x43, 0x04]); // mov [ebx+4], eax
+    assert_eq!(hit(&mut x86), None);
+    run(&mut x86, &[0x89, 0x03]); // mov [ebx], eax
+    assert_eq!(hit(&mut x86), Some((0x2000, 4, true)));
+    // Only the first hit is kept until taken.
+    assert_eq!(hit(&mut x86), None);
+}
+
+#[test]
+fn read_and_access() {
+    let mut x86 = watched(0x2000..0x2004, WatchKind::Read);
+    x86.regs.ebx = 0x2000;
+    run(&mut x86, &[0x89, 0x03]); // mov [ebx], eax
+    assert_eq!(hit(&mut x86), None);
+    run(&mut x86, &[0x8a, 0x43, 0x03]); // mov al, [ebx+3]
+    assert_eq!(hit(&mut x86), Some((0x2003, 1, false)));
+
+    let mut x86 = watched(0x2000..0x2004, WatchKind::Access);
+    x86.regs.ebx = 0x2000;
+    run(&mut x86, &[0x01, 0x03]); // add [ebx], eax
+    assert_eq!(hit(&mut x86), Some((0x2000, 4, false)));
+}
+
+#[test]
+fn fpu_and_mmx() {
+    let mut x86 = watched(0x2000..0x2008, WatchKind::Access);
+    x86.regs.ebx = 0x2000;
+    run(&mut x86, &[0xdd, 0x03]); // fld qword ptr [ebx]
+    assert_eq!(hit(&mut x86), Some((0x2000, 8, false)));
+    run(&mut x86, &[0xdd, 0x1b]); // fstp qword ptr [ebx]
+    assert_eq!(hit(&mut x86), Some((0x2000, 8, true)));
+    run(&mut x86, &[0x0f, 0xef, 0x03]); // pxor mm0, [ebx]
+    assert_eq!(hit(&mut x86), Some((0x2000, 8, false)));
+}
+
+#[test]
+fn string_ops() {
+    let mut x86 = watched(0x2010..0x2011, WatchKind::Write);
+    x86.regs.edi = 0x2000;
+    x86.regs.ecx = 0x20;
+    run(&mut x86, &[0xf3, 0xaa]); // rep stosb
+    assert_eq!(hit(&mut x86), Some((0x2000, 0x20, true)));
+
+    let mut x86 = watched(0x2010..0x2011, WatchKind::Read);
+    x86.regs.esi = 0x2010;
+    x86.regs.edi = 0x2100;
+    run(&mut x86, &[0xa5]); // movsd
+    assert_eq!(hit(&mut x86), Some((0x2010, 4, false)));
+}
+
+#[test]
+fn remove() {
+    let watchpoint = Watchpoint {
+        addrs: 0x2000..0x2004,
+        kind: WatchKind::Write,
+    };
+    let mut x86 = new_x86();
+    x86.watchpoints.add(watchpoint.clone());
+    assert!(x86.watchpoints.remove(&watchpoint));
+    assert!(!x86.watchpoints.remove(&watchpoint));
+    x86.regs.ebx = 0x2000;
+    run(&mut x86, &[0x89, 0x03]); // mov [ebx], eax
+    assert_eq!(hit(&mut x86), None);
+}
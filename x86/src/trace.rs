@@ -0,0 +1,283 @@
+//! Instruction execution traces, for diffing runs between emulator versions.
+//!
+//! A Tracer attached to an InstrCache records each executed instruction along
+//! with the registers, flags and memory it changed.  Memory writes are found
+//! from the instruction's explicit and implicit memory operands (as reported by
+//! iced), so rep-prefixed string ops only report their first element.
+
+use crate::{debug::Symbols, registers::Flags, StepError, StepResult, X86};
+use iced_x86::{InstructionInfoFactory, OpAccess, Register};
+use std::io::Write;
+
+/// Magic and version at the start of a binary trace.
+pub const TRACE_MAGIC: &[u8; 8] = b"R32TRACE";
+pub const TRACE_VERSION: u32 = 1;
+
+const REG_NAMES: [&str; 8] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"];
+const FLAG_NAMES: [(Flags, &str); 7] = [
+    (Flags::CF, "CF"),
+    (Flags::PF, "PF"),
+    (Flags::AF, "AF"),
+    (Flags::ZF, "ZF"),
+    (Flags::SF, "SF"),
+    (Flags::DF, "DF"),
+    (Flags::OF, "OF"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line of text per instruction.
+    Text,
+    /// Compact little-endian records after a TRACE_MAGIC/TRACE_VERSION header:
+    ///   u64 index, u32 eip, u8 len + instruction bytes, u8 status (0 ok, 1 interrupt, 2 error),
+    ///   u16 changed mask (bits 0-7 GPRs in eax..edi order, bit 8 eflags) + u32 per set bit,
+    ///   u8 write count + (u32 addr, u16 len, bytes) per write.
+    Binary,
+}
+
+/// Which executed instructions get recorded.
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    /// Only record instructions whose address falls in this range.
+    pub addrs: Option<std::ops::Range<u32>>,
+    /// Don't record the first `skip` executed instructions.
+    pub skip: u64,
+    /// Stop recording after this many instructions have been recorded.
+    pub limit: Option<u64>,
+}
+
+/// Machine state captured before an instruction runs.
+pub struct Pending {
+    index: u64,
+    ip: u32,
+    instr: iced_x86::Instruction,
+    regs: [u32; 9],
+    /// (addr, len) of memory the instruction may write.
+    writes: Vec<(u32, u32)>,
+}
+
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    /// Instructions executed since the tracer was attached, recorded or not.
+    executed: u64,
+    recorded: u64,
+    info: InstructionInfoFactory,
+    /// The first failure writing the trace, after which nothing more is written.
+    /// The instruction being traced has already run by then, so rather than failing
+    /// the step this is reported by flush().
+    error: Option<std::io::Error>,
+}
+
+fn reg_state(x86: &X86) -> [u32; 9] {
+    let r = &x86.regs;
+    [
+        r.eax,
+        r.ecx,
+        r.edx,
+        r.ebx,
+        r.esp,
+        r.ebp,
+        r.esi,
+        r.edi,
+        r.flags.bits(),
+    ]
+}
+
+impl Tracer {
+    pub fn new(
+        mut out: Box<dyn Write>,
+        format: TraceFormat,
+        filter: TraceFilter,
+    ) -> std::io::Result<Self> {
+        if format == TraceFormat::Binary {
+            out.write_all(TRACE_MAGIC)?;
+            out.write_all(&TRACE_VERSION.to_le_bytes())?;
+        }
+        Ok(Tracer {
+            out,
+            format,
+            filter,
+            executed: 0,
+            recorded: 0,
+            info: InstructionInfoFactory::new(),
+            error: None,
+        })
+    }
+
+    /// Flush the output, first reporting any earlier write failure.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+
+    /// Called before executing instr; returns the state to diff against if the
+    /// instruction passes the filters.
+    pub fn before(&mut self, x86: &X86, ip: u32, instr: &iced_x86::Instruction) -> Option<Pending> {
+        let index = self.executed;
+        self.executed += 1;
+        if index < self.filter.skip {
+            return None;
+        }
+        if let Some(limit) = self.filter.limit {
+            if self.recorded >= limit {
+                return None;
+            }
+        }
+        if let Some(addrs) = &self.filter.addrs {
+            if !addrs.contains(&ip) {
+                return None;
+            }
+        }
+        self.recorded += 1;
+
+        let regs = &x86.regs;
+        let get_reg = |reg: Register, _elem: usize, _size: usize| -> Option<u64> {
+            Some(match reg {
+                Register::FS => regs.fs_addr as u64,
+                _ if reg.is_segment_register() => 0,
+                _ if reg.is_gpr32() => regs.get32(reg) as u64,
+                _ if reg.is_gpr16() => regs.get16(reg) as u64,
+                _ if reg.is_gpr8() => regs.get8(reg) as u64,
+                _ => return None,
+            })
+        };
+        let mut writes = Vec::new();
+        for mem in self.info.info(instr).used_memory() {
+            if !matches!(
+                mem.access(),
+                OpAccess::Write
+                    | OpAccess::CondWrite
+                    | OpAccess::ReadWrite
+                    | OpAccess::ReadCondWrite
+            ) {
+                continue;
+            }
+            if let Some(addr) = mem.virtual_address(0, get_reg) {
+                writes.push((addr as u32, mem.memory_size().size() as u32));
+            }
+        }
+
+        Some(Pending {
+            index,
+            ip,
+            instr: *instr,
+            regs: reg_state(x86),
+            writes,
+        })
+    }
+
+    /// Called after executing the instruction that produced pending.
+    /// symbols names addresses in text traces.
+    pub fn after(
+        &mut self,
+        x86: &X86,
+        symbols: &Symbols,
+        pending: Pending,
+        result: &StepResult<()>,
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(err) = self.record(x86, symbols, pending, result) {
+            log::error!("trace: {err}; no longer tracing");
+            self.error = Some(err);
+        }
+    }
+
+    fn record(
+        &mut self,
+        x86: &X86,
+        symbols: &Symbols,
+        pending: Pending,
+        result: &StepResult<()>,
+    ) -> std::io::Result<()> {
+        let regs = reg_state(x86);
+        let bytes = x86
+            .mem
+            .get(pending.ip as usize..pending.ip as usize + pending.instr.len())
+            .unwrap_or(&[]);
+        let writes = pending
+            .writes
+            .iter()
+            .filter(|&&(addr, len)| len > 0 && x86.in_bounds(addr, len))
+            .map(|&(addr, len)| (addr, &x86.mem[addr as usize..(addr + len) as usize]));
+
+        match self.format {
+            TraceFormat::Text => {
+                let mut line = format!(
+                    "{} {:08x} {:32}",
+                    pending.index,
+                    pending.ip,
+                    crate::debug::format_instr(&pending.instr, symbols)
+                );
+                for (i, name) in REG_NAMES.iter().enumerate() {
+                    if regs[i] != pending.regs[i] {
+                        line.push_str(&format!(" {name}={:08x}", regs[i]));
+                    }
+                }
+                let (old, new) = (
+                    Flags::from_bits_truncate(pending.regs[8]),
+                    Flags::from_bits_truncate(regs[8]),
+                );
+                for (flag, name) in FLAG_NAMES {
+                    if old.contains(flag) != new.contains(flag) {
+                        let sign = if new.contains(flag) { '+' } else { '-' };
+                        line.push_str(&format!(" {sign}{name}"));
+                    }
+                }
+                for (addr, data) in writes {
+                    line.push_str(&format!(" [{addr:08x}]="));
+                    if matches!(data.len(), 1 | 2 | 4 | 8) {
+                        let mut value = [0u8; 8];
+                        value[..data.len()].copy_from_slice(data);
+                        let width = data.len() * 2;
+                        line.push_str(&format!("{:0width$x}", u64::from_le_bytes(value)));
+                    } else {
+                        for b in data {
+                            line.push_str(&format!("{b:02x}"));
+                        }
+                    }
+                }
+                match result {
+                    Ok(()) => {}
+                    Err(StepError::Interrupt) => line.push_str(" !interrupt"),
+                    Err(StepError::Error(err)) => line.push_str(&format!(" !{err}")),
+                }
+                writeln!(self.out, "{line}")
+            }
+            TraceFormat::Binary => {
+                let mut rec = Vec::with_capacity(64);
+                rec.extend_from_slice(&pending.index.to_le_bytes());
+                rec.extend_from_slice(&pending.ip.to_le_bytes());
+                rec.push(bytes.len() as u8);
+                rec.extend_from_slice(bytes);
+                rec.push(match result {
+                    Ok(()) => 0,
+                    Err(StepError::Interrupt) => 1,
+                    Err(StepError::Error(_)) => 2,
+                });
+                let mask = (0..9).fold(0u16, |mask, i| {
+                    mask | (((regs[i] != pending.regs[i]) as u16) << i)
+                });
+                rec.extend_from_slice(&mask.to_le_bytes());
+                for (i, reg) in regs.iter().enumerate() {
+                    if mask & (1 << i) != 0 {
+                        rec.extend_from_slice(&reg.to_le_bytes());
+                    }
+                }
+                let writes: Vec<_> = writes.take(u8::MAX as usize).collect();
+                rec.push(writes.len() as u8);
+                for (addr, data) in writes {
+                    rec.extend_from_slice(&addr.to_le_bytes());
+                    rec.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                    rec.extend_from_slice(data);
+                }
+                self.out.write_all(&rec)
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Memory watchpoints: stop when guest code reads or writes an address range.
+//!
+//! Accesses are checked as they happen, but a hit doesn't interrupt the
+//! instruction; it's recorded and the caller picks it up via take_hit() once
+//! the instruction completes, like the data breakpoints of real hardware.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Either a read or a write.
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Watchpoint {
+    pub addrs: std::ops::Range<u32>,
+    pub kind: WatchKind,
+}
+
+/// A memory access that tripped a watchpoint.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    /// The span of the access, which may be larger than the watched range
+    /// (e.g. for rep-prefixed string ops, which are checked as a whole).
+    pub addr: u32,
+    pub len: u32,
+    pub write: bool,
+}
+
+#[derive(Default)]
+pub struct Watchpoints {
+    list: Vec<Watchpoint>,
+    /// The first hit since the last take_hit().
+    hit: Option<WatchHit>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.list.push(watchpoint);
+    }
+
+    /// Remove a watchpoint previously add()ed, returning false if it wasn't found.
+    pub fn remove(&mut self, watchpoint: &Watchpoint) -> bool {
+        match self.list.iter().position(|wp| wp == watchpoint) {
+            Some(index) => {
+                self.list.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> &[Watchpoint] {
+        &self.list
+    }
+
+    pub fn take_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+
+    /// Note an access of len bytes at addr.
+    #[inline]
+    pub(crate) fn check(&mut self, addr: u32, len: u32, write: bool) {
+        if self.list.is_empty() || self.hit.is_some() {
+            return;
+        }
+        self.check_slow(addr, len, write);
+    }
+
+    fn check_slow(&mut self, addr: u32, len: u32, write: bool) {
+        let end = addr as u64 + len as u64;
+        let found = self.list.iter().find(|wp| {
+            wp.kind.matches(write)
+                && (addr as u64) < wp.addrs.end as u64
+                && (wp.addrs.start as u64) < end
+        });
+        if let Some(wp) = found {
+            self.hit = Some(WatchHit {
+                watchpoint: wp.clone(),
+                addr,
+                len,
+                write,
+            });
+        }
+    }
+}
@@ -1,6 +1,15 @@
 //! The central x86 machine object.
 
-use crate::{memory::Memory, ops, registers::Registers, StepError, StepResult};
+use crate::{
+    debug::Symbols,
+    expr::{Expr, Template},
+    memory::Memory,
+    ops,
+    registers::Registers,
+    trace::Tracer,
+    watch::Watchpoints,
+    StepError, StepResult,
+};
 use serde::ser::SerializeStruct;
 use std::collections::HashMap;
 
@@ -11,6 +20,7 @@ pub const NULL_POINTER_REGION_SIZE: u32 = 0x1000;
 pub struct X86 {
     pub mem: Vec<u8>,
     pub regs: Registers,
+    pub watchpoints: Watchpoints,
     /// Toggled on by breakpoints/process exit.
     // TODO: this is gross, because we must check it after every instruction.
     // It would be nice if there was some more clever way to thread process exit...
@@ -32,6 +42,7 @@ impl X86 {
         X86 {
             mem: Vec::new(),
             regs,
+            watchpoints: Watchpoints::default(),
             stopped: false,
             crashed: None,
         }
@@ -48,27 +59,46 @@ impl X86 {
 
     /// Check whether reading a T from mem[addr] would cause OOB, and crash() if so.
     fn check_oob<T>(&mut self, addr: u32) -> bool {
+        self.check_oob_len(addr, std::mem::size_of::<T>() as u32)
+    }
+
+    /// Check whether accessing len bytes at mem[addr] would cause OOB, and crash() if so.
+    pub(crate) fn check_oob_len(&mut self, addr: u32, len: u32) -> bool {
         if addr < NULL_POINTER_REGION_SIZE {
             self.crash(format!("crash: null pointer at {addr:#x}"));
             return true;
         }
-        if addr as usize + std::mem::size_of::<T>() > self.mem.len() {
+        if !self.in_bounds(addr, len) {
             self.crash(format!("crash: oob pointer at {addr:#x}"));
             return true;
         }
         false
     }
 
+    /// Whether len bytes at mem[addr] are accessible, without crashing if not.
+    pub(crate) fn in_bounds(&self, addr: u32, len: u32) -> bool {
+        addr >= NULL_POINTER_REGION_SIZE && addr as usize + len as usize <= self.mem.len()
+    }
+
+    pub fn write_u64(&mut self, addr: u32, value: u64) {
+        if self.check_oob::<u64>(addr) {
+            return;
+        }
+        self.watchpoints.check(addr, 8, true);
+        *self.mem.view_mut::<u64>(addr) = value;
+    }
     pub fn write_u32(&mut self, addr: u32, value: u32) {
         if self.check_oob::<u32>(addr) {
             return;
         }
+        self.watchpoints.check(addr, 4, true);
         self.mem.write_u32(addr, value);
     }
     pub fn write_u16(&mut self, addr: u32, value: u16) {
         if self.check_oob::<u16>(addr) {
             return;
         }
+        self.watchpoints.check(addr, 2, true);
         let addr = addr as usize;
         // Safety: check_oob checked bounds.
         unsafe {
@@ -80,20 +110,30 @@ impl X86 {
         if self.check_oob::<u8>(addr) {
             return;
         }
+        self.watchpoints.check(addr, 1, true);
         // Safety: check_oob checked bounds.
         unsafe { *self.mem.get_unchecked_mut(addr as usize) = value }
     }
 
+    pub fn read_u64(&mut self, addr: u32) -> u64 {
+        if self.check_oob::<u64>(addr) {
+            return 0;
+        }
+        self.watchpoints.check(addr, 8, false);
+        *self.mem.view::<u64>(addr)
+    }
     pub fn read_u32(&mut self, addr: u32) -> u32 {
         if self.check_oob::<u32>(addr) {
             return 0;
         }
+        self.watchpoints.check(addr, 4, false);
         self.mem.read_u32(addr)
     }
     pub fn read_u16(&mut self, addr: u32) -> u16 {
         if self.check_oob::<u16>(addr) {
             return 0;
         }
+        self.watchpoints.check(addr, 2, false);
         let offset = addr as usize;
         // Safety: check_oob checked bounds.
         unsafe {
@@ -105,6 +145,7 @@ impl X86 {
         if self.check_oob::<u8>(addr) {
             return 0;
         }
+        self.watchpoints.check(addr, 1, false);
         // Safety: check_oob checked bounds.
         unsafe { *self.mem.get_unchecked(addr as usize) }
     }
@@ -123,6 +164,14 @@ impl X86 {
         Ok(())
     }
 
+    /// Capture memory and registers, for a later load_snapshot().
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            mem: self.mem.clone(),
+            regs: self.regs.clone(),
+        }
+    }
+
     pub fn load_snapshot(&mut self, snap: Snapshot) {
         self.mem = snap.mem;
         self.regs = snap.regs;
@@ -135,16 +184,17 @@ impl serde::Serialize for X86 {
         serializer: S,
     ) -> std::result::Result<S::Ok, S::Error> {
         let mut state = serializer.serialize_struct("X86", 2)?;
-        // TODO: serialize remaining state.
+        // The other fields are debugger configuration or only meaningful mid-instruction.
         state.serialize_field("mem", serde_bytes::Bytes::new(&self.mem))?;
         state.serialize_field("regs", &self.regs)?;
         state.end()
     }
 }
 
+#[derive(Clone)]
 pub struct Snapshot {
-    mem: Vec<u8>,
-    regs: Registers,
+    pub mem: Vec<u8>,
+    pub regs: Registers,
 }
 
 impl<'de> serde::Deserialize<'de> for Snapshot {
@@ -178,6 +228,55 @@ impl<'de> serde::Deserialize<'de> for Snapshot {
     }
 }
 
+/// An execution breakpoint.  The default value stops every time it's reached.
+#[derive(Debug, Default)]
+pub struct Breakpoint {
+    /// If set, the breakpoint only counts as hit when this evaluates to nonzero.
+    pub condition: Option<Expr>,
+    /// Number of hits to pass over before stopping.
+    pub ignore_count: u32,
+    /// If set, this is a tracepoint: hits log this message and execution continues.
+    pub log: Option<Template>,
+    /// Number of times the breakpoint has been hit.
+    pub hits: u32,
+}
+
+impl Breakpoint {
+    /// Record reaching the breakpoint at addr, returning whether execution should stop.
+    fn reached(&mut self, x86: &X86, addr: u32) -> bool {
+        if let Some(condition) = &self.condition {
+            match condition.eval(x86) {
+                Ok(0) => return false,
+                Ok(_) => {}
+                Err(err) => {
+                    log::warn!("breakpoint {addr:x}: condition {condition}: {err}");
+                    return true;
+                }
+            }
+        }
+        self.hits += 1;
+        if self.hits <= self.ignore_count {
+            return false;
+        }
+        match &self.log {
+            Some(log) => {
+                log::info!("tracepoint {addr:x}: {}", log.render(x86));
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+/// An int3 to patch in over the instruction at addr.
+fn int3(addr: u32) -> iced_x86::Instruction {
+    let mut int3 = iced_x86::Instruction::with(iced_x86::Code::Int3);
+    // The instruction needs a length/next_ip so the execution machinery doesn't lose its location.
+    int3.set_len(1);
+    int3.set_next_ip(addr as u64 + 1);
+    int3
+}
+
 /// Cache of decoded instructions.
 /// This also caches the current instruction index, so that we don't need to map
 /// x86 eip addresses to the instruction cache entry.  Instead, whenever we step
@@ -187,12 +286,17 @@ pub struct InstrCache {
     pub instrs: Vec<(u32, iced_x86::Instruction)>,
     /// Current position within instrs.
     pub index: usize,
-    /// Span of addresses that refer to code, for error checking
-    pub span: std::ops::Range<u32>,
+    /// Spans of addresses that refer to code, for error checking
+    pub spans: Vec<std::ops::Range<u32>>,
 
     /// Places where we've patched out the instruction with an int3.
     /// The map values are the instruction from before the breakpoint.
-    breakpoints: HashMap<u32, iced_x86::Instruction>,
+    breakpoints: HashMap<u32, (iced_x86::Instruction, Breakpoint)>,
+    /// Set by resume() to run through a breakpoint at the current position.
+    resuming: bool,
+
+    /// If set, records each executed instruction.
+    pub tracer: Option<Tracer>,
 }
 
 impl InstrCache {
@@ -200,19 +304,46 @@ impl InstrCache {
         InstrCache {
             instrs: Vec::new(),
             index: 0,
-            span: 0..0,
+            spans: Vec::new(),
             breakpoints: HashMap::new(),
+            resuming: false,
+            tracer: None,
         }
     }
 
     pub fn disassemble(&mut self, buf: &[u8], ip: u32) {
+        self.disassemble_spans(&[(buf, ip)]);
+    }
+
+    /// Like disassemble(), but for several separate spans of code, e.g. the code
+    /// sections of an exe and the DLLs it loaded.
+    pub fn disassemble_spans(&mut self, spans: &[(&[u8], u32)]) {
         self.instrs.clear();
-        let mut decoder =
-            iced_x86::Decoder::with_ip(32, buf, ip as u64, iced_x86::DecoderOptions::NONE);
-        while decoder.can_decode() {
-            self.instrs.push((decoder.ip() as u32, decoder.decode()));
+        self.spans.clear();
+        for &(buf, ip) in spans {
+            let mut decoder =
+                iced_x86::Decoder::with_ip(32, buf, ip as u64, iced_x86::DecoderOptions::NONE);
+            while decoder.can_decode() {
+                self.instrs.push((decoder.ip() as u32, decoder.decode()));
+            }
+            self.spans.push(ip..decoder.ip() as u32);
+        }
+        self.instrs.sort_by_key(|&(ip, _)| ip);
+
+        // Reapply breakpoints over the freshly decoded instructions.
+        let addrs: Vec<u32> = self.breakpoints.keys().copied().collect();
+        for addr in addrs {
+            match self.instrs.binary_search_by_key(&addr, |&(ip, _)| ip) {
+                Ok(index) => {
+                    let prev = std::mem::replace(&mut self.instrs[index].1, int3(addr));
+                    self.breakpoints.get_mut(&addr).unwrap().0 = prev;
+                }
+                Err(_) => {
+                    log::warn!("dropping breakpoint at {addr:x}, no longer an instruction");
+                    self.breakpoints.remove(&addr);
+                }
+            }
         }
-        self.span = ip..decoder.ip() as u32;
     }
 
     /// Given an IP that wasn't found in the decoded instructions, re-decode starting at that
@@ -270,47 +401,89 @@ impl InstrCache {
     }
 
     pub fn jmp(&mut self, mem: &[u8], target_ip: u32) -> StepResult<()> {
-        if !self.span.contains(&target_ip) {
+        if !self.spans.iter().any(|span| span.contains(&target_ip)) {
             return Err(StepError::Error(format!(
-                "jmp to {target_ip:x} outside of code segment {:x?}",
-                self.span
+                "jmp to {target_ip:x} outside of code segments {:x?}",
+                self.spans
             )));
         }
         self.index = self.ip_to_instr_index(mem, target_ip).unwrap();
         Ok(())
     }
 
-    /// Replace the instruction found at a given ip, returning the previous instruction.
-    fn patch(&mut self, addr: u32, instr: iced_x86::Instruction) -> iced_x86::Instruction {
-        let index = self.ip_to_instr_index(&[], addr).expect("couldn't map ip");
-        std::mem::replace(&mut self.instrs[index].1, instr)
+    /// Replace the instruction found at a given ip, returning the previous instruction,
+    /// or None if no decoded instruction starts there.
+    fn patch(&mut self, addr: u32, instr: iced_x86::Instruction) -> Option<iced_x86::Instruction> {
+        let index = self
+            .instrs
+            .binary_search_by_key(&addr, |&(ip, _)| ip)
+            .ok()?;
+        Some(std::mem::replace(&mut self.instrs[index].1, instr))
     }
 
     /// Patch in an int3 over the instruction at that addr, backing up the current one.
-    pub fn add_breakpoint(&mut self, addr: u32) {
-        let mut int3 = iced_x86::Instruction::with(iced_x86::Code::Int3);
-        // The instruction needs a length/next_ip so the execution machinery doesn't lose its location.
-        int3.set_len(1);
-        int3.set_next_ip(addr as u64 + 1);
-        let prev = self.patch(addr, int3);
-        self.breakpoints.insert(addr, prev);
-    }
-
-    /// Undo an add_breakpoint().
-    pub fn clear_breakpoint(&mut self, addr: u32) {
-        let prev = self.breakpoints.remove(&addr).unwrap();
+    /// Replaces any existing breakpoint at addr.  Returns false if addr isn't the start
+    /// of a decoded instruction, e.g. if it's mid-instruction or in data.
+    pub fn add_breakpoint(&mut self, addr: u32, bp: Breakpoint) -> bool {
+        if let Some(entry) = self.breakpoints.get_mut(&addr) {
+            entry.1 = bp;
+            return true;
+        }
+        let Some(prev) = self.patch(addr, int3(addr)) else {
+            return false;
+        };
+        self.breakpoints.insert(addr, (prev, bp));
+        true
+    }
+
+    /// Undo an add_breakpoint(), returning false if there was no breakpoint at addr.
+    pub fn clear_breakpoint(&mut self, addr: u32) -> bool {
+        let Some((prev, _)) = self.breakpoints.remove(&addr) else {
+            return false;
+        };
         self.patch(addr, prev);
+        true
+    }
+
+    pub fn breakpoint(&self, addr: u32) -> Option<&Breakpoint> {
+        self.breakpoints.get(&addr).map(|(_, bp)| bp)
+    }
+
+    /// Make the next step() execute the instruction under a breakpoint at the
+    /// current position, rather than stopping there again.
+    pub fn resume(&mut self) {
+        self.resuming = true;
     }
 
     /// Executes the current instruction, updating eip.
     /// Returns Ok(false) if we jumped, Ok(true) if we single-stepped.
     /// Caller must call self.jmp() in the jump case.
-    pub fn step(&mut self, x86: &mut X86) -> StepResult<bool> {
+    /// symbols names addresses in the attached tracer's output, if any.
+    pub fn step(&mut self, x86: &mut X86, symbols: &Symbols) -> StepResult<bool> {
         let (prev_ip, ref instr) = self.instrs[self.index];
+        let mut instr = instr;
+        let resuming = std::mem::take(&mut self.resuming);
+        if instr.code() == iced_x86::Code::Int3 {
+            if let Some((prev, bp)) = self.breakpoints.get_mut(&prev_ip) {
+                if resuming || !bp.reached(x86, prev_ip) {
+                    // Run the patched-out instruction as if the breakpoint weren't there.
+                    instr = prev;
+                }
+            }
+        }
         let next_ip = instr.next_ip() as u32;
         // Need to update eip before executing because instructions like 'call' will push eip onto the stack.
         x86.regs.eip = next_ip;
-        match x86.run(instr) {
+        let pending = match &mut self.tracer {
+            Some(tracer) => tracer.before(x86, prev_ip, instr),
+            None => None,
+        };
+        let result = x86.run(instr);
+        if let Some(pending) = pending {
+            let tracer = self.tracer.as_mut().unwrap();
+            tracer.after(x86, symbols, pending, &result);
+        }
+        match result {
             Err(err) => {
                 // Point the debugger at the failed instruction.
                 x86.regs.eip = prev_ip;